@@ -0,0 +1,268 @@
+use crate::error::{BackendError, ErrorKind};
+use crate::logger::{log_debug, log_warning};
+use crate::runner::CommandSpec;
+use crate::settings;
+use serde::Deserialize;
+
+pub const DEFAULT_AUR_URL: &str = "https://aur.archlinux.org";
+
+/// The RPC rejects requests whose URI grows past ~4k characters; stay well below.
+const MULTIINFO_CHUNK: usize = 100;
+const REQUEST_TIMEOUT_SECS: &str = "15";
+
+/// One package as returned by the AUR RPC (v5) `info` and `search` endpoints.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AurPackage {
+    pub name: String,
+    pub package_base: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, rename = "URL")]
+    pub url: Option<String>,
+    /// `None` for orphaned packages.
+    #[serde(default)]
+    pub maintainer: Option<String>,
+    #[serde(default)]
+    pub num_votes: u64,
+    #[serde(default)]
+    pub popularity: f64,
+    /// Unix timestamp of when the package was flagged out of date.
+    #[serde(default)]
+    pub out_of_date: Option<i64>,
+    #[serde(default)]
+    pub first_submitted: i64,
+    #[serde(default)]
+    pub last_modified: i64,
+    /// Only filled by `info` lookups; search results leave these empty.
+    #[serde(default)]
+    pub depends: Vec<String>,
+    #[serde(default)]
+    pub make_depends: Vec<String>,
+    #[serde(default)]
+    pub check_depends: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    results: Vec<AurPackage>,
+}
+
+/// Client for the AUR web RPC. Requests go through `curl` like the rest of the
+/// backend's network access, so they honour the user's proxy settings.
+#[derive(Debug, Clone)]
+pub struct AurClient {
+    base_url: String,
+}
+
+impl AurClient {
+    pub fn new(base_url: &str) -> Self {
+        let base_url = base_url.trim().trim_end_matches('/');
+        Self {
+            base_url: if base_url.is_empty() {
+                DEFAULT_AUR_URL.to_string()
+            } else {
+                base_url.to_string()
+            },
+        }
+    }
+
+    /// A client for the server configured in settings (`aur_rpc_base_url`).
+    pub fn from_settings() -> Self {
+        Self::new(&settings::get().aur_rpc_base_url)
+    }
+
+    pub fn package_url(&self, name: &str) -> String {
+        format!("{}/packages/{}/", self.base_url, name)
+    }
+
+    pub fn pkgbase_url(&self, package_base: &str) -> String {
+        format!("{}/pkgbase/{}/", self.base_url, package_base)
+    }
+
+    pub fn git_url(&self, package_base: &str) -> String {
+        format!("{}/{}.git", self.base_url, package_base)
+    }
+
+    /// Look up a single package; `Ok(None)` when the AUR does not know it.
+    pub fn info(&self, name: &str) -> Result<Option<AurPackage>, BackendError> {
+        Ok(self.multiinfo(&[name])?.into_iter().next())
+    }
+
+    /// Look up several packages at once. Unknown names are simply absent.
+    pub fn multiinfo(&self, names: &[&str]) -> Result<Vec<AurPackage>, BackendError> {
+        let mut packages = Vec::new();
+        for chunk in names.chunks(MULTIINFO_CHUNK) {
+            let mut params = vec![("v", "5"), ("type", "info")];
+            params.extend(chunk.iter().map(|name| ("arg[]", *name)));
+            packages.extend(self.rpc(&params)?);
+        }
+        Ok(packages)
+    }
+
+    /// Packages whose name or description contains `term`.
+    pub fn search(&self, term: &str) -> Result<Vec<AurPackage>, BackendError> {
+        self.rpc(&[
+            ("v", "5"),
+            ("type", "search"),
+            ("by", "name-desc"),
+            ("arg", term),
+        ])
+    }
+
+    /// Fetch an HTML page from the AUR web interface (comments are not in the RPC).
+    pub fn fetch_page(&self, url: &str) -> Result<String, BackendError> {
+        let spec = CommandSpec::new("curl")
+            .args(["-fsSL", "--max-time", REQUEST_TIMEOUT_SECS])
+            .arg(url);
+        let output = spec
+            .output()
+            .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
+        if !output.status.success() {
+            return Err(BackendError::from_output(
+                &spec,
+                &output,
+                format!("Failed to fetch {}: HTTP error", url),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn rpc(&self, params: &[(&str, &str)]) -> Result<Vec<AurPackage>, BackendError> {
+        let mut spec = CommandSpec::new("curl")
+            .args(["-fsSL", "--max-time", REQUEST_TIMEOUT_SECS, "-G"])
+            .arg(format!("{}/rpc/", self.base_url));
+        for (key, value) in params {
+            spec = spec
+                .arg("--data-urlencode")
+                .arg(format!("{}={}", key, value));
+        }
+        log_debug(&format!("AUR RPC: {}", spec.command_line()));
+
+        let output = spec
+            .output()
+            .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
+        if !output.status.success() {
+            let message = format!(
+                "AUR RPC request failed (curl exit code {})",
+                output.status.code().unwrap_or(-1)
+            );
+            log_warning(&message);
+            return Err(BackendError::from_output(&spec, &output, message));
+        }
+        parse_response(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Decode an RPC reply, turning `"type": "error"` replies into `Err`.
+pub fn parse_response(body: &str) -> Result<Vec<AurPackage>, BackendError> {
+    let response: RpcResponse = serde_json::from_str(body).map_err(|e| {
+        BackendError::new(ErrorKind::Other, format!("Invalid AUR RPC response: {}", e))
+    })?;
+    if response.kind == "error" {
+        return Err(BackendError::new(
+            ErrorKind::Other,
+            format!(
+                "AUR RPC error: {}",
+                response
+                    .error
+                    .unwrap_or_else(|| "unknown error".to_string())
+            ),
+        ));
+    }
+    Ok(response.results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AurClient, parse_response};
+    use crate::runner;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    const INFO_PARU: &str = r#"{"version":5,"type":"multiinfo","resultcount":1,"results":[{"ID":1,"Name":"paru","PackageBaseID":2,"PackageBase":"paru","Version":"2.1.0-1","Description":"Feature packed AUR helper","URL":"https://github.com/morganamilo/paru","NumVotes":512,"Popularity":21.5,"OutOfDate":null,"Maintainer":"Morganamilo","FirstSubmitted":1600000000,"LastModified":1720000000,"URLPath":"/cgit/aur.git/snapshot/paru.tar.gz","Depends":["git","pacman"]}]}"#;
+    const SEARCH_GIT: &str = r#"{"version":5,"type":"search","resultcount":2,"results":[{"Name":"paru-git","PackageBase":"paru-git","Version":"2.1.0.r5-1","Description":null,"NumVotes":40,"Popularity":0.5,"OutOfDate":1725000000,"Maintainer":null,"LastModified":1721000000},{"Name":"paru-bin","PackageBase":"paru-bin","Version":"2.1.0-1","Description":"Prebuilt paru","NumVotes":90,"Popularity":2.0,"OutOfDate":null,"Maintainer":"someone","LastModified":1722000000}]}"#;
+    const ERROR_REPLY: &str = r#"{"version":5,"type":"error","resultcount":0,"results":[],"error":"Too many package results."}"#;
+
+    /// Serve canned replies over HTTP on 127.0.0.1, one per connection,
+    /// recording each request line.
+    fn stub_server(replies: Vec<&'static str>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind stub server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        thread::spawn(move || {
+            for body in replies {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                let _ = reader.read_line(&mut request_line);
+                let mut header = String::new();
+                while reader.read_line(&mut header).is_ok_and(|n| n > 2) {
+                    header.clear();
+                }
+                seen.lock().unwrap().push(request_line.trim().to_string());
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+        (url, requests)
+    }
+
+    #[test]
+    fn parses_typed_metadata_and_errors() {
+        let results = parse_response(SEARCH_GIT).expect("parse search reply");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].maintainer, None);
+        assert_eq!(results[0].out_of_date, Some(1_725_000_000));
+        assert_eq!(results[0].description, None);
+        assert_eq!(results[1].num_votes, 90);
+
+        let err = parse_response(ERROR_REPLY).unwrap_err();
+        assert!(err.to_string().contains("Too many package results"));
+        assert!(parse_response("<html>").is_err());
+    }
+
+    #[test]
+    fn talks_to_configured_server() {
+        if !runner::command_exists("curl") {
+            return;
+        }
+        let (url, requests) = stub_server(vec![INFO_PARU, SEARCH_GIT]);
+        let client = AurClient::new(&format!("{}/", url));
+
+        let paru = client.info("paru").expect("info").expect("paru exists");
+        assert_eq!(paru.package_base, "paru");
+        assert_eq!(paru.maintainer.as_deref(), Some("Morganamilo"));
+        assert_eq!(paru.num_votes, 512);
+        assert_eq!(paru.last_modified, 1_720_000_000);
+        assert_eq!(paru.depends, vec!["git", "pacman"]);
+
+        let found = client.search("helper").expect("search");
+        assert_eq!(found[1].name, "paru-bin");
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(
+            requests,
+            vec![
+                "GET /rpc/?v=5&type=info&arg[]=paru HTTP/1.1",
+                "GET /rpc/?v=5&type=search&by=name-desc&arg=helper HTTP/1.1",
+            ]
+        );
+        assert_eq!(client.pkgbase_url("paru"), format!("{}/pkgbase/paru/", url));
+    }
+}
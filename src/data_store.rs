@@ -1,10 +1,22 @@
 use crate::paru::Package;
+use crate::schedule::{self, Recurrence, Schedule};
+use crate::task_type::TaskType;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Mutex, OnceLock};
 
+/// How many past durations are kept per task type for time estimates.
+const RUN_HISTORY: usize = 10;
+
+/// How long a successful task took, in seconds.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RunDuration {
+    pub task_type: TaskType,
+    pub seconds: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(default)]
 pub struct AppData {
@@ -15,6 +27,9 @@ pub struct AppData {
     pub cached_updates: Vec<Package>,
     pub cached_installed_at: Option<i64>,
     pub cached_updates_at: Option<i64>,
+    pub schedules: Vec<Schedule>,
+    pub next_schedule_id: u64,
+    pub run_durations: Vec<RunDuration>,
 }
 
 pub static DATA: OnceLock<Mutex<AppData>> = OnceLock::new();
@@ -149,3 +164,80 @@ pub fn cached_installed_at() -> Option<i64> {
 pub fn cached_updates_at() -> Option<i64> {
     with_data(|data| data.cached_updates_at).unwrap_or(None)
 }
+
+pub fn schedules() -> Vec<Schedule> {
+    with_data(|data| data.schedules.clone()).unwrap_or_default()
+}
+
+pub fn add_schedule(
+    task_type: TaskType,
+    package_name: String,
+    recurrence: Recurrence,
+    first_run: i64,
+) -> u64 {
+    with_data_mut(|data| {
+        data.next_schedule_id += 1;
+        let id = data.next_schedule_id;
+        data.schedules.push(Schedule {
+            id,
+            task_type,
+            package_name,
+            recurrence,
+            next_run: first_run,
+            shifted_hours: 0,
+        });
+        data.schedules.sort_by_key(|s| s.next_run);
+        id
+    })
+    .unwrap_or(0)
+}
+
+pub fn remove_schedule(id: u64) {
+    let _ = with_data_mut(|data| data.schedules.retain(|s| s.id != id));
+}
+
+/// Schedules whose time has come. Recurring ones stay, moved to their next run.
+pub fn take_due_schedules(now: i64) -> Vec<Schedule> {
+    // Called every second by the worker; only write the file when something is due.
+    if !with_data(|data| data.schedules.iter().any(|s| s.is_due(now))).unwrap_or(false) {
+        return Vec::new();
+    }
+    with_data_mut(|data| {
+        let due = schedule::take_due(&mut data.schedules, now);
+        data.schedules.sort_by_key(|s| s.next_run);
+        due
+    })
+    .unwrap_or_default()
+}
+
+pub fn record_run_duration(task_type: &TaskType, seconds: u64) {
+    let _ = with_data_mut(|data| {
+        data.run_durations.push(RunDuration {
+            task_type: task_type.clone(),
+            seconds,
+        });
+        let same_type = data
+            .run_durations
+            .iter()
+            .filter(|run| &run.task_type == task_type)
+            .count();
+        let mut excess = same_type.saturating_sub(RUN_HISTORY);
+        data.run_durations.retain(|run| {
+            let drop = excess > 0 && &run.task_type == task_type;
+            excess -= usize::from(drop);
+            !drop
+        });
+    });
+}
+
+/// Durations of the last successful runs of `task_type`, oldest first.
+pub fn run_durations(task_type: &TaskType) -> Vec<u64> {
+    with_data(|data| {
+        data.run_durations
+            .iter()
+            .filter(|run| &run.task_type == task_type)
+            .map(|run| run.seconds)
+            .collect()
+    })
+    .unwrap_or_default()
+}
@@ -0,0 +1,133 @@
+use crate::error::{BackendError, ErrorKind};
+use crate::logger::log_warning;
+use crate::privilege::Escalation;
+use crate::settings;
+use std::fs;
+use std::path::Path;
+
+pub const DB_LOCK_PATH: &str = "/var/lib/pacman/db.lck";
+
+/// Only pacman takes the lock; paru and makepkg run it when they need to. They
+/// are left out, as parut runs makepkg itself for builds that don't lock.
+const LOCKING_PROGRAM: &str = "pacman";
+
+/// Whether `/var/lib/pacman/db.lck` keeps tasks from starting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockState {
+    Free,
+    /// The lock exists and `program` (process `pid`) is probably holding it.
+    Held {
+        pid: u32,
+        program: String,
+    },
+    /// The lock exists but nothing that could hold it is running: left behind
+    /// by a pacman that crashed or was killed.
+    Stale,
+    /// The lock exists, but the running processes could not be listed to tell
+    /// whether it is stale; treated as held.
+    Unknown,
+}
+
+pub fn check() -> LockState {
+    check_at(Path::new(DB_LOCK_PATH), Path::new("/proc"))
+}
+
+/// Look for `lock` and for a process in `proc_dir` that could be holding it.
+fn check_at(lock: &Path, proc_dir: &Path) -> LockState {
+    if !lock.exists() {
+        return LockState::Free;
+    }
+    let Ok(entries) = fs::read_dir(proc_dir) else {
+        return LockState::Unknown;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .find_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            (comm.trim() == LOCKING_PROGRAM).then(|| LockState::Held {
+                pid,
+                program: LOCKING_PROGRAM.to_string(),
+            })
+        })
+        .unwrap_or(LockState::Stale)
+}
+
+/// Remove the lock as root, but only while it is still stale: a pacman that
+/// started in the meantime keeps its lock.
+pub fn remove_stale() -> Result<(), BackendError> {
+    match check() {
+        LockState::Free => return Ok(()),
+        LockState::Held { program, pid } => {
+            return Err(BackendError::new(
+                ErrorKind::DatabaseLocked,
+                format!(
+                    "{} (process {}) is using the package database now; the lock was not removed",
+                    program, pid
+                ),
+            ));
+        }
+        LockState::Unknown => {
+            return Err(BackendError::new(
+                ErrorKind::DatabaseLocked,
+                "Could not tell whether a package manager is running; the lock was not removed",
+            ));
+        }
+        LockState::Stale => {}
+    }
+
+    let escalation = Escalation::resolve(&settings::get().privilege_helper)?;
+    let spec = escalation.command("rm").args(["-f", DB_LOCK_PATH]);
+    let output = spec
+        .output()
+        .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
+    if !output.status.success() {
+        return Err(BackendError::from_output(
+            &spec,
+            &output,
+            "Failed to remove the package database lock",
+        ));
+    }
+    log_warning(&format!("Removed stale pacman lock {}", DB_LOCK_PATH));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LockState, check_at};
+    use crate::sync_db::testing::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn tells_held_locks_from_stale_ones() {
+        let dir = temp_dir("db-lock");
+        let lock = dir.join("db.lck");
+        let proc_dir = dir.join("proc");
+        // parut's own builds run makepkg without the lock.
+        for (pid, comm) in [
+            ("1", "systemd"),
+            ("812", "bash"),
+            ("913", "makepkg"),
+            ("self", "parut"),
+        ] {
+            fs::create_dir_all(proc_dir.join(pid)).unwrap();
+            fs::write(proc_dir.join(pid).join("comm"), format!("{}\n", comm)).unwrap();
+        }
+
+        assert_eq!(check_at(&lock, &proc_dir), LockState::Free);
+        fs::write(&lock, "").unwrap();
+        assert_eq!(check_at(&lock, &proc_dir), LockState::Stale);
+
+        fs::create_dir_all(proc_dir.join("4242")).unwrap();
+        fs::write(proc_dir.join("4242/comm"), "pacman\n").unwrap();
+        assert_eq!(
+            check_at(&lock, &proc_dir),
+            LockState::Held {
+                pid: 4242,
+                program: "pacman".to_string()
+            }
+        );
+        assert_eq!(check_at(&lock, &dir.join("no-proc")), LockState::Unknown);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
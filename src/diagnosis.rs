@@ -0,0 +1,296 @@
+use crate::task_type::TaskType;
+
+/// A one-click fix offered next to a diagnosis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Remedy {
+    /// The failure was transient; run the same task again.
+    Retry,
+    /// Queue another task first, then retry the failed one after it.
+    QueueThenRetry {
+        label: &'static str,
+        task_type: TaskType,
+        package_name: String,
+    },
+    /// Fetch a missing PGP key into the user's keyring (used by makepkg), then retry.
+    ImportKey(String),
+    /// Nothing to run automatically; copy the details so the user can act on them.
+    CopyText { label: &'static str, text: String },
+}
+
+impl Remedy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Remedy::Retry => "Retry",
+            Remedy::QueueThenRetry { label, .. } | Remedy::CopyText { label, .. } => label,
+            Remedy::ImportKey(_) => "Import Key & Retry",
+        }
+    }
+}
+
+/// What a failed task most likely ran into, and what to do about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnosis {
+    pub title: &'static str,
+    pub explanation: String,
+    pub remedy: Remedy,
+}
+
+type Rule = fn(&[&str]) -> Option<Diagnosis>;
+
+/// Checked in order; the first match wins. More specific failures come first
+/// since e.g. a PGP failure also ends in "failed to commit transaction".
+const RULES: &[Rule] = &[
+    database_locked,
+    unknown_public_key,
+    invalid_signature,
+    conflicting_files,
+    unsatisfied_dependencies,
+    failed_download,
+];
+
+/// Scan a failed task's output for well-known pacman/makepkg failures.
+pub fn diagnose(output: &[String]) -> Option<Diagnosis> {
+    let lines: Vec<&str> = output.iter().map(String::as_str).collect();
+    RULES.iter().find_map(|rule| rule(&lines))
+}
+
+/// Packages that pacman or paru rejected as targets ("target not found: foo",
+/// or "foo (target)" under paru's "could not find all required packages").
+pub fn failed_targets(output: &[String]) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    for line in output {
+        let line = line.trim();
+        let name = match line.find("target not found: ") {
+            Some(pos) => &line[pos + "target not found: ".len()..],
+            None => match line.strip_suffix(" (target)") {
+                Some(name) => name.trim_start_matches(':').trim(),
+                None => continue,
+            },
+        };
+        let name = name.trim();
+        if !name.is_empty() && !targets.iter().any(|t| t == name) {
+            targets.push(name.to_string());
+        }
+    }
+    targets
+}
+
+fn find_line<'a>(lines: &[&'a str], needle: &str) -> Option<&'a str> {
+    lines
+        .iter()
+        .find(|line| line.to_lowercase().contains(needle))
+        .copied()
+}
+
+fn database_locked(lines: &[&str]) -> Option<Diagnosis> {
+    find_line(lines, "unable to lock database")?;
+    Some(Diagnosis {
+        title: "Package database is locked",
+        explanation: "Another pacman process was running. Retry once it has finished.".to_string(),
+        remedy: Remedy::Retry,
+    })
+}
+
+fn unknown_public_key(lines: &[&str]) -> Option<Diagnosis> {
+    let line = find_line(lines, "unknown public key")?;
+    let start = line.find("unknown public key")? + "unknown public key".len();
+    let key: String = line[start..]
+        .trim_start()
+        .chars()
+        .take_while(char::is_ascii_hexdigit)
+        .collect();
+    if key.is_empty() {
+        return None;
+    }
+    Some(Diagnosis {
+        title: "Unknown PGP key",
+        explanation: format!(
+            "The sources are signed with key {}, which is not in your keyring.",
+            key
+        ),
+        remedy: Remedy::ImportKey(key),
+    })
+}
+
+fn invalid_signature(lines: &[&str]) -> Option<Diagnosis> {
+    find_line(lines, "invalid or corrupted package (pgp signature)")
+        .or_else(|| find_line(lines, "signature from"))
+        .filter(|line| line.to_lowercase().contains("invalid"))?;
+    Some(Diagnosis {
+        title: "Package signature rejected",
+        explanation: "Usually an outdated archlinux-keyring. Update it, then retry.".to_string(),
+        remedy: Remedy::QueueThenRetry {
+            label: "Update Keyring & Retry",
+            task_type: TaskType::Install,
+            package_name: "archlinux-keyring".to_string(),
+        },
+    })
+}
+
+fn conflicting_files(lines: &[&str]) -> Option<Diagnosis> {
+    find_line(lines, "conflicting files")?;
+    // "<package>: <path> exists in filesystem"
+    let paths: Vec<&str> = lines
+        .iter()
+        .filter_map(|line| line.strip_suffix(" exists in filesystem"))
+        .filter_map(|line| line.split_once(": ").map(|(_, path)| path.trim()))
+        .collect();
+    let explanation = match paths.as_slice() {
+        [] => "Files the package wants to install already exist on disk.".to_string(),
+        [path] => format!("{} already exists and is not owned by the package.", path),
+        [path, rest @ ..] => format!(
+            "{} and {} other file(s) already exist and are not owned by the package.",
+            path,
+            rest.len()
+        ),
+    };
+    Some(Diagnosis {
+        title: "Conflicting files",
+        explanation: format!(
+            "{} Check who owns them with pacman -Qo before removing or overwriting.",
+            explanation
+        ),
+        remedy: Remedy::CopyText {
+            label: "Copy Paths",
+            text: paths.join("\n"),
+        },
+    })
+}
+
+fn unsatisfied_dependencies(lines: &[&str]) -> Option<Diagnosis> {
+    find_line(lines, "could not satisfy dependencies")?;
+    let detail = find_line(lines, "breaks dependency")
+        .or_else(|| find_line(lines, "unable to satisfy dependency"))
+        .map(|line| format!(" ({})", line.trim_start_matches(':').trim()))
+        .unwrap_or_default();
+    Some(Diagnosis {
+        title: "Unsatisfied dependencies",
+        explanation: format!(
+            "The package needs versions your system does not have{}. A full system update usually resolves this.",
+            detail
+        ),
+        remedy: Remedy::QueueThenRetry {
+            label: "Update System & Retry",
+            task_type: TaskType::Update,
+            package_name: "system".to_string(),
+        },
+    })
+}
+
+fn failed_download(lines: &[&str]) -> Option<Diagnosis> {
+    find_line(lines, "failed to retrieve some files")
+        .or_else(|| find_line(lines, "failed retrieving file"))?;
+    Some(Diagnosis {
+        title: "Download failed",
+        explanation: "A mirror was unreachable or had outdated files. Refreshing the databases picks up current package versions.".to_string(),
+        remedy: Remedy::QueueThenRetry {
+            label: "Refresh & Retry",
+            task_type: TaskType::Update,
+            package_name: "system".to_string(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Remedy, diagnose, failed_targets};
+    use crate::task_type::TaskType;
+
+    fn output(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn recognises_known_failures() {
+        let locked = diagnose(&output(&[
+            "error: failed to init transaction (unable to lock database)",
+            "error: could not lock database: File exists",
+        ]))
+        .expect("lock diagnosis");
+        assert_eq!(locked.remedy, Remedy::Retry);
+
+        let key = diagnose(&output(&[
+            "    foo-1.0.tar.gz ... FAILED (unknown public key 4AEE18F83AFDEB23)",
+            "==> ERROR: One or more PGP signatures could not be verified!",
+        ]))
+        .expect("key diagnosis");
+        assert_eq!(
+            key.remedy,
+            Remedy::ImportKey("4AEE18F83AFDEB23".to_string())
+        );
+
+        let keyring = diagnose(&output(&[
+            "error: glibc: signature from \"Packager <p@archlinux.org>\" is invalid",
+            "error: failed to commit transaction (invalid or corrupted package (PGP signature))",
+        ]))
+        .expect("signature diagnosis");
+        assert!(matches!(
+            keyring.remedy,
+            Remedy::QueueThenRetry { task_type: TaskType::Install, ref package_name, .. }
+                if package_name == "archlinux-keyring"
+        ));
+
+        let deps = diagnose(&output(&[
+            "error: failed to prepare transaction (could not satisfy dependencies)",
+            ":: installing icu (75.1-1) breaks dependency 'libicuuc.so=74-64' required by libxml2",
+        ]))
+        .expect("dependency diagnosis");
+        assert!(
+            deps.explanation
+                .contains("breaks dependency 'libicuuc.so=74-64'")
+        );
+
+        let download = diagnose(&output(&[
+            "error: failed retrieving file 'foo-1.0-1-x86_64.pkg.tar.zst' from mirror : 404",
+            "error: failed to commit transaction (failed to retrieve some files)",
+        ]))
+        .expect("download diagnosis");
+        assert_eq!(download.remedy.label(), "Refresh & Retry");
+
+        assert!(diagnose(&output(&["==> ERROR: A failure occurred in build()."])).is_none());
+    }
+
+    #[test]
+    fn finds_rejected_targets() {
+        assert_eq!(
+            failed_targets(&output(&[
+                "error: target not found: nosuchpkg",
+                "error: target not found: nosuchpkg",
+            ])),
+            vec!["nosuchpkg"]
+        );
+        assert_eq!(
+            failed_targets(&output(&[
+                ":: Resolving dependencies...",
+                "error: could not find all required packages:",
+                "    missing-aur (target)",
+                "    other-missing (target)",
+            ])),
+            vec!["missing-aur", "other-missing"]
+        );
+        assert!(failed_targets(&output(&["installing ripgrep..."])).is_empty());
+    }
+
+    #[test]
+    fn lists_conflicting_paths() {
+        let diagnosis = diagnose(&output(&[
+            "error: failed to commit transaction (conflicting files)",
+            "nodejs: /usr/bin/node exists in filesystem",
+            "nodejs: /usr/include/node/v8.h exists in filesystem",
+            "Errors occurred, no packages were upgraded.",
+        ]))
+        .expect("conflict diagnosis");
+        assert!(
+            diagnosis
+                .explanation
+                .starts_with("/usr/bin/node and 1 other file(s)")
+        );
+        assert_eq!(
+            diagnosis.remedy,
+            Remedy::CopyText {
+                label: "Copy Paths",
+                text: "/usr/bin/node\n/usr/include/node/v8.h".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use crate::runner::CommandSpec;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::process::{ExitStatus, Output};
+
+/// What went wrong, coarse enough for the UI to pick a recovery action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorKind {
+    /// A required program (paru, pacman, curl, a terminal) is not installed.
+    MissingProgram,
+    /// Another pacman process holds the database lock.
+    DatabaseLocked,
+    /// Mirrors or the AUR could not be reached.
+    Network,
+    /// A target does not exist in the repositories or the AUR.
+    PackageNotFound,
+    /// sudo or polkit rejected the password or the user lacks permission.
+    AuthenticationFailed,
+    /// The user canceled the task or dismissed one of its prompts.
+    Canceled,
+    /// The task ran longer than the timeout set for its type and was canceled.
+    TimedOut,
+    /// The command ran and failed for a reason not covered above.
+    CommandFailed,
+    /// Anything else: unreadable files, unexpected output, ...
+    Other,
+}
+
+impl ErrorKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ErrorKind::MissingProgram => "Program not installed",
+            ErrorKind::DatabaseLocked => "Package database locked",
+            ErrorKind::Network => "Network error",
+            ErrorKind::PackageNotFound => "Package not found",
+            ErrorKind::AuthenticationFailed => "Authentication failed",
+            ErrorKind::Canceled => "Canceled",
+            ErrorKind::TimedOut => "Timed out",
+            ErrorKind::CommandFailed => "Command failed",
+            ErrorKind::Other => "Error",
+        }
+    }
+
+    /// A short suggestion shown next to the error, if there is a useful one.
+    pub fn recovery_hint(self) -> Option<&'static str> {
+        match self {
+            ErrorKind::MissingProgram => Some("Install the missing program, then try again."),
+            ErrorKind::DatabaseLocked => {
+                Some("Another package manager is running. Wait for it to finish, then retry.")
+            }
+            ErrorKind::Network => Some("Check your connection and mirrors, then retry."),
+            ErrorKind::PackageNotFound => {
+                Some("Refresh the package databases or check the package name.")
+            }
+            ErrorKind::AuthenticationFailed => Some("The password was not accepted."),
+            ErrorKind::TimedOut => {
+                Some("Raise or turn off the timeout for this task type in Settings, then retry.")
+            }
+            ErrorKind::Canceled | ErrorKind::CommandFailed | ErrorKind::Other => None,
+        }
+    }
+}
+
+/// An error from `ParuBackend`, with enough context to explain and recover from it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackendError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// The command line that failed, if a command was involved.
+    pub command: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Captured error output; for PTY runs, the last lines of the terminal.
+    pub stderr: String,
+}
+
+impl BackendError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            command: None,
+            exit_code: None,
+            stderr: String::new(),
+        }
+    }
+
+    pub fn canceled() -> Self {
+        Self::new(ErrorKind::Canceled, "Task canceled by user")
+    }
+
+    pub fn timed_out(minutes: u64) -> Self {
+        Self::new(
+            ErrorKind::TimedOut,
+            format!("Timed out after {} minute(s)", minutes),
+        )
+    }
+
+    /// The command could not be started at all.
+    pub fn spawn_failed(spec: &CommandSpec, err: &io::Error) -> Self {
+        let (kind, message) = if err.kind() == io::ErrorKind::NotFound {
+            (
+                ErrorKind::MissingProgram,
+                format!("{} is not installed", spec.program),
+            )
+        } else {
+            (
+                ErrorKind::Other,
+                format!("Failed to execute {}: {}", spec.program, err),
+            )
+        };
+        Self {
+            command: Some(spec.command_line()),
+            ..Self::new(kind, message)
+        }
+    }
+
+    /// The command ran to completion and exited unsuccessfully.
+    pub fn from_output(spec: &CommandSpec, output: &Output, message: impl Into<String>) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let exit_code = output.status.code();
+        let mut kind = classify(&format!("{}\n{}", stderr, stdout));
+        // curl: couldn't resolve host / connect / timed out / TLS or receive failure.
+        if spec.program == "curl" && matches!(exit_code, Some(5..=7 | 28 | 35 | 56)) {
+            kind = ErrorKind::Network;
+        }
+        Self {
+            kind,
+            message: message.into(),
+            command: Some(spec.command_line()),
+            exit_code,
+            stderr,
+        }
+    }
+
+    /// An interactive run ended unsuccessfully; `output` is the tail of what it printed.
+    pub fn exited(spec: &CommandSpec, status: ExitStatus, output: &str) -> Self {
+        let message = match status.code() {
+            Some(code) => format!(
+                "{} exited with code {} - see task output",
+                spec.program, code
+            ),
+            None => format!(
+                "{} was terminated by a signal - see task output",
+                spec.program
+            ),
+        };
+        Self {
+            kind: classify(output),
+            message,
+            command: Some(spec.command_line()),
+            exit_code: status.code(),
+            stderr: output.to_string(),
+        }
+    }
+
+    /// Worth retrying as-is once the transient condition has passed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.kind, ErrorKind::DatabaseLocked | ErrorKind::Network)
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+/// Guess the error category from pacman/paru/sudo/curl output.
+pub fn classify(output: &str) -> ErrorKind {
+    const RULES: &[(ErrorKind, &[&str])] = &[
+        (
+            ErrorKind::DatabaseLocked,
+            &["unable to lock database", "could not lock database"],
+        ),
+        (
+            ErrorKind::AuthenticationFailed,
+            &[
+                "incorrect password attempt",
+                "sorry, try again",
+                "authentication failure",
+                "a password is required",
+                "is not in the sudoers file",
+                "no password was provided",
+                "no authentication agent found",
+                "request dismissed",
+                "not authorized",
+            ],
+        ),
+        (
+            ErrorKind::PackageNotFound,
+            &[
+                "target not found",
+                "' was not found",
+                "could not find all required packages",
+                "no aur package found",
+            ],
+        ),
+        (
+            ErrorKind::Network,
+            &[
+                "could not resolve host",
+                "failed retrieving file",
+                "failed to retrieve some files",
+                "failed to connect to",
+                "connection timed out",
+                "network is unreachable",
+                "temporary failure in name resolution",
+            ],
+        ),
+    ];
+
+    let text = output.to_lowercase();
+    RULES
+        .iter()
+        .find(|(_, needles)| needles.iter().any(|needle| text.contains(needle)))
+        .map(|(kind, _)| *kind)
+        .unwrap_or(ErrorKind::CommandFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BackendError, ErrorKind, classify};
+    use crate::runner::CommandSpec;
+    use std::io;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
+
+    #[test]
+    fn classifies_common_failures() {
+        assert_eq!(
+            classify("error: failed to init transaction (unable to lock database)"),
+            ErrorKind::DatabaseLocked
+        );
+        assert_eq!(
+            classify("error: target not found: nosuchpkg"),
+            ErrorKind::PackageNotFound
+        );
+        assert_eq!(
+            classify(
+                "error: failed retrieving file 'core.db' from mirror : Could not resolve host: mirror"
+            ),
+            ErrorKind::Network
+        );
+        assert_eq!(
+            classify("sudo: 3 incorrect password attempts"),
+            ErrorKind::AuthenticationFailed
+        );
+        assert_eq!(
+            classify("Error executing command as another user: Request dismissed"),
+            ErrorKind::AuthenticationFailed
+        );
+        assert_eq!(
+            classify("==> ERROR: A failure occurred in build()."),
+            ErrorKind::CommandFailed
+        );
+    }
+
+    #[test]
+    fn captures_command_context() {
+        let spec = CommandSpec::new("pacman").arg("-Si").arg("nosuchpkg");
+        let output = Output {
+            status: ExitStatus::from_raw(1 << 8),
+            stdout: Vec::new(),
+            stderr: b"error: package 'nosuchpkg' was not found\n".to_vec(),
+        };
+        let err = BackendError::from_output(&spec, &output, "Failed to get details");
+        assert_eq!(err.command.as_deref(), Some("pacman -Si nosuchpkg"));
+        assert_eq!(err.kind, ErrorKind::PackageNotFound);
+        assert_eq!(err.exit_code, Some(1));
+        assert_eq!(err.stderr, "error: package 'nosuchpkg' was not found");
+        assert_eq!(err.to_string(), "Failed to get details");
+        assert!(!err.is_retryable());
+
+        let curl = CommandSpec::new("curl").arg("https://aur.archlinux.org/rpc/");
+        let offline = Output {
+            status: ExitStatus::from_raw(6 << 8),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        assert!(
+            BackendError::from_output(&curl, &offline, "AUR RPC request failed").is_retryable()
+        );
+
+        let missing = BackendError::spawn_failed(&spec, &io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(missing.kind, ErrorKind::MissingProgram);
+        assert_eq!(missing.message, "pacman is not installed");
+    }
+}
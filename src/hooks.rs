@@ -0,0 +1,186 @@
+use crate::error::{BackendError, ErrorKind};
+use crate::pty::PtyEvent;
+use crate::runner::CommandSpec;
+use crate::settings::{HookStage, TaskHook};
+use crate::task_type::TaskType;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+/// Run the hooks in `hooks` that apply to `stage` of a `task_type` task, in
+/// order, writing their output to `output`. A failing hook only stops the task
+/// when it has `abort_on_failure` set.
+pub fn run_hooks(
+    hooks: &[TaskHook],
+    stage: HookStage,
+    task_type: &TaskType,
+    packages: &[&str],
+    output: &dyn Fn(String),
+    cancel_requested: &dyn Fn() -> bool,
+) -> Result<(), BackendError> {
+    for hook in hooks.iter().filter(|h| h.applies_to(stage, task_type)) {
+        output(format!(
+            "Running {} hook \"{}\": {}",
+            stage.label().to_lowercase(),
+            hook.name,
+            hook.command
+        ));
+        let spec = CommandSpec::new("sh")
+            .arg("-c")
+            .arg(hook.command.as_str())
+            .env("PARUT_HOOK_STAGE", stage.label())
+            .env("PARUT_TASK_TYPE", &format!("{:?}", task_type))
+            .env("PARUT_PACKAGES", &packages.join(" "));
+        let failure = match run_hook(&spec, output, cancel_requested)? {
+            None => continue,
+            Some(failure) => failure,
+        };
+        if hook.abort_on_failure {
+            output(format!(
+                "Hook \"{}\" {} - stopping the task.",
+                hook.name, failure
+            ));
+            return Err(BackendError {
+                command: Some(spec.command_line()),
+                ..BackendError::new(
+                    ErrorKind::Other,
+                    format!("{} hook \"{}\" {}", stage.label(), hook.name, failure),
+                )
+            });
+        }
+        output(format!("Hook \"{}\" {} - continuing.", hook.name, failure));
+    }
+    Ok(())
+}
+
+/// Run one hook to completion. Returns why it failed, if it did.
+fn run_hook(
+    spec: &CommandSpec,
+    output: &dyn Fn(String),
+    cancel_requested: &dyn Fn() -> bool,
+) -> Result<Option<String>, BackendError> {
+    let mut process = match spec.spawn_pty() {
+        Ok(process) => process,
+        Err(e) => return Ok(Some(format!("could not be started: {}", e))),
+    };
+    loop {
+        if cancel_requested() {
+            process.kill();
+            output("Task canceled by user.".to_string());
+            return Err(BackendError::canceled());
+        }
+        match process.recv_timeout(Duration::from_millis(200)) {
+            Ok(PtyEvent::Line(line)) | Ok(PtyEvent::Partial(line)) => output(line),
+            // Output can still be buffered after the hook exited, so wait
+            // for the terminal to close rather than for the process.
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Ok(match process.wait() {
+                    Ok(status) => describe_failure(status),
+                    Err(e) => Some(format!("could not be waited for: {}", e)),
+                });
+            }
+        }
+    }
+}
+
+fn describe_failure(status: std::process::ExitStatus) -> Option<String> {
+    match status.code() {
+        _ if status.success() => None,
+        Some(code) => Some(format!("failed with exit code {}", code)),
+        None => Some("was terminated by a signal".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_hooks;
+    use crate::pty::PtyEvent;
+    use crate::runner::{FakeRunner, with_runner};
+    use crate::settings::{HookStage, TaskHook};
+    use crate::task_type::TaskType;
+    use std::sync::{Arc, Mutex};
+
+    fn hook(name: &str, stage: HookStage, command: &str, abort_on_failure: bool) -> TaskHook {
+        TaskHook {
+            name: name.to_string(),
+            command: command.to_string(),
+            stage,
+            task_types: vec![TaskType::Update],
+            abort_on_failure,
+        }
+    }
+
+    #[test]
+    fn runs_matching_hooks_and_stops_on_required_failures() {
+        let runner = Arc::new(
+            FakeRunner::new()
+                .with_session(
+                    "sh -c snapper create",
+                    vec![PtyEvent::Line("snapshot 42".to_string())],
+                    0,
+                )
+                .with_session("sh -c notify-team", Vec::new(), 1)
+                .with_session("sh -c check-space", Vec::new(), 2),
+        );
+        let hooks = vec![
+            hook("Snapshot", HookStage::Before, "snapper create", true),
+            hook("Notify", HookStage::Before, "notify-team", false),
+            hook("Initramfs", HookStage::After, "mkinitcpio -P", true),
+        ];
+        let lines = Mutex::new(Vec::new());
+        let output = |line: String| lines.lock().unwrap().push(line);
+
+        let result = with_runner(runner.clone(), || {
+            run_hooks(
+                &hooks,
+                HookStage::Before,
+                &TaskType::Update,
+                &["system"],
+                &output,
+                &|| false,
+            )
+        });
+        assert!(result.is_ok());
+        assert_eq!(
+            runner.calls(),
+            vec!["sh -c snapper create", "sh -c notify-team"]
+        );
+        let lines = lines.lock().unwrap().clone();
+        assert!(lines.contains(&"snapshot 42".to_string()));
+        assert_eq!(
+            lines.last().unwrap(),
+            "Hook \"Notify\" failed with exit code 1 - continuing."
+        );
+
+        // Hooks for other task types don't run at all.
+        let result = with_runner(runner.clone(), || {
+            run_hooks(
+                &hooks,
+                HookStage::Before,
+                &TaskType::Install,
+                &["ripgrep"],
+                &|_| {},
+                &|| false,
+            )
+        });
+        assert!(result.is_ok());
+        assert_eq!(runner.calls().len(), 2);
+
+        let required = vec![hook("Space", HookStage::Before, "check-space", true)];
+        let err = with_runner(runner.clone(), || {
+            run_hooks(
+                &required,
+                HookStage::Before,
+                &TaskType::Update,
+                &["system"],
+                &|_| {},
+                &|| false,
+            )
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Before hook \"Space\" failed with exit code 2"
+        );
+    }
+}
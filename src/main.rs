@@ -2,11 +2,28 @@ use adw::prelude::*;
 use adw::{Application, ApplicationWindow, StyleManager};
 use gtk4::{CssProvider, gdk, glib};
 
+mod aur;
 mod data_store;
+mod db_lock;
+mod diagnosis;
+mod error;
+mod hooks;
 mod logger;
+mod package_cache;
+mod pacman_db;
 mod paru;
+mod preview;
+mod privilege;
+mod progress;
+mod prompts;
+mod pty;
+mod runner;
+mod schedule;
 mod settings;
+mod sync_db;
+mod task_history;
 mod task_queue;
+mod task_type;
 mod ui;
 mod utils;
 
@@ -17,6 +34,11 @@ const APP_ID: &str = "io.github.reubenpercival.parut";
 const CSS: &str = include_str!("style.css");
 
 fn main() -> glib::ExitCode {
+    // sudo starts parut as its askpass program when paru runs without a terminal.
+    if let Some(prompt) = privilege::askpass_prompt() {
+        return ParuGui::run_askpass(&prompt);
+    }
+
     // Log application start
     log_info("Parut application starting");
 
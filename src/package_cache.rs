@@ -0,0 +1,216 @@
+use crate::paru::ParuBackend;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PACMAN_CACHE_DIR: &str = "/var/cache/pacman/pkg";
+
+/// A package file kept in pacman's cache, which can be installed again with `-U`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedPackage {
+    /// `[epoch:]pkgver-pkgrel`
+    pub version: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+pub fn cached_versions(name: &str) -> Vec<CachedPackage> {
+    cached_versions_in(Path::new(PACMAN_CACHE_DIR), name)
+}
+
+/// Every cached file of `name` in `dir`, newest version first.
+fn cached_versions_in(dir: &Path, name: &str) -> Vec<CachedPackage> {
+    let mut versions: Vec<CachedPackage> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if !ParuBackend::is_package_file(&path) {
+                return None;
+            }
+            let file_name = path.file_name()?.to_str()?;
+            if ParuBackend::package_name_from_file(file_name)? != name {
+                return None;
+            }
+            Some(CachedPackage {
+                version: version_from_file(file_name)?.to_string(),
+                size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                path,
+            })
+        })
+        .collect();
+    versions.sort_by(|a, b| vercmp(&b.version, &a.version));
+    versions
+}
+
+/// `ripgrep-1:14.1.1-1-x86_64.pkg.tar.zst` -> `1:14.1.1-1`
+fn version_from_file(file_name: &str) -> Option<&str> {
+    let stem = &file_name[..file_name.find(".pkg.tar")?];
+    // name-pkgver-pkgrel-arch
+    let without_arch = &stem[..stem.rfind('-')?];
+    let pkgrel = without_arch.rfind('-')?;
+    let pkgver = without_arch[..pkgrel].rfind('-')?;
+    Some(&without_arch[pkgver + 1..])
+}
+
+/// Compare two package versions the way pacman's `vercmp` does: by epoch, then
+/// pkgver, then pkgrel when both have one.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (epoch_a, ver_a, rel_a) = split_version(a);
+    let (epoch_b, ver_b, rel_b) = split_version(b);
+    rpmvercmp(epoch_a, epoch_b)
+        .then_with(|| rpmvercmp(ver_a, ver_b))
+        .then_with(|| match (rel_a, rel_b) {
+            (Some(rel_a), Some(rel_b)) => rpmvercmp(rel_a, rel_b),
+            _ => Ordering::Equal,
+        })
+}
+
+/// `[epoch:]pkgver[-pkgrel]`, with a missing epoch read as `0`.
+fn split_version(version: &str) -> (&str, &str, Option<&str>) {
+    let digits = version
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(version.len());
+    let (epoch, rest) = match version[digits..].strip_prefix(':') {
+        Some(rest) if digits > 0 => (&version[..digits], rest),
+        Some(rest) => ("0", rest),
+        None => ("0", version),
+    };
+    match rest.rsplit_once('-') {
+        Some((ver, rel)) => (epoch, ver, Some(rel)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// libalpm's `rpmvercmp`: compare alternating runs of digits and letters,
+/// where digits beat letters and a trailing letter run means a pre-release.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let (sep_i, sep_j) = (i, j);
+        while i < a.len() && !a[i].is_ascii_alphanumeric() {
+            i += 1;
+        }
+        while j < b.len() && !b[j].is_ascii_alphanumeric() {
+            j += 1;
+        }
+        if i == a.len() || j == b.len() {
+            break;
+        }
+        // More separators make for the newer version ("1..0" vs "1.0").
+        if i - sep_i != j - sep_j {
+            return (i - sep_i).cmp(&(j - sep_j));
+        }
+
+        let numeric = a[i].is_ascii_digit();
+        let same_kind = |c: &u8| {
+            if numeric {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
+            }
+        };
+        let end_i = i + a[i..].iter().take_while(|c| same_kind(c)).count();
+        let end_j = j + b[j..].iter().take_while(|c| same_kind(c)).count();
+        if end_j == j {
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let (mut seg_a, mut seg_b) = (&a[i..end_i], &b[j..end_j]);
+        if numeric {
+            let trim = |s: &[u8]| -> usize { s.iter().take_while(|c| **c == b'0').count() };
+            seg_a = &seg_a[trim(seg_a)..];
+            seg_b = &seg_b[trim(seg_b)..];
+            match seg_a.len().cmp(&seg_b.len()) {
+                Ordering::Equal => {}
+                longer => return longer,
+            }
+        }
+        match seg_a.cmp(seg_b) {
+            Ordering::Equal => {}
+            different => return different,
+        }
+        (i, j) = (end_i, end_j);
+    }
+
+    let (rest_a, rest_b) = (&a[i..], &b[j..]);
+    if rest_a.is_empty() && rest_b.is_empty() {
+        return Ordering::Equal;
+    }
+    // "1.0" < "1.0.1", but "1.0alpha" < "1.0"
+    if (rest_a.is_empty() && !rest_b[0].is_ascii_alphabetic())
+        || rest_a.first().is_some_and(u8::is_ascii_alphabetic)
+    {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cached_versions_in, vercmp};
+    use crate::sync_db::testing::temp_dir;
+    use std::cmp::Ordering;
+    use std::fs;
+
+    #[test]
+    fn compares_versions_like_vercmp() {
+        for (a, b, expected) in [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0-1", "1.0-2", Ordering::Less),
+            ("1.0", "1.0-2", Ordering::Equal),
+            ("1.10", "1.9", Ordering::Greater),
+            ("1.0a", "1.0", Ordering::Less),
+            ("1.0alpha", "1.0beta", Ordering::Less),
+            ("1.0", "1.0.1", Ordering::Less),
+            ("1.0.1", "1.01", Ordering::Less),
+            ("1.0", "1..0", Ordering::Less),
+            ("1.001", "1.1", Ordering::Equal),
+            ("1:1.0-1", "2.0-1", Ordering::Greater),
+            ("0:2.0-1", "2.0-1", Ordering::Equal),
+            ("2.40+r16+gaa6a2f9-1", "2.40-1", Ordering::Greater),
+        ] {
+            assert_eq!(vercmp(a, b), expected, "{} vs {}", a, b);
+            assert_eq!(vercmp(b, a), expected.reverse(), "{} vs {}", b, a);
+        }
+    }
+
+    #[test]
+    fn lists_cached_versions_newest_first() {
+        let dir = temp_dir("package-cache");
+        for file in [
+            "ripgrep-14.0.3-1-x86_64.pkg.tar.zst",
+            "ripgrep-14.1.1-1-x86_64.pkg.tar.zst",
+            "ripgrep-14.1.1-1-x86_64.pkg.tar.zst.sig",
+            "ripgrep-14.1.0-2-x86_64.pkg.tar.zst",
+            "ripgrep-all-0.10.6-1-x86_64.pkg.tar.zst",
+            "ripgrep-1:9.0-1-x86_64.pkg.tar.xz",
+        ] {
+            fs::write(dir.join(file), "pkg").unwrap();
+        }
+
+        let versions: Vec<String> = cached_versions_in(&dir, "ripgrep")
+            .into_iter()
+            .map(|c| c.version)
+            .collect();
+        assert_eq!(
+            versions,
+            vec!["1:9.0-1", "14.1.1-1", "14.1.0-2", "14.0.3-1"]
+        );
+        assert!(cached_versions_in(&dir, "fd").is_empty());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::paru::{Package, PackageDetails};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+pub const LOCAL_DB_PATH: &str = "/var/lib/pacman/local";
+
+/// One package entry of a pacman database (local or sync), parsed from its `desc` file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DbPackage {
+    pub filename: String,
+    pub name: String,
+    pub version: String,
+    pub base: String,
+    pub description: String,
+    pub url: String,
+    pub arch: String,
+    pub build_date: Option<i64>,
+    pub install_date: Option<i64>,
+    pub packager: String,
+    /// Installed size in bytes (`%SIZE%` locally, `%ISIZE%` in sync databases).
+    pub size: u64,
+    /// Compressed package size in bytes; only known for sync database entries.
+    pub download_size: u64,
+    pub explicit: bool,
+    pub licenses: Vec<String>,
+    pub validation: Vec<String>,
+    pub groups: Vec<String>,
+    pub depends: Vec<String>,
+    pub optdepends: Vec<String>,
+    pub provides: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub replaces: Vec<String>,
+}
+
+impl DbPackage {
+    pub fn to_package(&self, repository: &str) -> Package {
+        Package {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            description: self.description.clone(),
+            repository: repository.to_string(),
+            installed_version: Some(self.version.clone()),
+            installed_size: Some(self.size),
+        }
+    }
+
+    /// Render the entry the way `pacman -Qi` would, so the details dialog
+    /// looks the same whichever source filled it.
+    pub fn to_details(&self, required_by: &[String], optional_for: &[String]) -> PackageDetails {
+        PackageDetails {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            description: self.description.clone(),
+            repository: String::new(),
+            url: self.url.clone(),
+            licenses: join_or_none(&self.licenses),
+            groups: join_or_none(&self.groups),
+            provides: join_or_none(&self.provides),
+            depends_on: join_or_none(&self.depends),
+            optional_deps: join_or_none(&self.optdepends),
+            required_by: join_or_none(required_by),
+            optional_for: join_or_none(optional_for),
+            conflicts_with: join_or_none(&self.conflicts),
+            replaces: join_or_none(&self.replaces),
+            installed_size: format_size(self.size),
+            packager: self.packager.clone(),
+            build_date: self.build_date.map(format_date).unwrap_or_default(),
+            install_date: self.install_date.map(format_date).unwrap_or_default(),
+            install_reason: if self.explicit {
+                "Explicitly installed".to_string()
+            } else {
+                "Installed as a dependency for another package".to_string()
+            },
+            install_script: String::new(),
+            validated_by: self
+                .validation
+                .iter()
+                .map(|v| match v.as_str() {
+                    "pgp" => "Signature",
+                    "sha256" => "SHA-256 Sum",
+                    "md5" => "MD5 Sum",
+                    _ => "None",
+                })
+                .collect::<Vec<_>>()
+                .join("  "),
+            votes: String::new(),
+            popularity: String::new(),
+            maintainer: String::new(),
+            package_base: self.base.clone(),
+            out_of_date: String::new(),
+            last_modified: String::new(),
+        }
+    }
+}
+
+/// Parse every `*/desc` file below `root` (normally `/var/lib/pacman/local`).
+pub fn read_local_db(root: &Path) -> io::Result<Vec<DbPackage>> {
+    let mut packages = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(entry.path().join("desc")) else {
+            continue;
+        };
+        if let Some(package) = parse_desc(&content) {
+            packages.push(package);
+        }
+    }
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(packages)
+}
+
+type LocalDbCache = Option<(PathBuf, Option<SystemTime>, Arc<Vec<DbPackage>>)>;
+
+static LOCAL_DB_CACHE: OnceLock<Mutex<LocalDbCache>> = OnceLock::new();
+
+/// [`read_local_db`], reused until pacman installs or removes something. Every
+/// transaction adds or deletes entry directories, which bumps `root`'s mtime.
+pub fn cached_local_db(root: &Path) -> io::Result<Arc<Vec<DbPackage>>> {
+    let modified = fs::metadata(root)?.modified().ok();
+    let cache = LOCAL_DB_CACHE.get_or_init(|| Mutex::new(None));
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((path, stamp, packages)) = cache.as_ref()
+        && path == root
+        && *stamp == modified
+    {
+        return Ok(packages.clone());
+    }
+
+    let packages = Arc::new(read_local_db(root)?);
+    *cache = Some((root.to_path_buf(), modified, packages.clone()));
+    Ok(packages)
+}
+
+/// Parse a pacman `desc` file: `%FIELD%` headers followed by one value per line,
+/// with blocks separated by blank lines.
+pub fn parse_desc(content: &str) -> Option<DbPackage> {
+    let mut package = DbPackage {
+        explicit: true,
+        ..Default::default()
+    };
+    let mut field = String::new();
+
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            field.clear();
+            continue;
+        }
+        if line.len() > 2 && line.starts_with('%') && line.ends_with('%') {
+            field = line.trim_matches('%').to_string();
+            continue;
+        }
+
+        let value = line.to_string();
+        match field.as_str() {
+            "FILENAME" => package.filename = value,
+            "NAME" => package.name = value,
+            "VERSION" => package.version = value,
+            "BASE" => package.base = value,
+            "DESC" => package.description = value,
+            "URL" => package.url = value,
+            "ARCH" => package.arch = value,
+            "BUILDDATE" => package.build_date = value.parse().ok(),
+            "INSTALLDATE" => package.install_date = value.parse().ok(),
+            "PACKAGER" => package.packager = value,
+            "SIZE" | "ISIZE" => package.size = value.parse().unwrap_or(0),
+            "CSIZE" => package.download_size = value.parse().unwrap_or(0),
+            // Only present for dependencies: 1 = installed as a dependency.
+            "REASON" => package.explicit = value != "1",
+            "LICENSE" => package.licenses.push(value),
+            "VALIDATION" => package.validation.push(value),
+            "GROUPS" => package.groups.push(value),
+            "DEPENDS" => package.depends.push(value),
+            "OPTDEPENDS" => package.optdepends.push(value),
+            "PROVIDES" => package.provides.push(value),
+            "CONFLICTS" => package.conflicts.push(value),
+            "REPLACES" => package.replaces.push(value),
+            _ => {}
+        }
+    }
+
+    if package.name.is_empty() {
+        None
+    } else {
+        Some(package)
+    }
+}
+
+/// Strip version constraints and descriptions: `glibc>=2.39` -> `glibc`,
+/// `bash-completion: for tab completion` -> `bash-completion`.
+pub fn dependency_name(dep: &str) -> &str {
+    let end = dep.find(['<', '>', '=', ':']).unwrap_or(dep.len());
+    dep[..end].trim()
+}
+
+/// Reverse dependencies of `target` (pacman's "Required By" / "Optional For"),
+/// matching both package names and anything `target` provides.
+pub fn reverse_dependencies(
+    packages: &[DbPackage],
+    target: &DbPackage,
+) -> (Vec<String>, Vec<String>) {
+    let mut satisfies: Vec<&str> = vec![target.name.as_str()];
+    satisfies.extend(target.provides.iter().map(|p| dependency_name(p)));
+
+    let mut required_by = Vec::new();
+    let mut optional_for = Vec::new();
+    for package in packages {
+        if package.name == target.name {
+            continue;
+        }
+        if package
+            .depends
+            .iter()
+            .any(|dep| satisfies.contains(&dependency_name(dep)))
+        {
+            required_by.push(package.name.clone());
+        }
+        if package
+            .optdepends
+            .iter()
+            .any(|dep| satisfies.contains(&dependency_name(dep)))
+        {
+            optional_for.push(package.name.clone());
+        }
+    }
+    (required_by, optional_for)
+}
+
+fn join_or_none(values: &[String]) -> String {
+    if values.is_empty() {
+        "None".to_string()
+    } else {
+        values.join("  ")
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, UNITS[unit])
+}
+
+/// A unix timestamp in pacman's local date format.
+pub fn format_date(unix_ts: i64) -> String {
+    chrono::DateTime::from_timestamp(unix_ts, 0)
+        .map(|dt| {
+            dt.with_timezone(&chrono::Local)
+                .format("%a %d %b %Y %I:%M:%S %p %Z")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dependency_name, parse_desc, read_local_db, reverse_dependencies};
+    use std::path::Path;
+
+    const FIXTURE_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/local");
+
+    #[test]
+    fn parses_desc_fields() {
+        let desc = "%NAME%\nbash\n\n%VERSION%\n5.2.037-1\n\n%DESC%\nThe GNU Bourne Again shell\n\n%SIZE%\n9563136\n\n%REASON%\n1\n\n%DEPENDS%\nreadline\nglibc\n\n%PROVIDES%\nsh\n";
+        let package = parse_desc(desc).expect("parse desc");
+        assert_eq!(package.name, "bash");
+        assert_eq!(package.version, "5.2.037-1");
+        assert_eq!(package.size, 9_563_136);
+        assert!(!package.explicit);
+        assert_eq!(package.depends, vec!["readline", "glibc"]);
+        assert_eq!(package.provides, vec!["sh"]);
+        assert!(parse_desc("%VERSION%\n1.0\n").is_none());
+    }
+
+    #[test]
+    fn reads_fixture_database_with_reverse_dependencies() {
+        let packages = read_local_db(Path::new(FIXTURE_DB)).expect("read fixture db");
+        let names: Vec<_> = packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["bash", "gtk4", "paru"]);
+
+        let bash = &packages[0];
+        let (required_by, optional_for) = reverse_dependencies(&packages, bash);
+        assert_eq!(required_by, vec!["paru"]);
+        assert!(optional_for.is_empty());
+
+        let details = bash.to_details(&required_by, &optional_for);
+        assert_eq!(details.installed_size, "9.12 MiB");
+        assert_eq!(
+            details.install_reason,
+            "Installed as a dependency for another package"
+        );
+        assert_eq!(details.required_by, "paru");
+        assert_eq!(details.optional_for, "None");
+    }
+
+    #[test]
+    fn strips_dependency_constraints() {
+        assert_eq!(dependency_name("glibc>=2.39"), "glibc");
+        assert_eq!(dependency_name("libreadline.so=8-64"), "libreadline.so");
+        assert_eq!(dependency_name("bat: colored pkgbuild printing"), "bat");
+    }
+}
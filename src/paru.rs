@@ -1,8 +1,29 @@
+use crate::aur::{AurClient, AurPackage};
+use crate::error::{BackendError, ErrorKind};
 use crate::logger::{log_debug, log_error, log_info, log_warning};
+use crate::pacman_db;
+use crate::preview::{PreviewRequest, TransactionPreview};
+use crate::privilege::Escalation;
+use crate::prompts::{self, PromptHandler};
+use crate::pty::PtyEvent;
+use crate::runner::{self, CommandSpec};
 use crate::settings;
+use crate::sync_db;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::process::Command;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+const PARTIAL_OUTPUT_IDLE: Duration = Duration::from_millis(400);
+const PROMPT_CONTEXT_LINES: usize = 20;
+
+thread_local! {
+    /// Set while [`ParuBackend::unattended`] runs on this thread.
+    static UNATTENDED: Cell<bool> = const { Cell::new(false) };
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Package {
@@ -11,6 +32,8 @@ pub struct Package {
     pub description: String,
     pub repository: String,
     pub installed_version: Option<String>,
+    #[serde(default)]
+    pub installed_size: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -38,6 +61,10 @@ pub struct PackageDetails {
     pub validated_by: String,
     pub votes: String,
     pub popularity: String,
+    pub maintainer: String,
+    pub package_base: String,
+    pub out_of_date: String,
+    pub last_modified: String,
 }
 
 #[derive(Debug, Clone)]
@@ -65,26 +92,189 @@ pub struct ParuBackend;
 
 impl ParuBackend {
     pub fn is_paru_installed() -> bool {
-        Self::command_exists("paru")
+        runner::command_exists("paru")
     }
 
     // UPDATED: Added limit parameter for performance
-    pub fn search_packages(query: &str, limit: Option<usize>) -> Result<Vec<Package>, String> {
+    pub fn search_packages(
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Package>, BackendError> {
+        Self::paru_search(&[], query, limit)
+    }
+
+    /// Search the AUR only; pairs with [`Self::search_repo_packages`]. Asks the
+    /// RPC directly and falls back to `paru -Ss --aur` when it is unreachable.
+    pub fn search_aur_packages(
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Package>, BackendError> {
+        Self::search_aur_packages_with(
+            &AurClient::from_settings(),
+            Path::new(pacman_db::LOCAL_DB_PATH),
+            query,
+            limit,
+        )
+    }
+
+    fn search_aur_packages_with(
+        client: &AurClient,
+        local_db: &Path,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Package>, BackendError> {
+        // The RPC matches a single keyword; like paru, send the longest term
+        // and require the others locally.
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let Some(keyword) = terms.iter().max_by_key(|term| term.len()) else {
+            return Ok(Vec::new());
+        };
+
+        let results = match client.search(keyword) {
+            Ok(results) => results,
+            Err(e) => {
+                log_warning(&format!(
+                    "AUR RPC search failed ({}), falling back to paru",
+                    e
+                ));
+                return Self::paru_search(&["--aur"], query, limit);
+            }
+        };
+
+        let local = pacman_db::cached_local_db(local_db).unwrap_or_default();
+        let installed: HashMap<&str, &str> = local
+            .iter()
+            .map(|p| (p.name.as_str(), p.version.as_str()))
+            .collect();
+
+        let mut results: Vec<AurPackage> = results
+            .into_iter()
+            .filter(|p| {
+                let text =
+                    format!("{} {}", p.name, p.description.as_deref().unwrap_or("")).to_lowercase();
+                terms.iter().all(|term| text.contains(term.as_str()))
+            })
+            .collect();
+        // Keep the most popular packages when the limit cuts the list short.
+        results.sort_by_key(|p| std::cmp::Reverse(p.num_votes));
+        if let Some(l) = limit {
+            results.truncate(l);
+        }
+
+        let packages: Vec<Package> = results
+            .into_iter()
+            .map(|p| Package {
+                installed_version: installed.get(p.name.as_str()).map(|v| v.to_string()),
+                name: p.name,
+                version: p.version,
+                description: p.description.unwrap_or_default(),
+                repository: "aur".to_string(),
+                installed_size: None,
+            })
+            .collect();
+        log_info(&format!(
+            "AUR search completed: found {} packages",
+            packages.len()
+        ));
+        Ok(packages)
+    }
+
+    /// Search the official repositories straight from the sync databases.
+    /// Needs no network and returns in milliseconds once the index is cached.
+    pub fn search_repo_packages(
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Package>, BackendError> {
+        Self::search_repo_packages_in(
+            Path::new(sync_db::SYNC_DB_PATH),
+            Path::new(sync_db::PACMAN_CONF_PATH),
+            Path::new(pacman_db::LOCAL_DB_PATH),
+            query,
+            limit,
+        )
+    }
+
+    fn search_repo_packages_in(
+        sync_dir: &Path,
+        pacman_conf: &Path,
+        local_db: &Path,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Package>, BackendError> {
+        log_debug(&format!("Searching sync databases with query: {}", query));
+
+        let index = sync_db::cached_index(sync_dir, pacman_conf).map_err(|e| {
+            let err = format!("Failed to read {}: {}", sync_dir.display(), e);
+            log_warning(&err);
+            BackendError::new(ErrorKind::Other, err)
+        })?;
+        if index.is_empty() {
+            return Err(BackendError::new(
+                ErrorKind::Other,
+                format!("No sync databases found in {}", sync_dir.display()),
+            ));
+        }
+
+        let local = pacman_db::cached_local_db(local_db).unwrap_or_default();
+        let installed: HashMap<&str, &str> = local
+            .iter()
+            .map(|p| (p.name.as_str(), p.version.as_str()))
+            .collect();
+
+        let mut packages: Vec<Package> = index
+            .search(query)
+            .into_iter()
+            .map(|entry| Package {
+                name: entry.package.name.clone(),
+                version: entry.package.version.clone(),
+                description: entry.package.description.clone(),
+                repository: entry.repository.clone(),
+                installed_version: installed
+                    .get(entry.package.name.as_str())
+                    .map(|v| v.to_string()),
+                installed_size: Some(entry.package.size),
+            })
+            .collect();
+        if let Some(l) = limit {
+            packages.truncate(l);
+        }
+
+        log_info(&format!(
+            "Sync database search completed: found {} packages in {} indexed",
+            packages.len(),
+            index.len()
+        ));
+        Ok(packages)
+    }
+
+    fn paru_search(
+        extra_args: &[&str],
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Package>, BackendError> {
         log_debug(&format!("Searching packages with query: {}", query));
 
-        let output = Command::new("paru")
+        let spec = CommandSpec::new("paru")
             .arg("-Ss")
-            .arg(query)
-            .output()
-            .map_err(|e| {
-                let err = format!("Failed to execute paru: {}", e);
-                log_error(&err);
-                err
-            })?;
+            .args(extra_args.iter().copied())
+            .arg(query);
+        let output = spec.output().map_err(|e| {
+            let err = BackendError::spawn_failed(&spec, &e);
+            log_error(&format!("Failed to execute paru: {}", e));
+            err
+        })?;
 
         if !output.status.success() {
+            // paru exits 1 when nothing matched; that is not an error.
+            if output.status.code() == Some(1) && output.stdout.is_empty() {
+                return Ok(Vec::new());
+            }
             log_error("Paru search failed");
-            return Err("Paru search failed".to_string());
+            return Err(BackendError::from_output(
+                &spec,
+                &output,
+                "Paru search failed",
+            ));
         }
 
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -104,23 +294,70 @@ impl ParuBackend {
         Ok(packages)
     }
 
-    pub fn list_installed() -> Result<Vec<Package>, String> {
+    pub fn list_installed() -> Result<Vec<Package>, BackendError> {
+        Self::list_installed_from(
+            Path::new(pacman_db::LOCAL_DB_PATH),
+            Path::new(sync_db::SYNC_DB_PATH),
+            Path::new(sync_db::PACMAN_CONF_PATH),
+        )
+    }
+
+    fn list_installed_from(
+        local_db: &Path,
+        sync_dir: &Path,
+        pacman_conf: &Path,
+    ) -> Result<Vec<Package>, BackendError> {
         log_debug("Listing installed packages with descriptions");
 
+        // Fast path: read the local database directly instead of parsing pacman's text.
+        match pacman_db::cached_local_db(local_db) {
+            Ok(local) if !local.is_empty() => {
+                let foreign_set = Self::foreign_packages_in(&local, sync_dir, pacman_conf);
+                let packages: Vec<Package> = local
+                    .iter()
+                    .map(|p| {
+                        let repository = if foreign_set.contains(&p.name) {
+                            "aur"
+                        } else {
+                            "repo"
+                        };
+                        p.to_package(repository)
+                    })
+                    .collect();
+                log_info(&format!(
+                    "Listed {} installed packages from {} ({} from AUR)",
+                    packages.len(),
+                    local_db.display(),
+                    foreign_set.len()
+                ));
+                return Ok(packages);
+            }
+            Ok(_) => log_warning(&format!(
+                "No packages found in {}, falling back to pacman -Q",
+                local_db.display()
+            )),
+            Err(e) => log_warning(&format!(
+                "Failed to read {}: {}, falling back to pacman -Q",
+                local_db.display(),
+                e
+            )),
+        }
+
         // 1. Get name and version
-        let output = Command::new("pacman")
-            .env("LANG", "C")
-            .arg("-Q")
-            .output()
-            .map_err(|e| {
-                let err = format!("Failed to execute pacman -Q: {}", e);
-                log_error(&err);
-                err
-            })?;
+        let spec = CommandSpec::new("pacman").env("LANG", "C").arg("-Q");
+        let output = spec.output().map_err(|e| {
+            let err = BackendError::spawn_failed(&spec, &e);
+            log_error(&format!("Failed to execute pacman -Q: {}", e));
+            err
+        })?;
 
         if !output.status.success() {
             log_error("Failed to list installed packages via pacman -Q");
-            return Err("Failed to list installed packages".to_string());
+            return Err(BackendError::from_output(
+                &spec,
+                &output,
+                "Failed to list installed packages",
+            ));
         }
 
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -129,7 +366,10 @@ impl ParuBackend {
         // 2. Get all descriptions in one go via pacman -Qi
         // This is much faster than individual calls
         let mut descriptions = HashMap::new();
-        if let Ok(details_output) = Command::new("pacman").env("LANG", "C").arg("-Qi").output()
+        if let Ok(details_output) = CommandSpec::new("pacman")
+            .env("LANG", "C")
+            .arg("-Qi")
+            .output()
             && details_output.status.success()
         {
             let details_stdout = String::from_utf8_lossy(&details_output.stdout);
@@ -172,7 +412,7 @@ impl ParuBackend {
         Ok(packages)
     }
 
-    pub fn list_updates() -> Result<Vec<Package>, String> {
+    pub fn list_updates() -> Result<Vec<Package>, BackendError> {
         log_debug("Checking for available updates");
 
         let mut packages = Vec::new();
@@ -180,11 +420,11 @@ impl ParuBackend {
 
         // 1. Try to get official repo updates via checkupdates (safely syncs DB)
         // If checkupdates is missing, we rely on paru -Qu (local DB only)
-        let use_checkupdates = Self::command_exists("checkupdates");
+        let use_checkupdates = runner::command_exists("checkupdates");
 
         if use_checkupdates {
             log_info("Using checkupdates for repo updates");
-            match Command::new("checkupdates").output() {
+            match CommandSpec::new("checkupdates").output() {
                 Ok(output) => {
                     if output.status.success() {
                         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -202,12 +442,11 @@ impl ParuBackend {
         // 2. Get AUR updates (or all if checkupdates failed/missing) via paru
         // -Qu: Upgradeable
         // -a: AUR only (if we used checkupdates), otherwise omit -a to get all
-        let mut cmd = Command::new("paru");
-        cmd.arg("-Qu").arg("--noconfirm"); // Non-interactive
+        let mut cmd = CommandSpec::new("paru").arg("-Qu").arg("--noconfirm"); // Non-interactive
 
         // If we successfully used checkupdates, we only need AUR from paru
         if use_checkupdates {
-            cmd.arg("-a");
+            cmd = cmd.arg("-a");
         }
 
         match cmd.output() {
@@ -288,6 +527,7 @@ impl ParuBackend {
                         description: String::new(),
                         repository: repo.to_string(),
                         installed_version: Some(parts[1].to_string()),
+                        installed_size: None,
                     })
                 } else {
                     None
@@ -297,35 +537,46 @@ impl ParuBackend {
     }
 
     pub fn is_aur_package(name: &str) -> bool {
-        let output = Command::new("pacman").arg("-Si").arg(name).output();
-
-        if let Ok(output) = output
-            && output.status.success()
-        {
-            return false;
+        // Anything the sync databases know comes from a repository.
+        match sync_db::cached_index(
+            Path::new(sync_db::SYNC_DB_PATH),
+            Path::new(sync_db::PACMAN_CONF_PATH),
+        ) {
+            Ok(index) if !index.is_empty() => {
+                if index.contains(name) {
+                    return false;
+                }
+            }
+            _ => {
+                if let Ok(output) = CommandSpec::new("pacman").arg("-Si").arg(name).output()
+                    && output.status.success()
+                {
+                    return false;
+                }
+            }
         }
 
-        true
+        match AurClient::from_settings().info(name) {
+            Ok(found) => found.is_some(),
+            // Offline: assume anything outside the repositories is from the AUR.
+            Err(_) => true,
+        }
     }
 
-    pub fn get_pkgbuild(package_name: &str) -> Result<String, String> {
+    pub fn get_pkgbuild(package_name: &str) -> Result<String, BackendError> {
         log_debug(&format!("Fetching PKGBUILD for package: {}", package_name));
 
-        let output = Command::new("paru")
-            .arg("-Gp")
-            .arg(package_name)
-            .output()
-            .map_err(|e| {
-                let err = format!("Failed to fetch PKGBUILD: {}", e);
-                log_error(&err);
-                err
-            })?;
+        let spec = CommandSpec::new("paru").arg("-Gp").arg(package_name);
+        let output = spec.output().map_err(|e| {
+            log_error(&format!("Failed to fetch PKGBUILD: {}", e));
+            BackendError::spawn_failed(&spec, &e)
+        })?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             let err = format!("Failed to get PKGBUILD: {}", stderr);
             log_error(&err);
-            return Err(err);
+            return Err(BackendError::from_output(&spec, &output, err));
         }
 
         let pkgbuild = String::from_utf8_lossy(&output.stdout).to_string();
@@ -333,7 +584,7 @@ impl ParuBackend {
         if pkgbuild.trim().is_empty() {
             let err = "PKGBUILD is empty or package not found".to_string();
             log_warning(&format!("{} for package: {}", err, package_name));
-            return Err(err);
+            return Err(BackendError::new(ErrorKind::PackageNotFound, err));
         }
 
         log_info(&format!(
@@ -343,83 +594,369 @@ impl ParuBackend {
         Ok(pkgbuild)
     }
 
-    pub fn install_package<F>(
-        name: &str,
+    /// Install several packages in one paru transaction.
+    pub fn install_packages<F>(
+        names: &[&str],
         output_callback: F,
         cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
-    ) -> Result<(), String>
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
     where
         F: Fn(String) + Send + Sync + 'static,
     {
-        log_info(&format!("Starting installation of package: {}", name));
-        let result = Self::run_paru_in_terminal(
-            &["-S", "--noconfirm", name],
-            output_callback,
-            cancel_requested,
-        );
+        let list = names.join(" ");
+        log_info(&format!("Starting installation of package(s): {}", list));
+        let mut args = vec!["-S"];
+        args.extend(Self::confirmation_args());
+        args.extend(names);
+        let result = Self::run_paru(&args, output_callback, cancel_requested, prompt_handler);
 
         match &result {
-            Ok(_) => log_info(&format!("Successfully installed package: {}", name)),
-            Err(e) => log_error(&format!(
-                "Failed to
-                                       install package {}: {}",
-                name, e
-            )),
+            Ok(_) => log_info(&format!("Successfully installed package(s): {}", list)),
+            Err(e) => log_error(&format!("Failed to install package(s) {}: {}", list, e)),
+        }
+
+        result
+    }
+
+    /// Remove several packages in one paru transaction.
+    pub fn remove_packages<F>(
+        names: &[&str],
+        output_callback: F,
+        cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        let list = names.join(" ");
+        log_info(&format!("Starting removal of package(s): {}", list));
+
+        let mut args = vec!["-Rns"];
+        args.extend(Self::confirmation_args());
+        args.extend(names);
+        let result = Self::run_paru(&args, output_callback, cancel_requested, prompt_handler);
+
+        match &result {
+            Ok(_) => log_info(&format!("Successfully removed package(s): {}", list)),
+            Err(e) => log_error(&format!("Failed to remove package(s) {}: {}", list, e)),
+        }
+
+        result
+    }
+
+    /// Install already built package files (`paru -U`).
+    pub fn install_package_files<F>(
+        paths: &[&str],
+        output_callback: F,
+        cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        log_info(&format!("Installing package files: {}", paths.join(" ")));
+        let mut args = vec!["-U"];
+        args.extend(Self::confirmation_args());
+        args.extend(paths);
+        let result = Self::run_paru(&args, output_callback, cancel_requested, prompt_handler);
+
+        if let Err(e) = &result {
+            log_error(&format!("Failed to install package files: {}", e));
+        }
+        result
+    }
+
+    /// Whether `path` looks like a package file `-U` can install.
+    pub fn is_package_file(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(Self::package_name_from_file)
+            .is_some()
+            && path.extension().is_none_or(|ext| ext != "sig")
+    }
+
+    /// The PKGBUILD in the local build directory `dir`, for review before building it.
+    pub fn read_local_pkgbuild(dir: &Path) -> Result<String, BackendError> {
+        let path = dir.join("PKGBUILD");
+        fs::read_to_string(&path).map_err(|e| {
+            BackendError::new(
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    ErrorKind::PackageNotFound
+                } else {
+                    ErrorKind::Other
+                },
+                format!("Failed to read {}: {}", path.display(), e),
+            )
+        })
+    }
+
+    /// Build the PKGBUILD in the local directory `dir` and install what it
+    /// builds, with its dependencies.
+    pub fn build_local_package<F>(
+        dir: &str,
+        output_callback: F,
+        cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        log_info(&format!("Building local PKGBUILD in {}", dir));
+        if !Path::new(dir).join("PKGBUILD").is_file() {
+            return Err(BackendError::new(
+                ErrorKind::PackageNotFound,
+                format!("{} does not contain a PKGBUILD", dir),
+            ));
         }
+        let mut args = vec!["-Bi"];
+        args.extend(Self::confirmation_args());
+        args.push(dir);
+        let result = Self::run_paru(&args, output_callback, cancel_requested, prompt_handler);
 
+        if let Err(e) = &result {
+            log_error(&format!("Failed to build {}: {}", dir, e));
+        }
         result
     }
 
-    pub fn remove_package<F>(
+    /// Where AUR packages are cloned and built ahead of a combined install.
+    pub fn aur_build_dir() -> PathBuf {
+        let mut path = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+        path.push("parut");
+        path.push("build");
+        path
+    }
+
+    /// Install everything the AUR packages `names` need to build, so the builds
+    /// themselves don't need the database lock. Packages of `names` that others
+    /// of them depend on are installed here as well (paru builds them), since
+    /// makepkg would otherwise install them without the lock. Returns those, so
+    /// their own builds can be skipped.
+    pub fn install_build_dependencies<F>(
+        names: &[&str],
+        output_callback: F,
+        cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
+        prompt_handler: PromptHandler,
+    ) -> Result<Vec<String>, BackendError>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        let packages = AurClient::from_settings().multiinfo(names)?;
+        let mut deps: Vec<String> = Vec::new();
+        let mut needed_first: Vec<String> = Vec::new();
+        for package in &packages {
+            for dep in package
+                .depends
+                .iter()
+                .chain(&package.make_depends)
+                .chain(&package.check_depends)
+            {
+                let dep_name = pacman_db::dependency_name(dep);
+                if names.contains(&dep_name) {
+                    if !needed_first.iter().any(|name| name == dep_name) {
+                        needed_first.push(dep_name.to_string());
+                    }
+                } else if !deps.contains(dep) {
+                    deps.push(dep.clone());
+                }
+            }
+        }
+
+        let missing = Self::missing_dependencies(&deps)?;
+        if missing.is_empty() && needed_first.is_empty() {
+            output_callback("All build dependencies are already installed.".to_string());
+            return Ok(Vec::new());
+        }
+        let output_callback = std::sync::Arc::new(output_callback);
+        if !missing.is_empty() {
+            let mut args = vec!["-S", "--asdeps", "--needed"];
+            args.extend(Self::confirmation_args());
+            args.extend(missing.iter().map(|dep| pacman_db::dependency_name(dep)));
+            let sink = output_callback.clone();
+            Self::run_paru(
+                &args,
+                move |line| sink(line),
+                cancel_requested.clone(),
+                prompt_handler.clone(),
+            )?;
+        }
+        if !needed_first.is_empty() {
+            output_callback(format!(
+                "Installing {} first, other packages build against it",
+                needed_first.join(" ")
+            ));
+            let mut args = vec!["-S", "--needed"];
+            args.extend(Self::confirmation_args());
+            args.extend(needed_first.iter().map(String::as_str));
+            let sink = output_callback.clone();
+            Self::run_paru(
+                &args,
+                move |line| sink(line),
+                cancel_requested,
+                prompt_handler,
+            )?;
+        }
+        Ok(needed_first)
+    }
+
+    /// The dependencies in `deps` that no installed package satisfies (`pacman -T`).
+    fn missing_dependencies(deps: &[String]) -> Result<Vec<String>, BackendError> {
+        if deps.is_empty() {
+            return Ok(Vec::new());
+        }
+        let spec = CommandSpec::new("pacman")
+            .arg("-T")
+            .args(deps.iter().cloned());
+        let output = spec
+            .output()
+            .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
+        // 127 means some are missing; pacman lists them on stdout.
+        if !output.status.success() && output.status.code() != Some(127) {
+            return Err(BackendError::from_output(
+                &spec,
+                &output,
+                "Failed to check build dependencies",
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Clone or update an AUR package and build it without installing it.
+    /// Returns the package files built for `name`.
+    pub fn build_aur_package<F>(
         name: &str,
         output_callback: F,
         cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
-    ) -> Result<(), String>
+        prompt_handler: PromptHandler,
+    ) -> Result<Vec<String>, BackendError>
     where
         F: Fn(String) + Send + Sync + 'static,
     {
-        log_info(&format!("Starting removal of package: {}", name));
+        log_info(&format!("Building AUR package: {}", name));
+        let client = AurClient::from_settings();
+        let Some(package) = client.info(name)? else {
+            return Err(BackendError::new(
+                ErrorKind::PackageNotFound,
+                format!("{} was not found in the AUR", name),
+            ));
+        };
+
+        let root = Self::aur_build_dir();
+        let clone_dir = root.join(&package.package_base);
+        let pkgdest = root.join("packages").join(&package.package_base);
+        let _ = fs::remove_dir_all(&pkgdest);
+        fs::create_dir_all(&pkgdest).map_err(|e| {
+            BackendError::new(
+                ErrorKind::Other,
+                format!("Failed to create {}: {}", pkgdest.display(), e),
+            )
+        })?;
+
+        let clone_path = clone_dir.to_string_lossy().to_string();
+        let git = if clone_dir.join(".git").is_dir() {
+            CommandSpec::new("git")
+                .arg("-C")
+                .arg(&clone_path)
+                .args(["pull", "--ff-only"])
+        } else {
+            CommandSpec::new("git")
+                .arg("clone")
+                .arg(client.git_url(&package.package_base))
+                .arg(&clone_path)
+        };
+        output_callback(format!("Running: {}", git.command_line()));
+        let output = git
+            .output()
+            .map_err(|e| BackendError::spawn_failed(&git, &e))?;
+        if !output.status.success() {
+            return Err(BackendError::from_output(
+                &git,
+                &output,
+                format!("Failed to fetch the sources of {}", package.package_base),
+            ));
+        }
 
-        let result = Self::run_paru_in_terminal(
-            &["-Rns", "--noconfirm", name],
+        let mut args = vec!["-B"];
+        args.extend(Self::confirmation_args());
+        args.push(&clone_path);
+        // makepkg writes packages to PKGDEST; the environment overrides makepkg.conf.
+        let pkgdest_path = pkgdest.to_string_lossy().to_string();
+        Self::run_paru_with_env(
+            &args,
+            &[("PKGDEST", &pkgdest_path)],
             output_callback,
             cancel_requested,
-        );
+            prompt_handler,
+        )?;
+
+        let built = Self::built_packages(&pkgdest, name);
+        if built.is_empty() {
+            return Err(BackendError::new(
+                ErrorKind::Other,
+                format!("No package for {} was built in {}", name, pkgdest_path),
+            ));
+        }
+        log_info(&format!("Built {}: {}", name, built.join(" ")));
+        Ok(built)
+    }
 
-        match &result {
-            Ok(_) => log_info(&format!("Successfully removed package: {}", name)),
-            Err(e) => log_error(&format!(
-                "Failed to
-                                       remove package {}: {}",
-                name, e
-            )),
+    /// Package files in `dir` for `name`. Split packages build their siblings
+    /// too; those are left out unless none of the files is `name` itself.
+    fn built_packages(dir: &Path, name: &str) -> Vec<String> {
+        let mut files: Vec<(String, String)> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if file_name.ends_with(".sig") {
+                    return None;
+                }
+                let package = Self::package_name_from_file(&file_name)?.to_string();
+                Some((package, entry.path().to_string_lossy().to_string()))
+            })
+            .collect();
+        files.sort();
+        if files.iter().any(|(package, _)| package == name) {
+            files.retain(|(package, _)| package == name);
         }
+        files.into_iter().map(|(_, path)| path).collect()
+    }
 
-        result
+    /// `ripgrep-14.1.1-1-x86_64.pkg.tar.zst` -> `ripgrep`
+    pub fn package_name_from_file(file_name: &str) -> Option<&str> {
+        let stem = &file_name[..file_name.find(".pkg.tar")?];
+        // name-pkgver-pkgrel-arch; the name itself may contain dashes.
+        let mut parts = stem.rsplitn(4, '-');
+        parts.nth(2)?;
+        parts.next()
     }
 
     pub fn update_system<F>(
         output_callback: F,
         cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
-    ) -> Result<(), String>
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
     where
         F: Fn(String) + Send + Sync + 'static,
     {
         log_info("Starting system update");
         let settings = settings::get();
-        let mut args = vec!["-Syu", "--noconfirm"];
+        let mut args = vec!["-Syu"];
+        args.extend(Self::confirmation_args());
         match settings.default_update_scope.as_str() {
             "repo-only" => args.push("--repo"),
             "aur-only" => args.push("--aur"),
             _ => {}
         }
 
-        let ignored: Vec<String> = settings
-            .ignored_updates
-            .iter()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
+        let ignored = Self::ignored_updates(&settings);
         let mut owned_args: Vec<String> = args.into_iter().map(ToString::to_string).collect();
         if !ignored.is_empty() {
             owned_args.push("--ignore".to_string());
@@ -427,7 +964,7 @@ impl ParuBackend {
         }
         let arg_refs: Vec<&str> = owned_args.iter().map(String::as_str).collect();
 
-        let result = Self::run_paru_in_terminal(&arg_refs, output_callback, cancel_requested);
+        let result = Self::run_paru(&arg_refs, output_callback, cancel_requested, prompt_handler);
 
         match &result {
             Ok(_) => log_info("System update completed successfully"),
@@ -437,21 +974,41 @@ impl ParuBackend {
         result
     }
 
+    /// The packages system updates skip (`--ignore`).
+    fn ignored_updates(settings: &settings::AppSettings) -> Vec<String> {
+        settings
+            .ignored_updates
+            .iter()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    /// What [`ParuBackend::update_system`] would upgrade from the repositories,
+    /// skipping the same packages. `None` when it only updates AUR packages,
+    /// which pacman can't preview.
+    pub fn system_update_preview() -> Option<PreviewRequest> {
+        let settings = settings::get();
+        (settings.default_update_scope != "aur-only").then(|| PreviewRequest::SystemUpdate {
+            ignored: Self::ignored_updates(&settings),
+        })
+    }
+
     pub fn update_package<F>(
         name: &str,
         output_callback: F,
         cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
-    ) -> Result<(), String>
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
     where
         F: Fn(String) + Send + Sync + 'static,
     {
         log_info(&format!("Starting update of package: {}", name));
 
-        let result = Self::run_paru_in_terminal(
-            &["-S", "--noconfirm", name],
-            output_callback,
-            cancel_requested,
-        );
+        let mut args = vec!["-S"];
+        args.extend(Self::confirmation_args());
+        args.push(name);
+        let result = Self::run_paru(&args, output_callback, cancel_requested, prompt_handler);
 
         match &result {
             Ok(_) => log_info(&format!("Successfully updated package: {}", name)),
@@ -464,15 +1021,17 @@ impl ParuBackend {
     pub fn clean_cache<F>(
         output_callback: F,
         cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
-    ) -> Result<(), String>
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
     where
         F: Fn(String) + Send + Sync + 'static,
     {
         log_info("Starting cache cleanup");
 
         // -Sc removes uninstalled packages from cache
-        let result =
-            Self::run_paru_in_terminal(&["-Sc", "--noconfirm"], output_callback, cancel_requested);
+        let mut args = vec!["-Sc"];
+        args.extend(Self::confirmation_args());
+        let result = Self::run_paru(&args, output_callback, cancel_requested, prompt_handler);
 
         match &result {
             Ok(_) => log_info("Cache cleanup completed successfully"),
@@ -491,7 +1050,7 @@ impl ParuBackend {
         };
         let pacman_cache = Self::dir_size_bytes("/var/cache/pacman/pkg");
 
-        let orphan_count = Command::new("pacman")
+        let orphan_count = CommandSpec::new("pacman")
             .arg("-Qtdq")
             .output()
             .ok()
@@ -512,18 +1071,56 @@ impl ParuBackend {
         }
     }
 
+    /// What `request` would install and remove, from pacman's `--print` output.
+    /// Nothing is changed and no root is needed.
+    pub fn preview_transaction(
+        request: &PreviewRequest,
+    ) -> Result<TransactionPreview, BackendError> {
+        let Some(args) = request.pacman_args() else {
+            return Ok(TransactionPreview::from_print(request, "", &[], &[]));
+        };
+        let spec = CommandSpec::new("pacman").env("LANG", "C").args(args);
+        log_debug(&format!("Previewing transaction: {}", spec.command_line()));
+        let output = spec
+            .output()
+            .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
+        if !output.status.success() {
+            return Err(BackendError::from_output(
+                &spec,
+                &output,
+                "pacman could not resolve the transaction",
+            ));
+        }
+
+        let local =
+            pacman_db::cached_local_db(Path::new(pacman_db::LOCAL_DB_PATH)).unwrap_or_default();
+        let sync = sync_db::cached_index(
+            Path::new(sync_db::SYNC_DB_PATH),
+            Path::new(sync_db::PACMAN_CONF_PATH),
+        )
+        .unwrap_or_default();
+        Ok(TransactionPreview::from_print(
+            request,
+            &String::from_utf8_lossy(&output.stdout),
+            &local,
+            &sync.packages,
+        ))
+    }
+
     pub fn remove_orphans<F>(
         output_callback: F,
         cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
-    ) -> Result<(), String>
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
     where
         F: Fn(String) + Send + Sync + 'static,
     {
         log_info("Starting orphan removal");
 
         // -c removes orphans (recursive)
-        let result =
-            Self::run_paru_in_terminal(&["-c", "--noconfirm"], output_callback, cancel_requested);
+        let mut args = vec!["-c"];
+        args.extend(Self::confirmation_args());
+        let result = Self::run_paru(&args, output_callback, cancel_requested, prompt_handler);
 
         match &result {
             Ok(_) => log_info("Orphan removal completed successfully"),
@@ -533,37 +1130,102 @@ impl ParuBackend {
         result
     }
 
-    pub fn get_package_details(name: &str) -> Result<PackageDetails, String> {
+    pub fn get_package_details(name: &str) -> Result<PackageDetails, BackendError> {
+        Self::get_package_details_from(
+            Path::new(pacman_db::LOCAL_DB_PATH),
+            Path::new(sync_db::SYNC_DB_PATH),
+            Path::new(sync_db::PACMAN_CONF_PATH),
+            name,
+        )
+    }
+
+    fn get_package_details_from(
+        local_db: &Path,
+        sync_dir: &Path,
+        pacman_conf: &Path,
+        name: &str,
+    ) -> Result<PackageDetails, BackendError> {
+        let local = pacman_db::cached_local_db(local_db).unwrap_or_default();
+        let mut details = match local.iter().find(|p| p.name == name) {
+            Some(package) => {
+                let (required_by, optional_for) = pacman_db::reverse_dependencies(&local, package);
+                let mut details = package.to_details(&required_by, &optional_for);
+                details.repository = match sync_db::cached_index(sync_dir, pacman_conf) {
+                    Ok(index) if !index.is_empty() => {
+                        index.repository_of(name).unwrap_or("aur").to_string()
+                    }
+                    _ if Self::get_foreign_packages().contains(name) => "aur".to_string(),
+                    _ => "repo".to_string(),
+                };
+                details
+            }
+            None => Self::query_package_details(name)?,
+        };
+
+        if details.repository == "aur" {
+            match AurClient::from_settings().info(&details.name) {
+                Ok(Some(aur)) => Self::apply_aur_metadata(&mut details, &aur),
+                Ok(None) => {}
+                Err(e) => log_warning(&format!("Could not load AUR metadata for {}: {}", name, e)),
+            }
+        }
+        Ok(details)
+    }
+
+    fn query_package_details(name: &str) -> Result<PackageDetails, BackendError> {
         let is_installed = Self::is_package_installed(name);
 
         // Use -Qi for installed, -Si for sync/aur
         let flag = if is_installed { "-Qi" } else { "-Si" };
 
-        let output = Command::new("paru")
-            .arg(flag)
-            .arg(name)
+        let spec = CommandSpec::new("paru").arg(flag).arg(name);
+        let output = spec
             .output()
-            .map_err(|e| format!("Failed to execute paru: {}", e))?;
+            .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
 
         if !output.status.success() {
-            return Err(format!("Failed to get details for {}", name));
+            return Err(BackendError::from_output(
+                &spec,
+                &output,
+                format!("Failed to get details for {}", name),
+            ));
         }
 
         let stdout = String::from_utf8_lossy(&output.stdout);
         Self::parse_package_details(&stdout, name)
     }
 
-    pub fn fetch_arch_news(limit: usize) -> Result<Vec<NewsItem>, String> {
+    fn apply_aur_metadata(details: &mut PackageDetails, aur: &AurPackage) {
+        details.votes = aur.num_votes.to_string();
+        details.popularity = format!("{:.2}", aur.popularity);
+        details.maintainer = aur
+            .maintainer
+            .clone()
+            .unwrap_or_else(|| "None (orphaned)".to_string());
+        details.package_base = aur.package_base.clone();
+        details.out_of_date = match aur.out_of_date {
+            Some(flagged) => format!("Yes, flagged {}", pacman_db::format_date(flagged)),
+            None => "No".to_string(),
+        };
+        details.last_modified = pacman_db::format_date(aur.last_modified);
+    }
+
+    pub fn fetch_arch_news(limit: usize) -> Result<Vec<NewsItem>, BackendError> {
         let item_limit = limit.max(1);
-        let output = Command::new("curl")
+        let spec = CommandSpec::new("curl")
             .arg("-fsSL")
-            .arg("https://archlinux.org/feeds/news/")
+            .arg("https://archlinux.org/feeds/news/");
+        let output = spec
             .output()
-            .map_err(|e| format!("Failed to execute curl: {}", e))?;
+            .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Failed to fetch Arch news feed: {}", stderr.trim()));
+            return Err(BackendError::from_output(
+                &spec,
+                &output,
+                format!("Failed to fetch Arch news feed: {}", stderr.trim()),
+            ));
         }
 
         let xml = String::from_utf8_lossy(&output.stdout);
@@ -597,26 +1259,55 @@ impl ParuBackend {
         }
 
         if items.is_empty() {
-            return Err("No news items were found in the feed".to_string());
+            return Err(BackendError::new(
+                ErrorKind::Other,
+                "No news items were found in the feed",
+            ));
         }
         Ok(items)
     }
 
-    pub fn fetch_aur_comments(package_name: &str) -> Result<Vec<AurComment>, String> {
-        log_debug(&format!("Fetching AUR comments for {}", package_name));
-        let url = format!("https://aur.archlinux.org/packages/{}/", package_name);
-
-        let output = Command::new("curl")
-            .arg("-fsSL")
-            .arg(&url)
+    /// Fetch a PGP key into the user's keyring, so makepkg can verify AUR sources.
+    pub fn import_pgp_key(key_id: &str) -> Result<(), BackendError> {
+        log_info(&format!("Importing PGP key {}", key_id));
+        let spec = CommandSpec::new("gpg").arg("--recv-keys").arg(key_id);
+        let output = spec
             .output()
-            .map_err(|e| format!("Failed to execute curl: {}", e))?;
-
+            .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
         if !output.status.success() {
-            return Err("Failed to fetch AUR comments page: HTTP error".to_string());
+            let err = BackendError::from_output(
+                &spec,
+                &output,
+                format!("Failed to import PGP key {}", key_id),
+            );
+            log_error(&format!("{}: {}", err, err.stderr));
+            return Err(err);
         }
+        Ok(())
+    }
 
-        let html = String::from_utf8_lossy(&output.stdout);
+    pub fn fetch_aur_comments(package_name: &str) -> Result<Vec<AurComment>, BackendError> {
+        log_debug(&format!("Fetching AUR comments for {}", package_name));
+        let client = AurClient::from_settings();
+
+        // The RPC has no comments endpoint. They live on the pkgbase page, which
+        // differs from the package name for split packages.
+        let package_base = match client.info(package_name) {
+            Ok(Some(package)) => package.package_base,
+            _ => package_name.to_string(),
+        };
+        let html = client.fetch_page(&client.pkgbase_url(&package_base))?;
+        let comments = Self::parse_aur_comments(&html);
+
+        log_info(&format!(
+            "Fetched {} comments for {}",
+            comments.len(),
+            package_name
+        ));
+        Ok(comments)
+    }
+
+    fn parse_aur_comments(html: &str) -> Vec<AurComment> {
         let mut comments = Vec::new();
 
         // Very basic HTML parsing for comments
@@ -630,9 +1321,12 @@ impl ParuBackend {
             };
             let header = &part[..header_end];
 
-            // Extract author: <a href="/users/AUTHOR/">AUTHOR</a>
-            let author = if let Some(a_start) = header.find("/users/") {
-                let sub = &header[a_start + 7..];
+            // Extract author: <a href="/account/AUTHOR/">AUTHOR</a> (older pages use /users/)
+            let author_link = ["/account/", "/users/"]
+                .iter()
+                .find_map(|prefix| header.find(prefix).map(|idx| idx + prefix.len()));
+            let author = if let Some(a_start) = author_link {
+                let sub = &header[a_start..];
                 if let Some(a_end) = sub.find('/') {
                     sub[..a_end].to_string()
                 } else {
@@ -690,16 +1384,11 @@ impl ParuBackend {
             }
         }
 
-        log_info(&format!(
-            "Fetched {} comments for {}",
-            comments.len(),
-            package_name
-        ));
-        Ok(comments)
+        comments
     }
 
     fn is_package_installed(name: &str) -> bool {
-        Command::new("pacman")
+        CommandSpec::new("pacman")
             .arg("-Qi")
             .arg(name)
             .output()
@@ -707,7 +1396,7 @@ impl ParuBackend {
             .unwrap_or(false)
     }
 
-    fn parse_package_details(output: &str, name: &str) -> Result<PackageDetails, String> {
+    fn parse_package_details(output: &str, name: &str) -> Result<PackageDetails, BackendError> {
         let mut details = PackageDetails {
             name: name.to_string(),
             version: String::new(),
@@ -732,6 +1421,10 @@ impl ParuBackend {
             validated_by: String::new(),
             votes: String::new(),
             popularity: String::new(),
+            maintainer: String::new(),
+            package_base: String::new(),
+            out_of_date: String::new(),
+            last_modified: String::new(),
         };
 
         for line in output.lines() {
@@ -763,6 +1456,10 @@ impl ParuBackend {
                     "Validated By" => details.validated_by = value,
                     "Votes" => details.votes = value,
                     "Popularity" => details.popularity = value,
+                    "Maintainer" => details.maintainer = value,
+                    "Base" | "Package Base" => details.package_base = value,
+                    "Out-of-date" | "Out Of Date" => details.out_of_date = value,
+                    "Last Modified" => details.last_modified = value,
                     _ => {}
                 }
             }
@@ -789,11 +1486,201 @@ impl ParuBackend {
             .replace("&#39;", "'")
     }
 
+    /// Run `f` with nobody there to answer paru: it gets `--noconfirm`, and runs
+    /// in the PTY even in terminal mode, where a terminal would wait for input.
+    pub fn unattended<R>(f: impl FnOnce() -> R) -> R {
+        let previous = UNATTENDED.with(|flag| flag.replace(true));
+        let result = f();
+        UNATTENDED.with(|flag| flag.set(previous));
+        result
+    }
+
+    /// Flags deciding who answers paru's questions: parut's dialogs, the
+    /// external terminal, or paru's own defaults via `--noconfirm`.
+    fn confirmation_args() -> Vec<&'static str> {
+        let settings = settings::get();
+        if !settings.interactive_prompts || UNATTENDED.with(Cell::get) {
+            vec!["--noconfirm"]
+        } else if settings.task_execution_mode == "terminal" {
+            Vec::new()
+        } else {
+            // PKGBUILD review happens in parut; paru's pager would stall the PTY.
+            vec!["--skipreview"]
+        }
+    }
+
+    fn run_paru<F>(
+        args: &[&str],
+        output_callback: F,
+        cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        Self::run_paru_with_env(args, &[], output_callback, cancel_requested, prompt_handler)
+    }
+
+    fn run_paru_with_env<F>(
+        args: &[&str],
+        env: &[(&str, &str)],
+        output_callback: F,
+        cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        match settings::get().task_execution_mode.as_str() {
+            "terminal" if !UNATTENDED.with(Cell::get) => Self::run_paru_in_terminal(
+                args,
+                env,
+                output_callback,
+                cancel_requested,
+                prompt_handler,
+            ),
+            _ => {
+                Self::run_paru_in_pty(args, env, output_callback, cancel_requested, prompt_handler)
+            }
+        }
+    }
+
+    fn run_paru_in_pty<F>(
+        args: &[&str],
+        env: &[(&str, &str)],
+        output_callback: F,
+        cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        let mut spec = CommandSpec::new("paru")
+            .env("LANG", "C")
+            .args(args.iter().copied());
+        for (key, value) in env {
+            spec = spec.env(key, value);
+        }
+        output_callback(format!("Running: {}", spec.command_line()));
+
+        let mut process = spec
+            .spawn_pty()
+            .map_err(|e| BackendError::spawn_failed(&spec, &e))?;
+
+        // Text without a newline (e.g. a sudo password prompt) is only shown once
+        // the process has been quiet for a moment, so partial writes don't spam.
+        let mut pending_partial: Option<(String, Instant)> = None;
+        let mut recent_lines: VecDeque<String> = VecDeque::new();
+        // Prompt text already answered on the current line, so redraws aren't asked twice.
+        let mut answered_prompt: Option<String> = None;
+
+        loop {
+            if cancel_requested() {
+                process.kill();
+                output_callback("Task canceled by user.".to_string());
+                return Err(BackendError::canceled());
+            }
+
+            match process.recv_timeout(Duration::from_millis(200)) {
+                Ok(PtyEvent::Line(line)) => {
+                    pending_partial = None;
+                    answered_prompt = None;
+                    if recent_lines.len() >= PROMPT_CONTEXT_LINES {
+                        recent_lines.pop_front();
+                    }
+                    recent_lines.push_back(line.clone());
+                    output_callback(line);
+                }
+                Ok(PtyEvent::Partial(text)) => {
+                    if answered_prompt.as_deref() == Some(text.trim()) {
+                        continue;
+                    }
+                    let Some(prompt) =
+                        prompts::detect_prompt(recent_lines.make_contiguous(), &text)
+                    else {
+                        pending_partial = Some((text, Instant::now()));
+                        continue;
+                    };
+                    // The prompt line reaches the output once the answer is echoed back.
+                    pending_partial = None;
+                    answered_prompt = Some(text.trim().to_string());
+                    match prompt_handler(prompt) {
+                        Some(answer) => {
+                            if let Err(e) = process.write_line(&answer) {
+                                process.kill();
+                                return Err(BackendError::new(
+                                    ErrorKind::Other,
+                                    format!("Failed to answer paru prompt: {}", e),
+                                ));
+                            }
+                        }
+                        None => {
+                            process.kill();
+                            output_callback("Prompt dismissed - task aborted.".to_string());
+                            return Err(BackendError::canceled());
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some((text, since)) = &pending_partial
+                        && since.elapsed() >= PARTIAL_OUTPUT_IDLE
+                    {
+                        output_callback(text.clone());
+                        pending_partial = None;
+                    }
+                    match process.try_wait() {
+                        Ok(Some(status)) => {
+                            return Self::paru_exit_result(&spec, status, &recent_lines);
+                        }
+                        Ok(None) => {}
+                        Err(e) => return Err(Self::wait_failed(&spec, e)),
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Some((text, _)) = pending_partial.take() {
+                        output_callback(text);
+                    }
+                    return match process.wait() {
+                        Ok(status) => Self::paru_exit_result(&spec, status, &recent_lines),
+                        Err(e) => Err(Self::wait_failed(&spec, e)),
+                    };
+                }
+            }
+        }
+    }
+
+    /// `recent_lines` is the tail of the terminal, used to tell why paru failed.
+    fn paru_exit_result(
+        spec: &CommandSpec,
+        status: std::process::ExitStatus,
+        recent_lines: &VecDeque<String>,
+    ) -> Result<(), BackendError> {
+        if status.success() {
+            return Ok(());
+        }
+        let tail = recent_lines.iter().cloned().collect::<Vec<_>>().join("\n");
+        Err(BackendError::exited(spec, status, &tail))
+    }
+
+    fn wait_failed(spec: &CommandSpec, err: std::io::Error) -> BackendError {
+        BackendError {
+            command: Some(spec.command_line()),
+            ..BackendError::new(
+                ErrorKind::Other,
+                format!("Failed to wait for {}: {}", spec.program, err),
+            )
+        }
+    }
+
+    /// Without a terminal emulator, paru runs in parut instead and gets root
+    /// through pkexec or sudo's askpass rather than a sudo prompt in a terminal.
     fn run_paru_in_terminal<F>(
         args: &[&str],
+        env: &[(&str, &str)],
         output_callback: F,
         cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync>,
-    ) -> Result<(), String>
+        prompt_handler: PromptHandler,
+    ) -> Result<(), BackendError>
     where
         F: Fn(String) + Send + Sync + 'static,
     {
@@ -809,24 +1696,23 @@ impl ParuBackend {
             terminals.retain(|t| *t != preferred);
             terminals.insert(0, preferred);
         }
-        let mut terminal_found = false;
-        let mut last_error = String::new();
+        let mut last_error = None;
 
         for terminal in terminals {
-            if !Self::command_exists(&terminal) {
+            if !runner::command_exists(&terminal) {
                 continue;
             }
-            terminal_found = true;
 
-            let mut cmd = Command::new(&terminal);
-            match terminal.as_str() {
-                "gnome-terminal" => {
-                    cmd.arg("--").arg("paru").args(args);
-                }
-                "konsole" | "xterm" | "xfce4-terminal" | "alacritty" => {
-                    cmd.arg("-e").arg("paru").args(args);
-                }
-                _ => {}
+            let separator = match terminal.as_str() {
+                "gnome-terminal" => "--",
+                _ => "-e",
+            };
+            let mut cmd = CommandSpec::new(&terminal)
+                .arg(separator)
+                .arg("paru")
+                .args(args.iter().copied());
+            for (key, value) in env {
+                cmd = cmd.env(key, value);
             }
 
             output_callback(format!(
@@ -839,10 +1725,9 @@ impl ParuBackend {
                     output_callback("Terminal opened - waiting for completion...".to_string());
                     loop {
                         if cancel_requested() {
-                            let _ = child.kill();
-                            let _ = child.wait();
+                            child.kill();
                             output_callback("Task canceled by user.".to_string());
-                            return Err("Task canceled by user".to_string());
+                            return Err(BackendError::canceled());
                         }
 
                         match child.try_wait() {
@@ -850,45 +1735,61 @@ impl ParuBackend {
                                 if status.success() {
                                     return Ok(());
                                 }
-                                return Err("Operation failed - check terminal output".to_string());
+                                return Err(BackendError {
+                                    command: Some(cmd.command_line()),
+                                    exit_code: status.code(),
+                                    ..BackendError::new(
+                                        ErrorKind::CommandFailed,
+                                        "Operation failed - check terminal output",
+                                    )
+                                });
                             }
                             Ok(None) => {
                                 std::thread::sleep(std::time::Duration::from_millis(200));
                             }
-                            Err(e) => {
-                                return Err(format!("Failed to wait for terminal: {}", e));
-                            }
+                            Err(e) => return Err(Self::wait_failed(&cmd, e)),
                         }
                     }
                 }
-                Err(e) => {
-                    last_error = format!("Failed to spawn {}: {}", terminal, e);
-                }
+                Err(e) => last_error = Some(BackendError::spawn_failed(&cmd, &e)),
             }
         }
 
-        if !terminal_found {
-            Err(format!(
-                "No terminal emulator found. Last error: {}",
-                last_error
-            ))
-        } else {
-            Err(last_error)
+        // Only set when a terminal was found but could not be started.
+        if let Some(err) = last_error {
+            return Err(err);
         }
-    }
 
-    fn command_exists(binary: &str) -> bool {
-        std::env::var_os("PATH")
-            .map(|paths| {
-                std::env::split_paths(&paths)
-                    .map(|p| p.join(binary))
-                    .any(|full| full.is_file())
-            })
-            .unwrap_or(false)
+        let escalation =
+            Escalation::resolve(&settings::get().privilege_helper).map_err(|err| BackendError {
+                message: format!("No terminal emulator found. {}", err.message),
+                ..err
+            })?;
+        output_callback(format!(
+            "No terminal emulator found - running paru here, authenticating through {}",
+            escalation.label()
+        ));
+        let escalation_args = escalation.paru_args();
+        let mut args: Vec<&str> = args.to_vec();
+        args.extend(escalation_args.iter().map(String::as_str));
+        // paru's PKGBUILD pager would stall without a terminal to scroll it in.
+        if !args.contains(&"--noconfirm") {
+            args.push("--skipreview");
+        }
+        let escalation_env = escalation.env();
+        let mut env = env.to_vec();
+        env.extend(escalation_env.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        Self::run_paru_in_pty(
+            &args,
+            &env,
+            output_callback,
+            cancel_requested,
+            prompt_handler,
+        )
     }
 
     fn dir_size_bytes(path: &str) -> u64 {
-        Command::new("du")
+        CommandSpec::new("du")
             .arg("-sb")
             .arg(path)
             .output()
@@ -952,6 +1853,7 @@ impl ParuBackend {
                     description,
                     repository: repository.to_string(),
                     installed_version,
+                    installed_size: None,
                 });
 
                 i += 2;
@@ -976,6 +1878,7 @@ impl ParuBackend {
                         description: String::new(),
                         repository: "unknown".to_string(),
                         installed_version: Some(parts[1].to_string()),
+                        installed_size: None,
                     })
                 } else {
                     None
@@ -984,10 +1887,37 @@ impl ParuBackend {
             .collect()
     }
 
+    /// Installed packages no sync database provides (AUR or locally built).
+    /// Falls back to `pacman -Qm` when the sync databases can't be read.
+    fn foreign_packages_in(
+        local: &[pacman_db::DbPackage],
+        sync_dir: &Path,
+        pacman_conf: &Path,
+    ) -> HashSet<String> {
+        match sync_db::cached_index(sync_dir, pacman_conf) {
+            Ok(index) if !index.is_empty() => {
+                let synced: HashSet<&str> = index
+                    .packages
+                    .iter()
+                    .map(|entry| entry.package.name.as_str())
+                    .collect();
+                local
+                    .iter()
+                    .filter(|p| !synced.contains(p.name.as_str()))
+                    .map(|p| p.name.clone())
+                    .collect()
+            }
+            _ => Self::get_foreign_packages(),
+        }
+    }
+
     fn get_foreign_packages() -> HashSet<String> {
         let mut foreign_set = HashSet::new();
 
-        if let Ok(output) = Command::new("pacman").env("LANG", "C").arg("-Qm").output()
+        if let Ok(output) = CommandSpec::new("pacman")
+            .env("LANG", "C")
+            .arg("-Qm")
+            .output()
             && output.status.success()
         {
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -1008,11 +1938,9 @@ impl ParuBackend {
             return repo_map;
         }
 
-        let mut cmd = Command::new("pacman");
-        cmd.arg("-Si");
-        for name in package_names {
-            cmd.arg(name);
-        }
+        let cmd = CommandSpec::new("pacman")
+            .arg("-Si")
+            .args(package_names.iter().copied());
 
         if let Ok(output) = cmd.output()
             && output.status.success()
@@ -1055,7 +1983,10 @@ impl ParuBackend {
             if foreign_packages.contains(package_name) {
                 repo_map.insert(package_name.clone(), "aur".to_string());
             } else {
-                let output = Command::new("pacman").arg("-Si").arg(package_name).output();
+                let output = CommandSpec::new("pacman")
+                    .arg("-Si")
+                    .arg(package_name)
+                    .output();
 
                 if let Ok(output) = output
                     && output.status.success()
@@ -1113,6 +2044,7 @@ impl ParuBackend {
                         description: String::new(),
                         repository,
                         installed_version: Some(parts[1].to_string()),
+                        installed_size: None,
                     })
                 } else {
                     None
@@ -1125,6 +2057,739 @@ impl ParuBackend {
 #[cfg(test)]
 mod tests {
     use super::ParuBackend;
+    use crate::aur::AurClient;
+    use crate::error::ErrorKind;
+    use crate::preview::PreviewRequest;
+    use crate::prompts::PromptHandler;
+    use crate::pty::PtyEvent;
+    use crate::runner::{FakeRunner, with_runner};
+    use crate::sync_db::testing::{temp_dir, write_sync_db};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    const PACMAN_Q: &str = include_str!("../tests/fixtures/pacman-Q.txt");
+    const PACMAN_QI: &str = include_str!("../tests/fixtures/pacman-Qi.txt");
+    const PACMAN_QM: &str = include_str!("../tests/fixtures/pacman-Qm.txt");
+    const CHECKUPDATES: &str = include_str!("../tests/fixtures/checkupdates.txt");
+    const PARU_QUA: &str = include_str!("../tests/fixtures/paru-Qua.txt");
+    const LOCAL_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/local");
+    const MISSING_DB: &str = "/nonexistent/pacman/local";
+    /// Without a readable pacman.conf every sync database is loaded.
+    const MISSING_CONF: &str = "/nonexistent/pacman.conf";
+
+    fn run_with<R>(runner: FakeRunner, f: impl FnOnce() -> R) -> (R, Arc<FakeRunner>) {
+        let runner = Arc::new(runner);
+        let result = with_runner(runner.clone(), f);
+        (result, runner)
+    }
+
+    /// Sync databases providing `bash` (core) and `gtk4` (extra) from the local
+    /// fixture, which leaves `paru` foreign.
+    fn local_sync_dbs(name: &str) -> std::path::PathBuf {
+        let dir = temp_dir(name);
+        write_sync_db(
+            &dir.join("core.db"),
+            &[("bash-5.2.037-1", "%NAME%\nbash\n\n%VERSION%\n5.2.037-1\n")],
+        );
+        write_sync_db(
+            &dir.join("extra.db"),
+            &[(
+                "gtk4-1:4.16.12-1",
+                "%NAME%\ngtk4\n\n%VERSION%\n1:4.16.12-1\n",
+            )],
+        );
+        dir
+    }
+
+    fn never_canceled() -> Arc<dyn Fn() -> bool + Send + Sync> {
+        Arc::new(|| false)
+    }
+
+    fn collect_output() -> (
+        Arc<Mutex<Vec<String>>>,
+        impl Fn(String) + Send + Sync + 'static,
+    ) {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        (lines, move |line: String| sink.lock().unwrap().push(line))
+    }
+
+    #[test]
+    fn list_installed_merges_descriptions_and_foreign_packages() {
+        let runner = FakeRunner::new()
+            .with_output("pacman -Q", PACMAN_Q)
+            .with_output("pacman -Qi", PACMAN_QI)
+            .with_output("pacman -Qm", PACMAN_QM);
+        let (installed, _) = run_with(runner, || {
+            ParuBackend::list_installed_from(
+                Path::new(MISSING_DB),
+                Path::new(MISSING_DB),
+                Path::new(MISSING_CONF),
+            )
+        });
+        let installed = installed.expect("list installed");
+
+        assert_eq!(installed.len(), 3);
+        assert_eq!(installed[0].name, "bash");
+        assert_eq!(installed[0].description, "The GNU Bourne Again shell");
+        assert_eq!(installed[0].repository, "repo");
+        assert_eq!(installed[2].name, "paru");
+        assert_eq!(installed[2].repository, "aur");
+    }
+
+    #[test]
+    fn list_installed_reads_local_database_without_pacman_queries() {
+        let sync_dir = local_sync_dbs("paru-installed");
+        let (installed, runner) = run_with(FakeRunner::new(), || {
+            ParuBackend::list_installed_from(
+                Path::new(LOCAL_DB),
+                &sync_dir,
+                Path::new(MISSING_CONF),
+            )
+        });
+        let installed = installed.expect("list installed");
+
+        assert_eq!(installed.len(), 3);
+        assert_eq!(installed[1].name, "gtk4");
+        assert_eq!(installed[1].installed_size, Some(44_040_192));
+        assert_eq!(installed[1].repository, "repo");
+        assert_eq!(installed[2].repository, "aur");
+        assert!(runner.calls().is_empty());
+        let _ = std::fs::remove_dir_all(sync_dir);
+    }
+
+    #[test]
+    fn search_repo_packages_reads_sync_databases_offline() {
+        let sync_dir = temp_dir("paru-search");
+        write_sync_db(
+            &sync_dir.join("core.db"),
+            &[
+                (
+                    "bash-5.2.037-1",
+                    "%NAME%\nbash\n\n%VERSION%\n5.2.037-1\n\n%DESC%\nThe GNU Bourne Again shell\n\n%ISIZE%\n9563136\n",
+                ),
+                (
+                    "zsh-5.9-5",
+                    "%NAME%\nzsh\n\n%VERSION%\n5.9-5\n\n%DESC%\nA very advanced and programmable command interpreter (shell)\n",
+                ),
+            ],
+        );
+
+        let (results, runner) = run_with(FakeRunner::new(), || {
+            ParuBackend::search_repo_packages_in(
+                &sync_dir,
+                Path::new(MISSING_CONF),
+                Path::new(LOCAL_DB),
+                "shell",
+                Some(10),
+            )
+        });
+        let results = results.expect("search sync dbs");
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].repository, "core");
+        assert_eq!(results[0].installed_version.as_deref(), Some("5.2.037-1"));
+        assert_eq!(results[0].installed_size, Some(9_563_136));
+        assert_eq!(results[1].installed_version, None);
+        assert!(runner.calls().is_empty());
+
+        let missing = ParuBackend::search_repo_packages_in(
+            Path::new("/nonexistent/pacman/sync"),
+            Path::new(MISSING_CONF),
+            Path::new(LOCAL_DB),
+            "shell",
+            None,
+        );
+        assert!(missing.is_err());
+        let _ = std::fs::remove_dir_all(sync_dir);
+    }
+
+    #[test]
+    fn search_aur_packages_falls_back_to_paru_without_rpc() {
+        let runner = FakeRunner::new()
+            .with_output(
+                "paru -Ss --aur parut",
+                "aur/parut 0.2.0-1 [+3 ~0.10]\n    A GTK frontend for paru\n",
+            )
+            .with_result("paru -Ss --aur zzzz", 1, "", "");
+        let ((found, none), runner) = run_with(runner, || {
+            (
+                ParuBackend::search_aur_packages("parut", Some(5)),
+                ParuBackend::search_aur_packages("zzzz", Some(5)),
+            )
+        });
+
+        let found = found.expect("aur search");
+        assert_eq!(found[0].repository, "aur");
+        assert_eq!(found[0].description, "A GTK frontend for paru");
+        assert!(none.expect("no-match search").is_empty());
+        let paru_calls: Vec<_> = runner
+            .calls()
+            .into_iter()
+            .filter(|call| call.starts_with("paru"))
+            .collect();
+        assert_eq!(paru_calls.len(), 2);
+    }
+
+    #[test]
+    fn search_aur_packages_uses_rpc_and_marks_installed() {
+        let reply = r#"{"version":5,"type":"search","resultcount":3,"results":[
+            {"Name":"paru","PackageBase":"paru","Version":"2.1.0-1","Description":"Feature packed AUR helper","NumVotes":512,"Popularity":21.5,"Maintainer":"Morganamilo","LastModified":1720000000},
+            {"Name":"paru-bin","PackageBase":"paru-bin","Version":"2.1.0-1","Description":"Feature packed AUR helper (binary)","NumVotes":90,"Popularity":2.0,"Maintainer":null,"LastModified":1720000000},
+            {"Name":"paru-git","PackageBase":"paru-git","Version":"2.1.0.r5-1","Description":"Git build","NumVotes":40,"Popularity":0.5,"Maintainer":"x","LastModified":1720000000}
+        ]}"#;
+        let runner = FakeRunner::new().with_output("curl -fsSL *", reply);
+        let client = AurClient::new("https://aur.test");
+        let (results, runner) = run_with(runner, || {
+            ParuBackend::search_aur_packages_with(
+                &client,
+                Path::new(LOCAL_DB),
+                "helper PARU",
+                Some(5),
+            )
+        });
+        let results = results.expect("aur search");
+
+        let names: Vec<_> = results.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["paru", "paru-bin"]);
+        assert_eq!(results[0].installed_version.as_deref(), Some("2.1.0-1"));
+        assert_eq!(results[1].installed_version, None);
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "curl -fsSL --max-time 15 -G https://aur.test/rpc/ --data-urlencode v=5 --data-urlencode type=search --data-urlencode by=name-desc --data-urlencode arg=helper"
+            ]
+        );
+    }
+
+    #[test]
+    fn get_package_details_adds_aur_metadata() {
+        let info = r#"{"version":5,"type":"multiinfo","resultcount":1,"results":[{"Name":"python-foo","PackageBase":"foo","Version":"1.0-1","NumVotes":7,"Popularity":0.123456,"OutOfDate":1725000000,"Maintainer":null,"LastModified":1720000000}]}"#;
+        let runner = FakeRunner::new()
+            .with_result("pacman -Qi python-foo", 1, "", "")
+            .with_output(
+                "paru -Si python-foo",
+                "Repository      : aur\nName            : python-foo\nVersion         : 1.0-1\n",
+            )
+            .with_output("curl -fsSL *", info);
+        let (details, _) = run_with(runner, || {
+            ParuBackend::get_package_details_from(
+                Path::new(MISSING_DB),
+                Path::new(MISSING_DB),
+                Path::new(MISSING_CONF),
+                "python-foo",
+            )
+        });
+        let details = details.expect("package details");
+        assert_eq!(details.votes, "7");
+        assert_eq!(details.popularity, "0.12");
+        assert_eq!(details.maintainer, "None (orphaned)");
+        assert_eq!(details.package_base, "foo");
+        assert!(details.out_of_date.starts_with("Yes"));
+        assert!(!details.last_modified.is_empty());
+    }
+
+    #[test]
+    fn parses_aur_comment_markup() {
+        let html = r##"<div class="comments">
+<h4 class="comment-header"><a href="/account/alex/">alex</a> commented on <a href="#comment-1" class="date" title="Permalink to this comment">2024-07-01 10:00 (UTC)</a></h4>
+<div class="article-content comment-content"><p>Build fails with &lt;gcc 14&gt;, use <code>--nocheck</code></p></div>
+<h4 class="comment-header">deleted</h4><div class="article-content comment-content"></div>
+</div>"##;
+        let comments = ParuBackend::parse_aur_comments(html);
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].author, "alex");
+        assert_eq!(comments[0].date, "2024-07-01 10:00 (UTC)");
+        assert_eq!(
+            comments[0].content,
+            "Build fails with <gcc 14>, use `--nocheck`"
+        );
+    }
+
+    #[test]
+    fn get_package_details_prefers_local_database() {
+        let sync_dir = local_sync_dbs("paru-details");
+        let (details, runner) = run_with(FakeRunner::new(), || {
+            ParuBackend::get_package_details_from(
+                Path::new(LOCAL_DB),
+                &sync_dir,
+                Path::new(MISSING_CONF),
+                "gtk4",
+            )
+        });
+        let details = details.expect("package details");
+
+        assert_eq!(details.version, "1:4.16.12-1");
+        assert_eq!(details.repository, "extra");
+        assert!(runner.calls().is_empty());
+
+        let info = r#"{"version":5,"type":"multiinfo","resultcount":1,"results":[{"Name":"paru","PackageBase":"paru","Version":"2.1.0-1","NumVotes":1500,"Popularity":20.5,"OutOfDate":null,"Maintainer":"Morganamilo","LastModified":1720000000}]}"#;
+        let runner = FakeRunner::new().with_output("curl -fsSL *", info);
+        let (details, _) = run_with(runner, || {
+            ParuBackend::get_package_details_from(
+                Path::new(LOCAL_DB),
+                &sync_dir,
+                Path::new(MISSING_CONF),
+                "paru",
+            )
+        });
+        let details = details.expect("package details");
+        assert_eq!(details.version, "2.1.0-1");
+        assert_eq!(details.votes, "1500");
+        assert_eq!(details.maintainer, "Morganamilo");
+        assert_eq!(details.out_of_date, "No");
+        assert_eq!(details.depends_on, "git  pacman  sh");
+        assert_eq!(details.install_reason, "Explicitly installed");
+        assert_eq!(details.repository, "aur");
+        let _ = std::fs::remove_dir_all(sync_dir);
+    }
+
+    #[test]
+    fn list_installed_fails_when_pacman_fails() {
+        let runner = FakeRunner::new().with_result("pacman -Q", 1, "", "error: no db");
+        let (installed, _) = run_with(runner, || {
+            ParuBackend::list_installed_from(
+                Path::new(MISSING_DB),
+                Path::new(MISSING_DB),
+                Path::new(MISSING_CONF),
+            )
+        });
+        assert!(installed.is_err());
+    }
+
+    #[test]
+    fn list_updates_combines_checkupdates_with_aur_updates() {
+        let runner = FakeRunner::new()
+            .with_program("checkupdates")
+            .with_output("checkupdates", CHECKUPDATES)
+            .with_output("paru -Qu --noconfirm -a", PARU_QUA);
+        let (updates, runner) = run_with(runner, ParuBackend::list_updates);
+        let updates = updates.expect("list updates");
+
+        let names: Vec<_> = updates.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["bash", "gtk4", "paru"]);
+        assert_eq!(updates[0].repository, "repo");
+        assert_eq!(updates[2].repository, "aur");
+        assert_eq!(updates[2].version, "2.1.1-1");
+        assert_eq!(
+            runner.calls(),
+            vec!["checkupdates", "paru -Qu --noconfirm -a"]
+        );
+    }
+
+    #[test]
+    fn list_updates_without_checkupdates_resolves_repositories() {
+        let runner = FakeRunner::new()
+            .with_output(
+                "paru -Qu --noconfirm",
+                "bash 5.2.037-1 -> 5.2.037-2\nparu 2.1.0-1 -> 2.1.1-1\n",
+            )
+            .with_output("pacman -Qm", PACMAN_QM)
+            .with_output(
+                "pacman -Si bash",
+                "Repository      : core\nName            : bash\n",
+            );
+        let (updates, _) = run_with(runner, ParuBackend::list_updates);
+        let updates = updates.expect("list updates");
+
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].repository, "core");
+        assert_eq!(updates[1].repository, "aur");
+    }
+
+    #[test]
+    fn get_package_details_reads_local_db_for_installed_packages() {
+        let sync_dir = local_sync_dbs("paru-details-installed");
+        let (details, runner) = run_with(FakeRunner::new(), || {
+            ParuBackend::get_package_details_from(
+                Path::new(LOCAL_DB),
+                &sync_dir,
+                Path::new(MISSING_CONF),
+                "bash",
+            )
+        });
+        let details = details.expect("package details");
+
+        assert_eq!(details.version, "5.2.037-1");
+        assert_eq!(details.provides, "sh");
+        assert_eq!(
+            details.install_reason,
+            "Installed as a dependency for another package"
+        );
+        assert_eq!(details.repository, "core");
+        assert!(runner.calls().is_empty());
+        let _ = std::fs::remove_dir_all(sync_dir);
+    }
+
+    #[test]
+    fn get_package_details_falls_back_to_qi_without_local_db() {
+        let bash_qi = PACMAN_QI.split("\n\n").next().unwrap();
+        let runner = FakeRunner::new()
+            .with_output("pacman -Qi bash", bash_qi)
+            .with_output("paru -Qi bash", bash_qi);
+        let (details, runner) = run_with(runner, || {
+            ParuBackend::get_package_details_from(
+                Path::new(MISSING_DB),
+                Path::new(MISSING_DB),
+                Path::new(MISSING_CONF),
+                "bash",
+            )
+        });
+        let details = details.expect("package details");
+
+        assert_eq!(details.version, "5.2.037-1");
+        assert_eq!(details.provides, "sh");
+        assert_eq!(
+            details.install_reason,
+            "Installed as a dependency for another package"
+        );
+        assert_eq!(runner.calls(), vec!["pacman -Qi bash", "paru -Qi bash"]);
+    }
+
+    #[test]
+    fn get_package_details_queries_sync_db_when_not_installed() {
+        let runner = FakeRunner::new()
+            .with_result(
+                "pacman -Qi ripgrep",
+                1,
+                "",
+                "error: package 'ripgrep' was not found",
+            )
+            .with_output(
+                "paru -Si ripgrep",
+                "Repository      : extra\nName            : ripgrep\nVersion         : 14.1.1-1\n",
+            );
+        let (details, _) = run_with(runner, || {
+            ParuBackend::get_package_details_from(
+                Path::new(MISSING_DB),
+                Path::new(MISSING_DB),
+                Path::new(MISSING_CONF),
+                "ripgrep",
+            )
+        });
+        let details = details.expect("package details");
+        assert_eq!(details.repository, "extra");
+        assert_eq!(details.version, "14.1.1-1");
+    }
+
+    #[test]
+    fn estimate_cleanup_sums_cache_sizes_and_orphans() {
+        let runner = FakeRunner::new()
+            .with_output(
+                "du -sb /var/cache/pacman/pkg",
+                "1048576\t/var/cache/pacman/pkg\n",
+            )
+            .with_output("du -sb *", "2048\t/home/user/.cache/paru/clone\n")
+            .with_output("pacman -Qtdq", "libfoo\nlibbar\n");
+        let (estimate, _) = run_with(runner, ParuBackend::estimate_cleanup);
+
+        assert_eq!(estimate.pacman_cache_bytes, 1_048_576);
+        assert_eq!(estimate.orphan_count, 2);
+    }
+
+    #[test]
+    fn install_package_streams_output_and_answers_prompts() {
+        let runner = FakeRunner::new().with_session(
+            "paru -S --skipreview ripgrep",
+            vec![
+                PtyEvent::Line("resolving dependencies...".to_string()),
+                PtyEvent::Partial(":: Proceed with installation? [Y/n]".to_string()),
+                PtyEvent::Line(":: Proceed with installation? [Y/n] y".to_string()),
+                PtyEvent::Line("(1/1) installing ripgrep".to_string()),
+            ],
+            0,
+        );
+        let (lines, output_callback) = collect_output();
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let asked_sink = asked.clone();
+        let prompt_handler: PromptHandler = Arc::new(move |prompt| {
+            asked_sink.lock().unwrap().push(prompt.question);
+            Some("y".to_string())
+        });
+
+        let (result, runner) = run_with(runner, || {
+            ParuBackend::install_packages(
+                &["ripgrep"],
+                output_callback,
+                never_canceled(),
+                prompt_handler,
+            )
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(*asked.lock().unwrap(), vec!["Proceed with installation?"]);
+        assert_eq!(runner.inputs(), vec!["y"]);
+        assert_eq!(
+            *lines.lock().unwrap(),
+            vec![
+                "Running: paru -S --skipreview ripgrep",
+                "resolving dependencies...",
+                ":: Proceed with installation? [Y/n] y",
+                "(1/1) installing ripgrep",
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_package_reports_paru_exit_code() {
+        let runner = FakeRunner::new().with_session(
+            "paru -Rns --skipreview foo",
+            vec![PtyEvent::Line("error: target not found: foo".to_string())],
+            1,
+        );
+        let (_, output_callback) = collect_output();
+        let (result, _) = run_with(runner, || {
+            ParuBackend::remove_packages(
+                &["foo"],
+                output_callback,
+                never_canceled(),
+                Arc::new(|_| None),
+            )
+        });
+        let err = result.unwrap_err();
+        assert_eq!(err.to_string(), "paru exited with code 1 - see task output");
+        assert_eq!(err.kind, ErrorKind::PackageNotFound);
+        assert_eq!(err.exit_code, Some(1));
+        assert_eq!(err.command.as_deref(), Some("paru -Rns --skipreview foo"));
+        assert_eq!(err.stderr, "error: target not found: foo");
+    }
+
+    #[test]
+    fn batched_install_is_one_transaction() {
+        let runner = FakeRunner::new().with_session(
+            "paru -S --skipreview ripgrep fd bat",
+            vec![PtyEvent::Line("(3/3) installing bat".to_string())],
+            0,
+        );
+        let (_, output_callback) = collect_output();
+        let (result, runner) = run_with(runner, || {
+            ParuBackend::install_packages(
+                &["ripgrep", "fd", "bat"],
+                output_callback,
+                never_canceled(),
+                Arc::new(|_| None),
+            )
+        });
+        assert!(result.is_ok());
+        assert_eq!(runner.calls(), vec!["paru -S --skipreview ripgrep fd bat"]);
+    }
+
+    #[test]
+    fn finds_missing_build_dependencies_and_built_files() {
+        let runner = FakeRunner::new().with_result(
+            "pacman -T cargo rust>=1.80 git",
+            127,
+            "rust>=1.80\n",
+            "",
+        );
+        let deps = ["cargo", "rust>=1.80", "git"].map(String::from);
+        let (missing, _) = run_with(runner, || ParuBackend::missing_dependencies(&deps));
+        assert_eq!(missing.expect("pacman -T"), vec!["rust>=1.80"]);
+
+        let dir = temp_dir("paru-built");
+        for file in [
+            "python-foo-1.2-1-any.pkg.tar.zst",
+            "python-foo-1.2-1-any.pkg.tar.zst.sig",
+            "python-foo-docs-1.2-1-any.pkg.tar.zst",
+        ] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let built = ParuBackend::built_packages(&dir, "python-foo");
+        assert_eq!(built.len(), 1);
+        assert!(built[0].ends_with("python-foo-1.2-1-any.pkg.tar.zst"));
+        // Asked for a name the split package does not build: keep everything.
+        assert_eq!(ParuBackend::built_packages(&dir, "foo").len(), 2);
+        assert_eq!(
+            ParuBackend::package_name_from_file("ripgrep-14.1.1-1-x86_64.pkg.tar.zst"),
+            Some("ripgrep")
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn unattended_runs_never_ask() {
+        let runner = FakeRunner::new().with_session("paru -Sc --noconfirm", Vec::new(), 0);
+        let (_, output_callback) = collect_output();
+        let (cleaned, runner) = run_with(runner, || {
+            ParuBackend::unattended(|| {
+                ParuBackend::clean_cache(output_callback, never_canceled(), Arc::new(|_| None))
+            })
+        });
+        assert!(cleaned.is_ok());
+        assert_eq!(runner.calls(), vec!["paru -Sc --noconfirm"]);
+        assert_eq!(ParuBackend::confirmation_args(), vec!["--skipreview"]);
+    }
+
+    #[test]
+    fn installs_build_dependencies_and_packages_built_against() {
+        let info = r#"{"version":5,"type":"multiinfo","resultcount":2,"results":[{"Name":"foo","PackageBase":"foo","Version":"1.0-1","Depends":["libfoo>=2"],"MakeDepends":["cmake"]},{"Name":"libfoo","PackageBase":"libfoo","Version":"2.0-1","Depends":["glibc"]}]}"#;
+        let runner = FakeRunner::new()
+            .with_output("curl -fsSL *", info)
+            .with_result("pacman -T cmake glibc", 127, "cmake\n", "")
+            .with_session(
+                "paru -S --asdeps --needed --skipreview cmake",
+                Vec::new(),
+                0,
+            )
+            .with_session("paru -S --needed --skipreview libfoo", Vec::new(), 0);
+        let (_, output_callback) = collect_output();
+        let (installed, runner) = run_with(runner, || {
+            ParuBackend::install_build_dependencies(
+                &["foo", "libfoo"],
+                output_callback,
+                never_canceled(),
+                Arc::new(|_| None),
+            )
+        });
+        assert_eq!(installed.expect("build dependencies"), vec!["libfoo"]);
+        assert_eq!(
+            runner.calls()[1..],
+            [
+                "pacman -T cmake glibc",
+                "paru -S --asdeps --needed --skipreview cmake",
+                "paru -S --needed --skipreview libfoo",
+            ]
+        );
+    }
+
+    #[test]
+    fn installs_local_files_and_builds_local_pkgbuilds() {
+        let dir = temp_dir("paru-local");
+        std::fs::write(dir.join("PKGBUILD"), "pkgname=foo\n").unwrap();
+        let dir_path = dir.to_string_lossy().to_string();
+        let build = format!("paru -Bi --skipreview {}", dir_path);
+        let runner = FakeRunner::new()
+            .with_session(
+                "paru -U --skipreview /tmp/foo-1.0-1-any.pkg.tar.zst",
+                Vec::new(),
+                0,
+            )
+            .with_session(
+                &build,
+                vec![PtyEvent::Line("==> Finished making: foo".to_string())],
+                0,
+            );
+        let (_, output_callback) = collect_output();
+        let (installed, runner) = run_with(runner, || {
+            ParuBackend::install_package_files(
+                &["/tmp/foo-1.0-1-any.pkg.tar.zst"],
+                output_callback,
+                never_canceled(),
+                Arc::new(|_| None),
+            )
+        });
+        assert!(installed.is_ok());
+        let (_, output_callback) = collect_output();
+        let built = with_runner(runner.clone(), || {
+            ParuBackend::build_local_package(
+                &dir_path,
+                output_callback,
+                never_canceled(),
+                Arc::new(|_| None),
+            )
+        });
+        assert!(built.is_ok());
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "paru -U --skipreview /tmp/foo-1.0-1-any.pkg.tar.zst",
+                build.as_str()
+            ]
+        );
+        assert_eq!(
+            ParuBackend::read_local_pkgbuild(&dir).unwrap(),
+            "pkgname=foo\n"
+        );
+
+        let empty = dir.join("empty");
+        std::fs::create_dir_all(&empty).unwrap();
+        let err = ParuBackend::read_local_pkgbuild(&empty).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::PackageNotFound);
+        assert!(ParuBackend::is_package_file(Path::new(
+            "/tmp/foo-1.0-1-any.pkg.tar.zst"
+        )));
+        assert!(!ParuBackend::is_package_file(Path::new(
+            "/tmp/foo-1.0-1-any.pkg.tar.zst.sig"
+        )));
+        assert!(!ParuBackend::is_package_file(Path::new("/tmp/notes.txt")));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn import_pgp_key_reports_gpg_failure() {
+        let runner = FakeRunner::new()
+            .with_output("gpg --recv-keys 4AEE18F83AFDEB23", "")
+            .with_result(
+                "gpg --recv-keys DEADBEEF",
+                2,
+                "",
+                "gpg: keyserver receive failed: No data",
+            );
+        let (results, runner) = run_with(runner, || {
+            (
+                ParuBackend::import_pgp_key("4AEE18F83AFDEB23"),
+                ParuBackend::import_pgp_key("DEADBEEF"),
+            )
+        });
+        assert!(results.0.is_ok());
+        let err = results.1.unwrap_err();
+        assert_eq!(err.to_string(), "Failed to import PGP key DEADBEEF");
+        assert_eq!(err.exit_code, Some(2));
+        assert_eq!(runner.calls().len(), 2);
+    }
+
+    #[test]
+    fn preview_reports_pacman_resolution_errors() {
+        let runner = FakeRunner::new()
+            .with_output(
+                "pacman -Rns --print --print-format %r %n %v %s foo",
+                "local foo 1.0-1 0\nlocal libfoo 2.0-1 0\n",
+            )
+            .with_result(
+                "pacman -S --print --print-format %r %n %v %s nosuchpkg",
+                1,
+                "",
+                "error: target not found: nosuchpkg\n",
+            );
+        let (results, _) = run_with(runner, || {
+            (
+                ParuBackend::preview_transaction(&PreviewRequest::Remove(vec!["foo".to_string()])),
+                ParuBackend::preview_transaction(&PreviewRequest::Install {
+                    repo: vec!["nosuchpkg".to_string()],
+                    aur: Vec::new(),
+                }),
+            )
+        });
+        let removed: Vec<String> = results
+            .0
+            .expect("remove preview")
+            .remove
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(removed, vec!["foo", "libfoo"]);
+        let err = results.1.unwrap_err();
+        assert_eq!(err.kind, ErrorKind::PackageNotFound);
+    }
+
+    #[test]
+    fn dismissed_prompt_aborts_the_task() {
+        let runner = FakeRunner::new().with_session(
+            "paru -c --skipreview",
+            vec![PtyEvent::Partial(
+                ":: Do you want to remove these packages? [Y/n]".to_string(),
+            )],
+            0,
+        );
+        let (_, output_callback) = collect_output();
+        let (result, runner) = run_with(runner, || {
+            ParuBackend::remove_orphans(output_callback, never_canceled(), Arc::new(|_| None))
+        });
+        let err = result.unwrap_err();
+        assert_eq!(err.to_string(), "Task canceled by user");
+        assert_eq!(err.kind, ErrorKind::Canceled);
+        assert!(runner.inputs().is_empty());
+    }
 
     #[test]
     fn parses_update_lines_with_repo_prefix() {
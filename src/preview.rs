@@ -0,0 +1,228 @@
+use crate::pacman_db::DbPackage;
+use crate::sync_db::SyncPackage;
+
+/// What pacman prints for each package with [`PRINT_FORMAT`].
+pub const PRINT_FORMAT: &str = "%r %n %v %s";
+
+/// The transaction a task would run, to preview before queueing it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviewRequest {
+    /// Install these packages; `aur` ones are built by paru and can't be
+    /// resolved by pacman.
+    Install { repo: Vec<String>, aur: Vec<String> },
+    /// Remove these packages with their unneeded dependencies (`-Rns`).
+    Remove(Vec<String>),
+    /// Upgrade everything from the current databases (`-Su`), except `ignored`.
+    SystemUpdate { ignored: Vec<String> },
+}
+
+impl PreviewRequest {
+    /// pacman arguments that print the transaction instead of running it, or
+    /// `None` when there is nothing for pacman to resolve.
+    pub fn pacman_args(&self) -> Option<Vec<String>> {
+        let (operation, targets): (&str, &[String]) = match self {
+            PreviewRequest::Install { repo, .. } if repo.is_empty() => return None,
+            PreviewRequest::Install { repo, .. } => ("-S", repo),
+            PreviewRequest::Remove(targets) => ("-Rns", targets),
+            PreviewRequest::SystemUpdate { .. } => ("-Su", &[]),
+        };
+        let mut args: Vec<String> = [operation, "--print", "--print-format", PRINT_FORMAT]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        if let PreviewRequest::SystemUpdate { ignored } = self
+            && !ignored.is_empty()
+        {
+            args.push("--ignore".to_string());
+            args.push(ignored.join(","));
+        }
+        args.extend(targets.iter().cloned());
+        Some(args)
+    }
+
+    fn targets(&self) -> &[String] {
+        match self {
+            PreviewRequest::Install { repo, .. } => repo,
+            PreviewRequest::Remove(targets) => targets,
+            PreviewRequest::SystemUpdate { .. } => &[],
+        }
+    }
+}
+
+/// One package the transaction installs, upgrades or removes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewPackage {
+    pub name: String,
+    /// The version after the transaction; for removals, the installed one.
+    pub version: String,
+    /// The installed version, when an install replaces it.
+    pub old_version: Option<String>,
+    pub repository: String,
+    pub download_size: u64,
+    /// Change in installed size, in bytes.
+    pub size_change: i64,
+    /// Pulled in (or removed) for another package rather than asked for.
+    pub dependency: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransactionPreview {
+    pub install: Vec<PreviewPackage>,
+    pub remove: Vec<PreviewPackage>,
+    /// AUR packages paru builds as well; their dependencies aren't resolved here.
+    pub aur: Vec<String>,
+}
+
+impl TransactionPreview {
+    /// Build the preview from pacman's `--print` output, taking installed sizes
+    /// from the local database and the sync databases.
+    pub fn from_print(
+        request: &PreviewRequest,
+        printed: &str,
+        local: &[DbPackage],
+        sync: &[SyncPackage],
+    ) -> Self {
+        let targets = request.targets();
+        let installed = |name: &str| local.iter().find(|p| p.name == name);
+        let mut preview = TransactionPreview {
+            aur: match request {
+                PreviewRequest::Install { aur, .. } => aur.clone(),
+                _ => Vec::new(),
+            },
+            ..Default::default()
+        };
+
+        for line in printed.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [repository, name, version, size] = fields[..] else {
+                continue;
+            };
+            let old = installed(name);
+            let dependency = !targets.is_empty() && !targets.iter().any(|t| t == name);
+            if matches!(request, PreviewRequest::Remove(_)) {
+                preview.remove.push(PreviewPackage {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    old_version: None,
+                    repository: repository.to_string(),
+                    download_size: 0,
+                    size_change: -(old.map_or(0, |p| p.size) as i64),
+                    dependency,
+                });
+                continue;
+            }
+            let new_size = sync
+                .iter()
+                .find(|p| p.repository == repository && p.package.name == name)
+                .map_or(0, |p| p.package.size);
+            preview.install.push(PreviewPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+                old_version: old.map(|p| p.version.clone()),
+                repository: repository.to_string(),
+                download_size: size.parse().unwrap_or(0),
+                size_change: new_size as i64 - old.map_or(0, |p| p.size) as i64,
+                dependency,
+            });
+        }
+        preview
+    }
+
+    pub fn download_size(&self) -> u64 {
+        self.install.iter().map(|p| p.download_size).sum()
+    }
+
+    pub fn size_change(&self) -> i64 {
+        self.install
+            .iter()
+            .chain(&self.remove)
+            .map(|p| p.size_change)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty() && self.remove.is_empty() && self.aur.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PreviewRequest, TransactionPreview};
+    use crate::pacman_db::DbPackage;
+    use crate::sync_db::SyncPackage;
+
+    fn package(name: &str, version: &str, size: u64) -> DbPackage {
+        DbPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            size,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sizes_installs_upgrades_and_removals() {
+        let local = vec![
+            package("glibc", "2.40-1", 48_000_000),
+            package("libfoo", "1.0-1", 300),
+        ];
+        let sync = vec![
+            SyncPackage {
+                repository: "core".to_string(),
+                package: package("glibc", "2.41-1", 49_000_000),
+            },
+            SyncPackage {
+                repository: "extra".to_string(),
+                package: package("ripgrep", "14.1.1-1", 5_000_000),
+            },
+        ];
+
+        let install = PreviewRequest::Install {
+            repo: vec!["ripgrep".to_string()],
+            aur: vec!["paru-bin".to_string()],
+        };
+        assert_eq!(
+            install.pacman_args().unwrap().join(" "),
+            "-S --print --print-format %r %n %v %s ripgrep"
+        );
+        let preview = TransactionPreview::from_print(
+            &install,
+            "core glibc 2.41-1 9000000\nextra ripgrep 14.1.1-1 1600000\n",
+            &local,
+            &sync,
+        );
+        assert_eq!(preview.install.len(), 2);
+        assert!(preview.install[0].dependency);
+        assert_eq!(preview.install[0].old_version.as_deref(), Some("2.40-1"));
+        assert_eq!(preview.install[0].size_change, 1_000_000);
+        assert!(!preview.install[1].dependency);
+        assert_eq!(preview.download_size(), 10_600_000);
+        assert_eq!(preview.size_change(), 6_000_000);
+        assert_eq!(preview.aur, vec!["paru-bin"]);
+
+        let remove = PreviewRequest::Remove(vec!["foo".to_string()]);
+        let preview = TransactionPreview::from_print(
+            &remove,
+            "local foo 2.0-1 0\nlocal libfoo 1.0-1 0\n",
+            &local,
+            &sync,
+        );
+        assert_eq!(preview.remove.len(), 2);
+        assert!(preview.remove[1].dependency);
+        assert_eq!(preview.size_change(), -300);
+        assert_eq!(preview.download_size(), 0);
+
+        let only_aur = PreviewRequest::Install {
+            repo: Vec::new(),
+            aur: vec!["paru-bin".to_string()],
+        };
+        assert!(only_aur.pacman_args().is_none());
+        let update = PreviewRequest::SystemUpdate {
+            ignored: vec!["linux".to_string(), "nvidia".to_string()],
+        };
+        assert_eq!(
+            update.pacman_args().unwrap().join(" "),
+            "-Su --print --print-format %r %n %v %s --ignore linux,nvidia"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::error::{BackendError, ErrorKind};
+use crate::runner::{self, CommandSpec};
+use std::path::PathBuf;
+
+/// Set in paru's environment so that parut, started by sudo as its askpass
+/// program, shows the password dialog instead of the main window.
+pub const ASKPASS_ENV: &str = "PARUT_ASKPASS";
+
+/// How paru gets root when there is no terminal to type a password into.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Escalation {
+    /// `pkexec`, which asks through the desktop's polkit agent.
+    Pkexec,
+    /// `sudo -A`, with parut itself as the askpass program.
+    SudoAskpass(PathBuf),
+}
+
+impl Escalation {
+    /// Pick the helper for `preference` ("auto", "pkexec" or "sudo"). Automatic
+    /// prefers sudo, which remembers the password for the rest of the task,
+    /// while pkexec asks again for every pacman call paru makes.
+    pub fn resolve(preference: &str) -> Result<Self, BackendError> {
+        let sudo = || {
+            if !runner::command_exists("sudo") {
+                return None;
+            }
+            std::env::current_exe().ok().map(Escalation::SudoAskpass)
+        };
+        let pkexec = || runner::command_exists("pkexec").then_some(Escalation::Pkexec);
+        let found = match preference {
+            "pkexec" => pkexec(),
+            "sudo" => sudo(),
+            _ => sudo().or_else(pkexec),
+        };
+        found.ok_or_else(|| {
+            BackendError::new(
+                ErrorKind::MissingProgram,
+                match preference {
+                    "pkexec" => "pkexec is not installed (install polkit)",
+                    "sudo" => "sudo is not installed",
+                    _ => "Neither pkexec nor sudo is installed, so paru cannot get root",
+                },
+            )
+        })
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Escalation::Pkexec => "polkit",
+            Escalation::SudoAskpass(_) => "sudo",
+        }
+    }
+
+    /// Flags telling paru to escalate this way.
+    pub fn paru_args(&self) -> Vec<String> {
+        match self {
+            Escalation::Pkexec => vec!["--sudo".to_string(), "pkexec".to_string()],
+            Escalation::SudoAskpass(_) => vec!["--sudoflags".to_string(), "-A".to_string()],
+        }
+    }
+
+    /// `program` run as root through this helper.
+    pub fn command(&self, program: &str) -> CommandSpec {
+        let mut spec = match self {
+            Escalation::Pkexec => CommandSpec::new("pkexec"),
+            Escalation::SudoAskpass(_) => CommandSpec::new("sudo").arg("-A"),
+        }
+        .arg(program);
+        for (key, value) in self.env() {
+            spec = spec.env(&key, &value);
+        }
+        spec
+    }
+
+    pub fn env(&self) -> Vec<(String, String)> {
+        match self {
+            Escalation::Pkexec => Vec::new(),
+            Escalation::SudoAskpass(exe) => vec![
+                ("SUDO_ASKPASS".to_string(), exe.display().to_string()),
+                (ASKPASS_ENV.to_string(), "1".to_string()),
+            ],
+        }
+    }
+}
+
+/// The prompt to show when parut was started as sudo's askpass program.
+pub fn askpass_prompt() -> Option<String> {
+    std::env::var_os(ASKPASS_ENV)?;
+    Some(
+        std::env::args()
+            .nth(1)
+            .filter(|prompt| !prompt.trim().is_empty())
+            .unwrap_or_else(|| "Password:".to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Escalation;
+    use crate::error::ErrorKind;
+    use crate::runner::{FakeRunner, with_runner};
+    use std::sync::Arc;
+
+    #[test]
+    fn picks_an_available_helper_or_fails_cleanly() {
+        let both = Arc::new(
+            FakeRunner::new()
+                .with_program("sudo")
+                .with_program("pkexec"),
+        );
+        let pkexec_only = Arc::new(FakeRunner::new().with_program("pkexec"));
+        let neither = Arc::new(FakeRunner::new());
+
+        let auto = with_runner(both.clone(), || Escalation::resolve("auto")).unwrap();
+        assert!(matches!(auto, Escalation::SudoAskpass(_)));
+        assert_eq!(auto.paru_args(), vec!["--sudoflags", "-A"]);
+        assert!(auto.env().iter().any(|(key, _)| key == "SUDO_ASKPASS"));
+        assert_eq!(auto.command("rm").arg("-f").command_line(), "sudo -A rm -f");
+
+        let chosen = with_runner(both, || Escalation::resolve("pkexec")).unwrap();
+        assert_eq!(chosen.paru_args(), vec!["--sudo", "pkexec"]);
+        assert_eq!(
+            with_runner(pkexec_only.clone(), || Escalation::resolve("auto")).unwrap(),
+            Escalation::Pkexec
+        );
+
+        let err = with_runner(pkexec_only, || Escalation::resolve("sudo")).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::MissingProgram);
+        let err = with_runner(neither, || Escalation::resolve("auto")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Neither pkexec nor sudo is installed, so paru cannot get root"
+        );
+    }
+}
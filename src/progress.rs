@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+
+/// Verbs pacman, paru and makepkg put in front of the package they work on.
+const PACKAGE_VERBS: &[&str] = &[
+    "installing",
+    "upgrading",
+    "reinstalling",
+    "downgrading",
+    "removing",
+    "downloaded:",
+];
+
+/// What a running task is doing right now, read from pacman, paru and makepkg
+/// output. Reset whenever the task enters a new phase.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProgressDetails {
+    /// The package being downloaded, built, installed or removed.
+    pub package: Option<String>,
+    /// Position in the current phase from pacman's "(3/10)" counters: the item
+    /// being worked on, and how many there are.
+    pub step: Option<(u32, u32)>,
+    /// How far along the current item is, from its progress bar.
+    pub step_fraction: Option<f64>,
+    /// Bytes downloaded and bytes to download.
+    pub bytes: Option<(u64, u64)>,
+    /// Download rate in bytes per second.
+    pub rate: Option<u64>,
+    /// Byte counts come from pacman's "Total" line once it shows one, rather
+    /// than from the line of whichever file finished last.
+    #[serde(skip)]
+    has_total: bool,
+}
+
+impl ProgressDetails {
+    /// Read one line of output. Returns whether anything changed.
+    pub fn update(&mut self, line: &str) -> bool {
+        let before = self.clone();
+        let line = line.trim();
+
+        if let Some(rest) = line.strip_prefix("==> Making package: ") {
+            self.package = rest.split_whitespace().next().map(str::to_string);
+            return *self != before;
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let percent = tokens
+            .last()
+            .and_then(|t| t.strip_suffix('%'))
+            .and_then(|t| t.parse::<f64>().ok())
+            .map(|p| (p / 100.0).clamp(0.0, 1.0));
+
+        let is_total = tokens.first() == Some(&"Total");
+        let counted = if is_total { &tokens[1..] } else { &tokens[..] };
+        let step = counted.first().and_then(|t| parse_step(t));
+        if let Some(step) = step {
+            self.step = Some(step);
+            self.step_fraction = percent;
+            if !is_total {
+                self.package = counted
+                    .get(1)
+                    .filter(|verb| PACKAGE_VERBS.contains(&verb.to_lowercase().as_str()))
+                    .and_then(|_| counted.get(2))
+                    .map(|name| name.to_string());
+            }
+        }
+
+        // " ripgrep-14.1.1-1-x86_64  1632.2 KiB  3.20 MiB/s 00:00 [####] 45%"
+        if let Some((size, rate)) = transfer(&tokens)
+            && (is_total || !self.has_total)
+        {
+            let fraction = percent.unwrap_or(0.0);
+            self.bytes = Some(((size as f64 * fraction) as u64, size));
+            self.rate = rate;
+            self.has_total |= is_total;
+            if !is_total && step.is_none() {
+                self.package = tokens.first().map(|file| package_from_file(file));
+            }
+        }
+
+        *self != before
+    }
+
+    /// Overall progress of the current phase, 0.0 to 1.0.
+    pub fn fraction(&self) -> Option<f64> {
+        if let Some((done, total)) = self.bytes
+            && total > 0
+        {
+            return Some((done as f64 / total as f64).min(1.0));
+        }
+        let (current, total) = self.step.filter(|(_, total)| *total > 0)?;
+        // Without a bar, "(2/4)" means the second item has just started.
+        let done = current.saturating_sub(1) as f64 + self.step_fraction.unwrap_or(0.0);
+        Some((done / total as f64).min(1.0))
+    }
+}
+
+/// "(3/10)" as (3, 10).
+fn parse_step(token: &str) -> Option<(u32, u32)> {
+    let (current, total) = token
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .split_once('/')?;
+    Some((current.parse().ok()?, total.parse().ok()?))
+}
+
+fn unit_bytes(unit: &str) -> Option<f64> {
+    Some(match unit {
+        "B" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    })
+}
+
+/// The size and rate columns of a pacman download line.
+fn transfer(tokens: &[&str]) -> Option<(u64, Option<u64>)> {
+    let mut size = None;
+    let mut rate = None;
+    for pair in tokens.windows(2) {
+        let Ok(value) = pair[0].parse::<f64>() else {
+            continue;
+        };
+        if let Some(per_second) = pair[1].strip_suffix("/s") {
+            rate = unit_bytes(per_second).map(|unit| (value * unit) as u64);
+        } else if size.is_none() {
+            size = unit_bytes(pair[1]).map(|unit| (value * unit) as u64);
+        }
+    }
+    Some((size?, rate))
+}
+
+/// "ripgrep-14.1.1-1-x86_64" (pacman's name for the file) as "ripgrep".
+fn package_from_file(file: &str) -> String {
+    let parts: Vec<&str> = file.rsplitn(4, '-').collect();
+    if parts.len() == 4 {
+        parts[3].to_string()
+    } else {
+        file.to_string()
+    }
+}
+
+/// Seconds left for a task that has run for `elapsed` seconds, `phase_elapsed`
+/// of them in its current phase. Blends that phase's progress (`fraction`)
+/// with how long past runs of the same task type took (`past_runs`, in
+/// seconds), trusting progress more the further it got.
+pub fn estimate_remaining(
+    elapsed: u64,
+    phase_elapsed: u64,
+    fraction: Option<f64>,
+    past_runs: &[u64],
+) -> Option<u64> {
+    let by_progress = fraction
+        .filter(|f| *f > 0.01 && *f < 1.0)
+        .map(|f| (f, phase_elapsed as f64 / f * (1.0 - f)));
+    let by_history = median(past_runs)
+        .map(|typical| typical.saturating_sub(elapsed))
+        .filter(|left| *left > 0)
+        .map(|left| left as f64);
+    let seconds = match (by_progress, by_history) {
+        (Some((f, progress)), Some(history)) => f * progress + (1.0 - f) * history,
+        (Some((_, progress)), None) => progress,
+        (None, Some(history)) => history,
+        (None, None) => return None,
+    };
+    Some(seconds.round() as u64)
+}
+
+fn median(values: &[u64]) -> Option<u64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    sorted.get(sorted.len() / 2).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProgressDetails, estimate_remaining};
+
+    #[test]
+    fn reads_packages_counters_and_downloads() {
+        let mut details = ProgressDetails::default();
+        assert!(
+            details.update(
+                " ripgrep-14.1.1-1-x86_64    1024.0 KiB  512.0 KiB/s 00:02 [######----]  50%"
+            )
+        );
+        assert_eq!(details.package.as_deref(), Some("ripgrep"));
+        assert_eq!(details.bytes, Some((512 * 1024, 1024 * 1024)));
+        assert_eq!(details.rate, Some(512 * 1024));
+
+        details.update(" Total (1/2)        4.0 MiB  2.00 MiB/s 00:01 [#####-----]  25%");
+        assert_eq!(details.bytes, Some((1024 * 1024, 4 * 1024 * 1024)));
+        assert_eq!(details.step, Some((1, 2)));
+        // The total keeps counting even as single files finish.
+        details.update(" fd-10.2.0-1-x86_64    1.0 MiB  1.00 MiB/s 00:00 [##########] 100%");
+        assert_eq!(details.bytes, Some((1024 * 1024, 4 * 1024 * 1024)));
+        assert_eq!(details.fraction(), Some(0.25));
+
+        let mut details = ProgressDetails::default();
+        details.update("(2/4) upgrading glibc");
+        assert_eq!(details.package.as_deref(), Some("glibc"));
+        assert_eq!(details.fraction(), Some(0.25));
+        details.update("(2/4) upgrading glibc                  [##########] 100%");
+        assert_eq!(details.fraction(), Some(0.5));
+        details.update("(1/1) checking keys in keyring         [##########] 100%");
+        assert_eq!(details.package, None);
+        assert!(!details.update("(1/1) checking keys in keyring         [##########] 100%"));
+
+        details.update("==> Making package: paru 2.1.0-1 (Fri 16 Oct 2026)");
+        assert_eq!(details.package.as_deref(), Some("paru"));
+    }
+
+    #[test]
+    fn estimates_from_progress_and_past_runs() {
+        assert_eq!(estimate_remaining(60, 60, None, &[]), None);
+        assert_eq!(estimate_remaining(60, 60, Some(0.5), &[]), Some(60));
+        assert_eq!(
+            estimate_remaining(60, 60, None, &[100, 300, 200]),
+            Some(140)
+        );
+        // Past the usual time there is nothing to go on but progress.
+        assert_eq!(estimate_remaining(400, 400, None, &[100, 300, 200]), None);
+        assert_eq!(estimate_remaining(60, 60, Some(0.25), &[200]), Some(150));
+        // Half of a phase that began 10s into a 10 minute task: 10s to go.
+        assert_eq!(estimate_remaining(600, 10, Some(0.5), &[]), Some(10));
+    }
+}
@@ -0,0 +1,268 @@
+use std::sync::Arc;
+
+/// Called from the task thread when paru is waiting for input.
+/// Returns the answer to write to paru, or `None` to abort the task.
+pub type PromptHandler = Arc<dyn Fn(Prompt) -> Option<String> + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptKind {
+    /// A `[Y/n]` / `[y/N]` question.
+    Confirm { default_yes: bool },
+    /// A numbered menu, e.g. provider selection.
+    Choice {
+        options: Vec<PromptOption>,
+        default: Option<String>,
+    },
+    /// Free-form input such as paru's `==>` menus or group member selection.
+    Text { default: Option<String> },
+    /// A password prompt (sudo). The answer is never logged or echoed.
+    Password,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptOption {
+    pub value: String,
+    pub label: String,
+    pub repository: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt {
+    pub question: String,
+    pub details: Vec<String>,
+    pub kind: PromptKind,
+}
+
+impl Prompt {
+    /// Default answer paru would pick on its own, used for the dialog's preselection.
+    pub fn default_answer(&self) -> String {
+        match &self.kind {
+            PromptKind::Confirm { default_yes } => if *default_yes { "y" } else { "n" }.to_string(),
+            PromptKind::Choice { default, .. } | PromptKind::Text { default } => {
+                default.clone().unwrap_or_default()
+            }
+            PromptKind::Password => String::new(),
+        }
+    }
+}
+
+/// Inspect text that paru printed without a trailing newline and decide whether
+/// it is waiting for an answer. `context` holds the most recent complete lines.
+pub fn detect_prompt(context: &[String], text: &str) -> Option<Prompt> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let lower = trimmed.to_lowercase();
+
+    if lower.ends_with("[y/n]") || lower.ends_with("[y/n]:") {
+        let default_yes = trimmed.contains("[Y/n]");
+        let question = trimmed
+            .trim_end_matches(':')
+            .trim_end_matches("[Y/n]")
+            .trim_end_matches("[y/N]")
+            .trim_end_matches("[y/n]")
+            .trim();
+        return Some(Prompt {
+            question: strip_marker(question).to_string(),
+            details: Vec::new(),
+            kind: PromptKind::Confirm { default_yes },
+        });
+    }
+
+    if lower.contains("password") && trimmed.ends_with(':') {
+        return Some(Prompt {
+            question: trimmed.to_string(),
+            details: Vec::new(),
+            kind: PromptKind::Password,
+        });
+    }
+
+    if lower.starts_with("enter a number") {
+        let (heading, menu) = menu_block(context);
+        let options = parse_menu_options(&menu);
+        return Some(Prompt {
+            question: heading.unwrap_or_else(|| "Select an option".to_string()),
+            details: Vec::new(),
+            kind: PromptKind::Choice {
+                options,
+                default: parse_default(trimmed),
+            },
+        });
+    }
+
+    if lower.starts_with("enter a selection") {
+        let (heading, menu) = menu_block(context);
+        return Some(Prompt {
+            question: heading.unwrap_or_else(|| "Enter a selection".to_string()),
+            details: menu,
+            kind: PromptKind::Text {
+                default: parse_default(trimmed),
+            },
+        });
+    }
+
+    // paru's own menus: a few "==> ..." lines followed by a bare "==>" input marker.
+    if trimmed == "==>" {
+        let menu: Vec<String> = context
+            .iter()
+            .rev()
+            .take_while(|line| line.trim_start().starts_with("==>"))
+            .map(|line| {
+                line.trim_start()
+                    .trim_start_matches("==>")
+                    .trim()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        let question = menu
+            .first()
+            .cloned()
+            .unwrap_or_else(|| "paru is waiting for input".to_string());
+        return Some(Prompt {
+            question,
+            details: menu.into_iter().skip(1).collect(),
+            kind: PromptKind::Text { default: None },
+        });
+    }
+
+    None
+}
+
+fn strip_marker(text: &str) -> &str {
+    text.trim_start_matches("::").trim()
+}
+
+fn parse_default(text: &str) -> Option<String> {
+    let start = text.find("(default=")? + "(default=".len();
+    let end = text[start..].find(')')? + start;
+    Some(text[start..end].to_string())
+}
+
+/// Find the most recent ":: There are N ..." heading and the menu lines after it.
+fn menu_block(context: &[String]) -> (Option<String>, Vec<String>) {
+    let Some(start) = context
+        .iter()
+        .rposition(|line| line.trim_start().starts_with(":: There are"))
+    else {
+        return (None, Vec::new());
+    };
+    let heading = strip_marker(context[start].trim())
+        .trim_end_matches(':')
+        .to_string();
+    let menu = context[start + 1..]
+        .iter()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    (Some(heading), menu)
+}
+
+/// Parse pacman/paru numbered menus:
+/// ```text
+/// :: Repository extra
+///    1) jdk-openjdk  2) jdk11-openjdk
+/// ```
+fn parse_menu_options(menu: &[String]) -> Vec<PromptOption> {
+    let mut options = Vec::new();
+    let mut repository = None;
+
+    for line in menu {
+        if let Some(repo) = line.strip_prefix(":: Repository ") {
+            repository = Some(repo.trim().to_string());
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            let Some(number) = token.strip_suffix(')') else {
+                continue;
+            };
+            if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            if let Some(label) = tokens.next() {
+                options.push(PromptOption {
+                    value: number.to_string(),
+                    label: label.to_string(),
+                    repository: repository.clone(),
+                });
+            }
+        }
+    }
+
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PromptKind, PromptOption, detect_prompt};
+
+    fn lines(input: &[&str]) -> Vec<String> {
+        input.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn detects_confirm_prompts_with_default() {
+        let prompt = detect_prompt(&[], ":: Proceed with installation? [Y/n] ").unwrap();
+        assert_eq!(prompt.question, "Proceed with installation?");
+        assert_eq!(prompt.kind, PromptKind::Confirm { default_yes: true });
+
+        let conflict = detect_prompt(
+            &[],
+            ":: foo and foo-git are in conflict. Remove foo-git? [y/N]",
+        )
+        .unwrap();
+        assert_eq!(conflict.kind, PromptKind::Confirm { default_yes: false });
+        assert_eq!(conflict.default_answer(), "n");
+    }
+
+    #[test]
+    fn detects_provider_selection() {
+        let context = lines(&[
+            "resolving dependencies...",
+            ":: There are 3 providers available for java-runtime:",
+            ":: Repository extra",
+            "   1) jdk-openjdk  2) jdk11-openjdk  3) jdk17-openjdk",
+            "",
+        ]);
+        let prompt = detect_prompt(&context, "Enter a number (default=1): ").unwrap();
+        assert_eq!(
+            prompt.question,
+            "There are 3 providers available for java-runtime"
+        );
+        let PromptKind::Choice { options, default } = prompt.kind else {
+            panic!("expected a choice prompt");
+        };
+        assert_eq!(default.as_deref(), Some("1"));
+        assert_eq!(options.len(), 3);
+        assert_eq!(
+            options[1],
+            PromptOption {
+                value: "2".to_string(),
+                label: "jdk11-openjdk".to_string(),
+                repository: Some("extra".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn detects_password_and_paru_menus() {
+        let password = detect_prompt(&[], "[sudo] password for alex: ").unwrap();
+        assert_eq!(password.kind, PromptKind::Password);
+
+        let context = lines(&[
+            ":: Resolving dependencies...",
+            "==> Packages to cleanBuild?",
+            "==> [N]one [A]ll [Ab]ort [I]nstalled [No]tInstalled or (1 2 3, 1-3, ^4)",
+        ]);
+        let menu = detect_prompt(&context, "==> ").unwrap();
+        assert_eq!(menu.question, "Packages to cleanBuild?");
+        assert_eq!(menu.details.len(), 1);
+
+        assert!(detect_prompt(&[], "downloading foo-1.0.tar.zst...").is_none());
+    }
+}
@@ -0,0 +1,337 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+const PTY_COLUMNS: u16 = 120;
+const PTY_ROWS: u16 = 40;
+/// How long a killed process group gets to exit on SIGTERM before SIGKILL.
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PtyEvent {
+    /// A complete line of output (terminated by `\n` or a `\r` redraw).
+    Line(String),
+    /// Text printed without a line terminator yet, e.g. a prompt waiting for input.
+    Partial(String),
+}
+
+/// A child process attached to a pseudo-terminal owned by parut.
+///
+/// Output is read on a background thread and delivered as [`PtyEvent`]s, so the
+/// caller can poll for cancellation and process exit in between lines.
+pub struct PtyProcess {
+    child: Child,
+    writer: File,
+    events: Receiver<PtyEvent>,
+}
+
+impl PtyProcess {
+    pub fn spawn(program: &str, args: &[String], env: &[(String, String)]) -> io::Result<Self> {
+        let (master, slave) = open_pty()?;
+
+        let mut cmd = Command::new(program);
+        cmd.args(args)
+            .env("TERM", "xterm-256color")
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        for (key, value) in env {
+            cmd.env(key, value);
+        }
+
+        // SAFETY: only async-signal-safe libc calls are made between fork and exec.
+        unsafe {
+            cmd.pre_exec(|| {
+                // Become a session leader so the PTY slave (already on fd 0)
+                // turns into our controlling terminal; sudo and pacman need one.
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let child = cmd.spawn()?;
+        // Drop our copies of the slave side so reads hit EOF once the child exits.
+        drop(cmd);
+
+        let writer = File::from(master);
+        let reader = writer.try_clone()?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || Self::read_loop(reader, tx));
+
+        Ok(Self {
+            child,
+            writer,
+            events: rx,
+        })
+    }
+
+    fn read_loop(mut reader: File, tx: mpsc::Sender<PtyEvent>) {
+        let mut splitter = LineSplitter::default();
+        let mut buf = [0u8; 4096];
+        loop {
+            match reader.read(&mut buf) {
+                // Linux reports EIO on the master once every slave fd is closed.
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    for event in splitter.push(&buf[..n]) {
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(rest) = splitter.finish() {
+            let _ = tx.send(PtyEvent::Line(rest));
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<PtyEvent, RecvTimeoutError> {
+        self.events.recv_timeout(timeout)
+    }
+
+    /// Write a line of input to the process, as if typed on its terminal.
+    pub fn write_line(&mut self, text: &str) -> io::Result<()> {
+        self.writer.write_all(text.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.child.wait()
+    }
+
+    /// Terminate the whole process group started for this terminal: SIGTERM
+    /// first, so pacman can release its lock, then SIGKILL if it is still
+    /// running after [`KILL_GRACE`].
+    pub fn kill(&mut self) {
+        let Ok(pid) = i32::try_from(self.child.id()) else {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            return;
+        };
+        // SAFETY: plain syscall; the negative pid targets the group led by our child.
+        unsafe {
+            libc::kill(-pid, libc::SIGTERM);
+        }
+        let deadline = std::time::Instant::now() + KILL_GRACE;
+        while std::time::Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        // SAFETY: as above.
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+        let _ = self.child.wait();
+    }
+}
+
+fn open_pty() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut master: libc::c_int = -1;
+    let mut slave: libc::c_int = -1;
+    let size = libc::winsize {
+        ws_row: PTY_ROWS,
+        ws_col: PTY_COLUMNS,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    // SAFETY: openpty writes two fds on success; we take ownership of both right away.
+    let rc = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            &size,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+
+    for fd in [&master, &slave] {
+        // SAFETY: fcntl on fds we own; keeps them from leaking into other children.
+        unsafe {
+            libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+    }
+
+    Ok((master, slave))
+}
+
+/// Splits raw terminal output into clean lines.
+#[derive(Default)]
+struct LineSplitter {
+    pending: Vec<u8>,
+    /// The last line ended by a bare `\r`, i.e. one a progress bar may redraw.
+    last_redraw: Option<String>,
+    /// The previous byte was `\r`, so a `\n` now ends that line rather than
+    /// an empty one.
+    after_cr: bool,
+}
+
+impl LineSplitter {
+    fn push(&mut self, bytes: &[u8]) -> Vec<PtyEvent> {
+        let mut events = Vec::new();
+        for &byte in bytes {
+            let after_cr = std::mem::replace(&mut self.after_cr, byte == b'\r');
+            if byte == b'\n' || byte == b'\r' {
+                let raw = std::mem::take(&mut self.pending);
+                let line = clean_line(&raw);
+                if byte == b'\n' && after_cr {
+                    // `\r\n`: the line was a real one after all, not a redraw.
+                    self.last_redraw = None;
+                    continue;
+                }
+                // Progress bars redraw the same line with `\r`; skip exact repeats
+                // of those, but keep repeated lines of ordinary output.
+                let redraw = byte == b'\r';
+                let repeated = redraw && self.last_redraw.as_deref() == Some(line.as_str());
+                if !line.is_empty() && !repeated {
+                    events.push(PtyEvent::Line(line.clone()));
+                }
+                self.last_redraw = redraw.then_some(line);
+            } else {
+                self.pending.push(byte);
+            }
+        }
+
+        if !self.pending.is_empty() {
+            let partial = clean_line(&self.pending);
+            if !partial.is_empty() {
+                events.push(PtyEvent::Partial(partial));
+            }
+        }
+        events
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        let raw = std::mem::take(&mut self.pending);
+        let line = clean_line(&raw);
+        if line.is_empty() { None } else { Some(line) }
+    }
+}
+
+fn clean_line(raw: &[u8]) -> String {
+    strip_ansi(&String::from_utf8_lossy(raw))
+        .trim_end()
+        .to_string()
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement, titles) from terminal text.
+pub fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            if !c.is_control() || c == '\t' {
+                out.push(c);
+            }
+            continue;
+        }
+
+        match chars.next() {
+            // CSI: ESC [ params... final byte in @..~
+            Some('[') => {
+                for next in chars.by_ref() {
+                    if ('@'..='~').contains(&next) {
+                        break;
+                    }
+                }
+            }
+            // OSC: ESC ] ... terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(next) = chars.next() {
+                    if next == '\u{7}' {
+                        break;
+                    }
+                    if next == '\u{1b}' {
+                        chars.next_if_eq(&'\\');
+                        break;
+                    }
+                }
+            }
+            // Two-character sequences such as ESC ( B
+            Some('(') | Some(')') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineSplitter, PtyEvent, PtyProcess, strip_ansi};
+    use std::time::Duration;
+
+    #[test]
+    fn strips_color_and_cursor_sequences() {
+        let input =
+            "\u{1b}[1;34m::\u{1b}[0m\u{1b}[1m Synchronizing package databases...\u{1b}[0m\u{1b}[K";
+        assert_eq!(strip_ansi(input), ":: Synchronizing package databases...");
+        assert_eq!(strip_ansi("\u{1b}]0;paru\u{7}done"), "done");
+    }
+
+    #[test]
+    fn splits_lines_and_collapses_progress_redraws() {
+        let mut splitter = LineSplitter::default();
+        let events = splitter.push(b"first\r\n 50%\r 50%\r100%\nProceed? [Y/n] ");
+        assert_eq!(
+            events,
+            vec![
+                PtyEvent::Line("first".to_string()),
+                PtyEvent::Line(" 50%".to_string()),
+                PtyEvent::Line("100%".to_string()),
+                PtyEvent::Partial("Proceed? [Y/n]".to_string()),
+            ]
+        );
+        assert_eq!(splitter.finish().as_deref(), Some("Proceed? [Y/n]"));
+
+        // Lines that really repeat are kept, whatever their line ending.
+        let mut splitter = LineSplitter::default();
+        let events = splitter.push(b"ok\nok\nwarn\r\nwarn\r\n");
+        let lines: Vec<PtyEvent> = ["ok", "ok", "warn", "warn"]
+            .iter()
+            .map(|l| PtyEvent::Line(l.to_string()))
+            .collect();
+        assert_eq!(events, lines);
+    }
+
+    #[test]
+    fn captures_output_of_process_on_pty() {
+        let args = vec!["-c".to_string(), "printf 'hello\\nworld\\n'".to_string()];
+        let mut process = PtyProcess::spawn("sh", &args, &[]).expect("spawn sh on pty");
+
+        let mut lines = Vec::new();
+        while let Ok(event) = process.recv_timeout(Duration::from_secs(5)) {
+            if let PtyEvent::Line(line) = event {
+                lines.push(line);
+            }
+        }
+        assert!(process.wait().expect("wait for sh").success());
+        assert_eq!(lines, vec!["hello".to_string(), "world".to_string()]);
+    }
+}
@@ -0,0 +1,383 @@
+use crate::pty::{PtyEvent, PtyProcess};
+use std::cell::RefCell;
+use std::io;
+use std::process::{Child, Command, ExitStatus, Output};
+use std::sync::Arc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+/// Everything needed to start an external command, independent of how it runs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+impl CommandSpec {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// `program arg1 arg2 ...`, used for logs and for matching scripted commands.
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Run to completion and capture stdout/stderr.
+    pub fn output(&self) -> io::Result<Output> {
+        current().output(self)
+    }
+
+    /// Start detached from parut's stdio (used for terminal emulators).
+    pub fn spawn(&self) -> io::Result<Box<dyn ChildProcess>> {
+        current().spawn(self)
+    }
+
+    /// Start on a pseudo-terminal owned by parut.
+    pub fn spawn_pty(&self) -> io::Result<Box<dyn PtySession>> {
+        current().spawn_pty(self)
+    }
+}
+
+pub trait ChildProcess: Send {
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+    fn kill(&mut self);
+}
+
+pub trait PtySession: Send {
+    fn recv_timeout(&self, timeout: Duration) -> Result<PtyEvent, RecvTimeoutError>;
+    fn write_line(&mut self, text: &str) -> io::Result<()>;
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+    fn wait(&mut self) -> io::Result<ExitStatus>;
+    fn kill(&mut self);
+}
+
+/// How `ParuBackend` reaches paru, pacman and friends.
+pub trait CommandRunner: Send + Sync {
+    fn output(&self, spec: &CommandSpec) -> io::Result<Output>;
+    fn spawn(&self, spec: &CommandSpec) -> io::Result<Box<dyn ChildProcess>>;
+    fn spawn_pty(&self, spec: &CommandSpec) -> io::Result<Box<dyn PtySession>>;
+    fn command_exists(&self, program: &str) -> bool;
+}
+
+/// Runs real processes on the host.
+pub struct SystemRunner;
+
+impl SystemRunner {
+    fn command(spec: &CommandSpec) -> Command {
+        let mut cmd = Command::new(&spec.program);
+        cmd.args(&spec.args);
+        for (key, value) in &spec.env {
+            cmd.env(key, value);
+        }
+        cmd
+    }
+}
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, spec: &CommandSpec) -> io::Result<Output> {
+        Self::command(spec).output()
+    }
+
+    fn spawn(&self, spec: &CommandSpec) -> io::Result<Box<dyn ChildProcess>> {
+        Ok(Box::new(Self::command(spec).spawn()?))
+    }
+
+    fn spawn_pty(&self, spec: &CommandSpec) -> io::Result<Box<dyn PtySession>> {
+        Ok(Box::new(PtyProcess::spawn(
+            &spec.program,
+            &spec.args,
+            &spec.env,
+        )?))
+    }
+
+    fn command_exists(&self, program: &str) -> bool {
+        std::env::var_os("PATH")
+            .map(|paths| {
+                std::env::split_paths(&paths)
+                    .map(|p| p.join(program))
+                    .any(|full| full.is_file())
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl ChildProcess for Child {
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Child::try_wait(self)
+    }
+
+    fn kill(&mut self) {
+        let _ = Child::kill(self);
+        let _ = self.wait();
+    }
+}
+
+impl PtySession for PtyProcess {
+    fn recv_timeout(&self, timeout: Duration) -> Result<PtyEvent, RecvTimeoutError> {
+        PtyProcess::recv_timeout(self, timeout)
+    }
+
+    fn write_line(&mut self, text: &str) -> io::Result<()> {
+        PtyProcess::write_line(self, text)
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        PtyProcess::try_wait(self)
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        PtyProcess::wait(self)
+    }
+
+    fn kill(&mut self) {
+        PtyProcess::kill(self)
+    }
+}
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<Arc<dyn CommandRunner>>> = const { RefCell::new(None) };
+}
+
+/// The runner used by the backend on this thread.
+pub fn current() -> Arc<dyn CommandRunner> {
+    OVERRIDE
+        .with(|runner| runner.borrow().clone())
+        .unwrap_or_else(|| Arc::new(SystemRunner))
+}
+
+pub fn command_exists(program: &str) -> bool {
+    current().command_exists(program)
+}
+
+/// Route every command issued by `f` on this thread through `runner`.
+#[cfg(test)]
+pub fn with_runner<R>(runner: Arc<dyn CommandRunner>, f: impl FnOnce() -> R) -> R {
+    let previous = OVERRIDE.with(|slot| slot.replace(Some(runner)));
+    let result = f();
+    OVERRIDE.with(|slot| *slot.borrow_mut() = previous);
+    result
+}
+
+#[cfg(test)]
+pub use fake::FakeRunner;
+
+#[cfg(test)]
+mod fake {
+    use super::{ChildProcess, CommandRunner, CommandSpec, PtySession};
+    use crate::pty::PtyEvent;
+    use std::collections::{HashSet, VecDeque};
+    use std::io;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn exit_status(code: i32) -> ExitStatus {
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[derive(Clone)]
+    struct Script {
+        pattern: String,
+        stdout: String,
+        stderr: String,
+        code: i32,
+        events: Vec<PtyEvent>,
+    }
+
+    impl Script {
+        /// Exact command line, or a prefix when the pattern ends in ` *`.
+        fn matches(&self, command_line: &str) -> bool {
+            match self.pattern.strip_suffix(" *") {
+                Some(prefix) => command_line.starts_with(prefix),
+                None => command_line == self.pattern,
+            }
+        }
+    }
+
+    /// A scripted stand-in for paru/pacman that records every call it receives.
+    #[derive(Default)]
+    pub struct FakeRunner {
+        scripts: Vec<Script>,
+        programs: HashSet<String>,
+        calls: Mutex<Vec<String>>,
+        inputs: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl FakeRunner {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Mark `program` as present on `PATH`.
+        pub fn with_program(mut self, program: &str) -> Self {
+            self.programs.insert(program.to_string());
+            self
+        }
+
+        pub fn with_output(self, pattern: &str, stdout: &str) -> Self {
+            self.with_result(pattern, 0, stdout, "")
+        }
+
+        pub fn with_result(mut self, pattern: &str, code: i32, stdout: &str, stderr: &str) -> Self {
+            self.scripts.push(Script {
+                pattern: pattern.to_string(),
+                stdout: stdout.to_string(),
+                stderr: stderr.to_string(),
+                code,
+                events: Vec::new(),
+            });
+            self
+        }
+
+        /// Script an interactive (PTY) run: the events are replayed in order.
+        pub fn with_session(mut self, pattern: &str, events: Vec<PtyEvent>, code: i32) -> Self {
+            self.scripts.push(Script {
+                pattern: pattern.to_string(),
+                stdout: String::new(),
+                stderr: String::new(),
+                code,
+                events,
+            });
+            self
+        }
+
+        /// Every command line run so far, in order.
+        pub fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        /// Lines written to interactive sessions (prompt answers).
+        pub fn inputs(&self) -> Vec<String> {
+            self.inputs.lock().unwrap().clone()
+        }
+
+        fn record(&self, spec: &CommandSpec) -> Option<Script> {
+            let command_line = spec.command_line();
+            self.calls.lock().unwrap().push(command_line.clone());
+            self.scripts
+                .iter()
+                .find(|script| script.matches(&command_line))
+                .cloned()
+        }
+    }
+
+    impl CommandRunner for FakeRunner {
+        fn output(&self, spec: &CommandSpec) -> io::Result<Output> {
+            let output = match self.record(spec) {
+                Some(script) => Output {
+                    status: exit_status(script.code),
+                    stdout: script.stdout.into_bytes(),
+                    stderr: script.stderr.into_bytes(),
+                },
+                None => Output {
+                    status: exit_status(127),
+                    stdout: Vec::new(),
+                    stderr: format!("unscripted command: {}", spec.command_line()).into_bytes(),
+                },
+            };
+            Ok(output)
+        }
+
+        fn spawn(&self, spec: &CommandSpec) -> io::Result<Box<dyn ChildProcess>> {
+            let script = self
+                .record(spec)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, spec.command_line()))?;
+            Ok(Box::new(FakeChild {
+                status: exit_status(script.code),
+            }))
+        }
+
+        fn spawn_pty(&self, spec: &CommandSpec) -> io::Result<Box<dyn PtySession>> {
+            let script = self
+                .record(spec)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, spec.command_line()))?;
+            Ok(Box::new(FakeSession {
+                events: Mutex::new(script.events.into()),
+                status: exit_status(script.code),
+                inputs: self.inputs.clone(),
+            }))
+        }
+
+        fn command_exists(&self, program: &str) -> bool {
+            self.programs.contains(program)
+        }
+    }
+
+    struct FakeChild {
+        status: ExitStatus,
+    }
+
+    impl ChildProcess for FakeChild {
+        fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+            Ok(Some(self.status))
+        }
+
+        fn kill(&mut self) {}
+    }
+
+    struct FakeSession {
+        events: Mutex<VecDeque<PtyEvent>>,
+        status: ExitStatus,
+        inputs: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PtySession for FakeSession {
+        fn recv_timeout(&self, _timeout: Duration) -> Result<PtyEvent, RecvTimeoutError> {
+            self.events
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or(RecvTimeoutError::Disconnected)
+        }
+
+        fn write_line(&mut self, text: &str) -> io::Result<()> {
+            self.inputs.lock().unwrap().push(text.to_string());
+            Ok(())
+        }
+
+        fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+            if self.events.lock().unwrap().is_empty() {
+                Ok(Some(self.status))
+            } else {
+                Ok(None)
+            }
+        }
+
+        fn wait(&mut self) -> io::Result<ExitStatus> {
+            Ok(self.status)
+        }
+
+        fn kill(&mut self) {
+            self.events.lock().unwrap().clear();
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use crate::task_type::TaskType;
+use chrono::{Days, Local, NaiveDateTime, TimeDelta, TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// The format scheduled times are entered in, in local time.
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recurrence {
+    Once,
+    Daily,
+    Weekly,
+}
+
+impl Recurrence {
+    pub const ALL: [Recurrence; 3] = [Recurrence::Once, Recurrence::Daily, Recurrence::Weekly];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Recurrence::Once => "Once",
+            Recurrence::Daily => "Every day",
+            Recurrence::Weekly => "Every week",
+        }
+    }
+
+    /// The occurrence after `at`, at the same local time of day (so DST changes
+    /// don't shift a nightly run by an hour). `shifted_hours` is how far a DST
+    /// gap pushed `at` past that time; returns the same for the new run.
+    fn after(self, at: i64, shifted_hours: u32) -> Option<(i64, u32)> {
+        self.after_in(&Local, at, shifted_hours)
+    }
+
+    fn after_in<Tz: TimeZone>(self, tz: &Tz, at: i64, shifted_hours: u32) -> Option<(i64, u32)> {
+        let days = match self {
+            Recurrence::Once => return None,
+            Recurrence::Daily => 1,
+            Recurrence::Weekly => 7,
+        };
+        let local = tz.timestamp_opt(at, 0).single()?.naive_local()
+            - TimeDelta::hours(shifted_hours.into());
+        let next = local.checked_add_days(Days::new(days))?;
+        // A time the clocks skip when DST starts is moved forward by whole hours
+        // until it exists, so that night's run still happens.
+        (0..=48).find_map(|hours| {
+            tz.from_local_datetime(&(next + TimeDelta::hours(hours.into())))
+                .earliest()
+                .map(|at| (at.timestamp(), hours))
+        })
+    }
+}
+
+/// A task the worker queues by itself once `next_run` has passed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: u64,
+    pub task_type: TaskType,
+    pub package_name: String,
+    pub recurrence: Recurrence,
+    /// Unix timestamp of the next run.
+    pub next_run: i64,
+    /// Hours a DST gap pushed `next_run` past its usual local time.
+    #[serde(default)]
+    pub shifted_hours: u32,
+}
+
+impl Schedule {
+    pub fn is_due(&self, now: i64) -> bool {
+        self.next_run <= now
+    }
+
+    /// Move `next_run` past `now`. Runs missed while parut was closed are not
+    /// made up one by one; the run just queued stands in for them.
+    /// Returns `false` once a one-off schedule has run.
+    fn advance(&mut self, now: i64) -> bool {
+        while self.next_run <= now {
+            match self.recurrence.after(self.next_run, self.shifted_hours) {
+                Some((next, shifted_hours)) if next > self.next_run => {
+                    self.next_run = next;
+                    self.shifted_hours = shifted_hours;
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Remove the schedules that are due from `schedules` and return them. Recurring
+/// ones are put back with their next run.
+pub fn take_due(schedules: &mut Vec<Schedule>, now: i64) -> Vec<Schedule> {
+    let due: Vec<Schedule> = schedules
+        .iter()
+        .filter(|s| s.is_due(now))
+        .cloned()
+        .collect();
+    schedules.retain_mut(|s| !s.is_due(now) || s.advance(now));
+    due
+}
+
+/// Parse a local time written as [`TIME_FORMAT`].
+pub fn parse_local_time(text: &str) -> Option<i64> {
+    let naive = NaiveDateTime::parse_from_str(text.trim(), TIME_FORMAT).ok()?;
+    Some(naive.and_local_timezone(Local).earliest()?.timestamp())
+}
+
+/// `at` as [`TIME_FORMAT`], the way [`parse_local_time`] reads it back.
+pub fn format_local_time(at: i64) -> String {
+    Local
+        .timestamp_opt(at, 0)
+        .single()
+        .map(|dt| dt.format(TIME_FORMAT).to_string())
+        .unwrap_or_default()
+}
+
+/// "Today 03:00", "Tomorrow 03:00" or "Mon 19 Oct 03:00".
+pub fn describe_next_run(at: i64, now: i64) -> String {
+    let (Some(at), Some(now)) = (
+        Local.timestamp_opt(at, 0).single(),
+        Local.timestamp_opt(now, 0).single(),
+    ) else {
+        return String::new();
+    };
+    let days = (at.date_naive() - now.date_naive()).num_days();
+    let time = at.format("%H:%M");
+    match days {
+        0 => format!("Today {}", time),
+        1 => format!("Tomorrow {}", time),
+        _ => at.format("%a %d %b %H:%M").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recurrence, Schedule, format_local_time, parse_local_time, take_due};
+    use crate::task_type::TaskType;
+    use chrono::{
+        FixedOffset, MappedLocalTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+    };
+
+    /// Central European time around 2026-03-29, when clocks jump from 02:00
+    /// to 03:00.
+    #[derive(Clone)]
+    struct SpringForward;
+
+    impl SpringForward {
+        fn switch() -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2026, 3, 29)
+                .unwrap()
+                .and_hms_opt(1, 0, 0)
+                .unwrap()
+        }
+
+        fn offset(summer: bool) -> FixedOffset {
+            FixedOffset::east_opt(if summer { 7200 } else { 3600 }).unwrap()
+        }
+    }
+
+    impl TimeZone for SpringForward {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            SpringForward
+        }
+
+        fn offset_from_local_date(&self, _: &NaiveDate) -> MappedLocalTime<FixedOffset> {
+            MappedLocalTime::None
+        }
+
+        fn offset_from_local_datetime(
+            &self,
+            local: &NaiveDateTime,
+        ) -> MappedLocalTime<FixedOffset> {
+            let winter_utc = *local - Self::offset(false);
+            let summer_utc = *local - Self::offset(true);
+            match (winter_utc < Self::switch(), summer_utc >= Self::switch()) {
+                (true, _) => MappedLocalTime::Single(Self::offset(false)),
+                (_, true) => MappedLocalTime::Single(Self::offset(true)),
+                _ => MappedLocalTime::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, _: &NaiveDate) -> FixedOffset {
+            Self::offset(false)
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Self::offset(*utc >= Self::switch())
+        }
+    }
+
+    fn schedule(id: u64, recurrence: Recurrence, next_run: i64) -> Schedule {
+        Schedule {
+            id,
+            task_type: TaskType::CleanCache,
+            package_name: "system".to_string(),
+            recurrence,
+            next_run,
+            shifted_hours: 0,
+        }
+    }
+
+    #[test]
+    fn takes_due_schedules_and_keeps_recurring_ones() {
+        let start = parse_local_time("2026-03-02 03:00").expect("parse time");
+        let mut schedules = vec![
+            schedule(1, Recurrence::Once, start),
+            schedule(2, Recurrence::Weekly, start),
+            schedule(3, Recurrence::Daily, start + 3600),
+        ];
+
+        assert!(take_due(&mut schedules, start - 1).is_empty());
+        assert_eq!(schedules.len(), 3);
+
+        let due: Vec<u64> = take_due(&mut schedules, start)
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(due, vec![1, 2]);
+        let left: Vec<u64> = schedules.iter().map(|s| s.id).collect();
+        assert_eq!(left, vec![2, 3]);
+        assert_eq!(format_local_time(schedules[0].next_run), "2026-03-09 03:00");
+
+        // Ten days closed: the daily run happens once, then resumes at its usual time.
+        let later = parse_local_time("2026-03-12 12:00").unwrap();
+        let due: Vec<u64> = take_due(&mut schedules, later)
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(due, vec![2, 3]);
+        assert_eq!(format_local_time(schedules[0].next_run), "2026-03-16 03:00");
+        assert_eq!(format_local_time(schedules[1].next_run), "2026-03-13 04:00");
+    }
+
+    #[test]
+    fn runs_falling_into_a_dst_gap_move_forward() {
+        let local = |day, hour, minute| {
+            NaiveDate::from_ymd_opt(2026, 3, day)
+                .unwrap()
+                .and_time(NaiveTime::from_hms_opt(hour, minute, 0).unwrap())
+        };
+        let at = SpringForward
+            .from_local_datetime(&local(28, 2, 30))
+            .single()
+            .unwrap()
+            .timestamp();
+
+        // 02:30 does not exist on the 29th; the run happens at 03:30 instead.
+        let (next, shifted) = Recurrence::Daily
+            .after_in(&SpringForward, at, 0)
+            .expect("next run");
+        assert_eq!(shifted, 1);
+        let next_local = |at| {
+            Utc.timestamp_opt(at, 0)
+                .unwrap()
+                .with_timezone(&SpringForward)
+                .naive_local()
+        };
+        assert_eq!(next_local(next), local(29, 3, 30));
+
+        // And the day after it is back at 02:30.
+        let (after, shifted) = Recurrence::Daily
+            .after_in(&SpringForward, next, shifted)
+            .expect("run after");
+        assert_eq!(shifted, 0);
+        assert_eq!(next_local(after), local(30, 2, 30));
+    }
+
+    #[test]
+    fn parses_entered_times() {
+        let at = parse_local_time(" 2026-10-16 21:30 ").expect("parse time");
+        assert_eq!(format_local_time(at), "2026-10-16 21:30");
+        assert!(parse_local_time("tomorrow").is_none());
+        assert!(parse_local_time("2026-13-01 10:00").is_none());
+    }
+}
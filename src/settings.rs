@@ -1,9 +1,88 @@
+use crate::task_type::TaskType;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Mutex;
 use std::sync::OnceLock;
 
+/// One task of a [`Routine`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RoutineStep {
+    pub task_type: TaskType,
+    pub package_name: String,
+    /// Go on with the next step even if this one fails.
+    #[serde(default)]
+    pub continue_on_failure: bool,
+}
+
+/// A named sequence of tasks queued together, each step after the previous one.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Routine {
+    pub name: String,
+    pub steps: Vec<RoutineStep>,
+}
+
+impl Routine {
+    fn full_maintenance() -> Self {
+        let step = |task_type, continue_on_failure| RoutineStep {
+            task_type,
+            package_name: "system".to_string(),
+            continue_on_failure,
+        };
+        Self {
+            name: "Full Maintenance".to_string(),
+            steps: vec![
+                step(TaskType::Update, false),
+                step(TaskType::RemoveOrphans, true),
+                step(TaskType::CleanCache, true),
+            ],
+        }
+    }
+}
+
+/// Cancel tasks of `task_type` that are still running after `minutes`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TaskTimeout {
+    pub task_type: TaskType,
+    pub minutes: u64,
+}
+
+/// Whether a [`TaskHook`] runs before or after its task.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum HookStage {
+    Before,
+    After,
+}
+
+impl HookStage {
+    pub fn label(self) -> &'static str {
+        match self {
+            HookStage::Before => "Before",
+            HookStage::After => "After",
+        }
+    }
+}
+
+/// A shell command run around tasks of the given types. After-hooks only run
+/// once the task itself has succeeded.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TaskHook {
+    pub name: String,
+    /// Run with `sh -c`.
+    pub command: String,
+    pub stage: HookStage,
+    pub task_types: Vec<TaskType>,
+    /// Fail the task if the hook fails. For before-hooks the task does not run.
+    #[serde(default)]
+    pub abort_on_failure: bool,
+}
+
+impl TaskHook {
+    pub fn applies_to(&self, stage: HookStage, task_type: &TaskType) -> bool {
+        self.stage == stage && self.task_types.contains(task_type)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct AppSettings {
@@ -18,7 +97,10 @@ pub struct AppSettings {
     pub confirm_remove: bool,
     pub confirm_update_all: bool,
     pub confirm_clean_cache: bool,
+    pub task_execution_mode: String, // embedded, terminal
+    pub interactive_prompts: bool,
     pub terminal_preference: String, // auto, gnome-terminal, konsole, xterm, xfce4-terminal, alacritty
+    pub privilege_helper: String,    // auto, pkexec, sudo
     pub show_only_updates_from: String, // all, repo-only, aur-only
     pub default_sort_installed: u32,
     pub default_sort_search: u32,
@@ -35,6 +117,8 @@ pub struct AppSettings {
     pub refresh_on_network_reconnect: bool,
     pub cache_ttl_minutes: u64,
     pub max_parallel_tasks: usize,
+    pub batch_package_tasks: bool,
+    pub parallel_aur_builds: bool,
     pub task_output_lines_limit: usize,
     pub confirm_batch_install: bool,
     pub confirm_batch_remove: bool,
@@ -44,6 +128,20 @@ pub struct AppSettings {
     pub startup_tab: String, // dashboard, search, installed, updates, watchlist
     pub show_package_sizes_in_lists: bool,
     pub auto_clear_completed_tasks_minutes: u64, // 0, 5, 15, 60
+    pub aur_rpc_base_url: String,
+    pub routines: Vec<Routine>,
+    pub task_timeouts: Vec<TaskTimeout>,
+    pub task_hooks: Vec<TaskHook>,
+}
+
+impl AppSettings {
+    /// The timeout for `task_type`, if one is set.
+    pub fn timeout_minutes(&self, task_type: &TaskType) -> Option<u64> {
+        self.task_timeouts
+            .iter()
+            .find(|t| &t.task_type == task_type && t.minutes > 0)
+            .map(|t| t.minutes)
+    }
 }
 
 impl Default for AppSettings {
@@ -60,7 +158,10 @@ impl Default for AppSettings {
             confirm_remove: true,
             confirm_update_all: true,
             confirm_clean_cache: true,
+            task_execution_mode: "embedded".to_string(),
+            interactive_prompts: true,
             terminal_preference: "auto".to_string(),
+            privilege_helper: "auto".to_string(),
             show_only_updates_from: "all".to_string(),
             default_sort_installed: 0,
             default_sort_search: 0,
@@ -77,6 +178,8 @@ impl Default for AppSettings {
             refresh_on_network_reconnect: true,
             cache_ttl_minutes: 60,
             max_parallel_tasks: 1,
+            batch_package_tasks: true,
+            parallel_aur_builds: true,
             task_output_lines_limit: 300,
             confirm_batch_install: true,
             confirm_batch_remove: true,
@@ -86,6 +189,10 @@ impl Default for AppSettings {
             startup_tab: "dashboard".to_string(),
             show_package_sizes_in_lists: false,
             auto_clear_completed_tasks_minutes: 0,
+            aur_rpc_base_url: crate::aur::DEFAULT_AUR_URL.to_string(),
+            routines: vec![Routine::full_maintenance()],
+            task_timeouts: Vec::new(),
+            task_hooks: Vec::new(),
         }
     }
 }
@@ -174,6 +281,8 @@ mod tests {
         assert_eq!(parsed.confirm_remove, settings.confirm_remove);
         assert_eq!(parsed.confirm_update_all, settings.confirm_update_all);
         assert_eq!(parsed.confirm_clean_cache, settings.confirm_clean_cache);
+        assert_eq!(parsed.task_execution_mode, settings.task_execution_mode);
+        assert_eq!(parsed.interactive_prompts, settings.interactive_prompts);
         assert_eq!(parsed.terminal_preference, settings.terminal_preference);
         assert_eq!(
             parsed.show_only_updates_from,
@@ -209,6 +318,8 @@ mod tests {
         );
         assert_eq!(parsed.cache_ttl_minutes, settings.cache_ttl_minutes);
         assert_eq!(parsed.max_parallel_tasks, settings.max_parallel_tasks);
+        assert_eq!(parsed.batch_package_tasks, settings.batch_package_tasks);
+        assert_eq!(parsed.parallel_aur_builds, settings.parallel_aur_builds);
         assert_eq!(
             parsed.task_output_lines_limit,
             settings.task_output_lines_limit
@@ -233,5 +344,10 @@ mod tests {
             parsed.auto_clear_completed_tasks_minutes,
             settings.auto_clear_completed_tasks_minutes
         );
+        assert_eq!(parsed.aur_rpc_base_url, settings.aur_rpc_base_url);
+        assert_eq!(parsed.routines, settings.routines);
+        assert_eq!(parsed.task_timeouts, settings.task_timeouts);
+        assert_eq!(parsed.task_hooks, settings.task_hooks);
+        assert_eq!(parsed.privilege_helper, settings.privilege_helper);
     }
 }
@@ -0,0 +1,348 @@
+use crate::pacman_db::{self, DbPackage};
+use flate2::read::GzDecoder;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+pub const SYNC_DB_PATH: &str = "/var/lib/pacman/sync";
+pub const PACMAN_CONF_PATH: &str = "/etc/pacman.conf";
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// A package from one of the sync databases, tagged with its repository.
+#[derive(Debug, Clone)]
+pub struct SyncPackage {
+    pub repository: String,
+    pub package: DbPackage,
+}
+
+/// Every package of every sync database, in pacman.conf repository order.
+#[derive(Debug, Default)]
+pub struct SyncIndex {
+    pub packages: Vec<SyncPackage>,
+    /// Lowercased "name description provides" per package, for substring search.
+    haystacks: Vec<String>,
+}
+
+impl SyncIndex {
+    fn new(packages: Vec<SyncPackage>) -> Self {
+        let haystacks = packages
+            .iter()
+            .map(|entry| {
+                let package = &entry.package;
+                let mut text = format!("{} {}", package.name, package.description);
+                for provide in &package.provides {
+                    text.push(' ');
+                    text.push_str(pacman_db::dependency_name(provide));
+                }
+                text.to_lowercase()
+            })
+            .collect();
+        Self {
+            packages,
+            haystacks,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.packages.iter().any(|entry| entry.package.name == name)
+    }
+
+    /// The first repository (in pacman.conf order) that has `name`.
+    pub fn repository_of(&self, name: &str) -> Option<&str> {
+        self.packages
+            .iter()
+            .find(|entry| entry.package.name == name)
+            .map(|entry| entry.repository.as_str())
+    }
+
+    /// Like `pacman -Ss`: every whitespace-separated term must appear in the
+    /// name, description or provides (case-insensitive).
+    pub fn search(&self, query: &str) -> Vec<&SyncPackage> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        self.packages
+            .iter()
+            .zip(&self.haystacks)
+            .filter(|(_, haystack)| terms.iter().all(|term| haystack.contains(term.as_str())))
+            .map(|(entry, _)| entry)
+            .collect()
+    }
+}
+
+/// Read one repository database (`core.db`, ...). These are tarballs, usually
+/// gzip-compressed, with one `name-version/desc` entry per package.
+pub fn read_sync_db(path: &Path) -> io::Result<Vec<DbPackage>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 4];
+    let read = file.read(&mut magic)?;
+    drop(file);
+
+    let reader = BufReader::new(File::open(path)?);
+    if read >= 2 && magic[..2] == GZIP_MAGIC {
+        read_db_archive(GzDecoder::new(reader))
+    } else if read == 4 && magic == ZSTD_MAGIC {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "zstd-compressed sync databases are not supported",
+        ))
+    } else {
+        read_db_archive(reader)
+    }
+}
+
+fn read_db_archive<R: Read>(reader: R) -> io::Result<Vec<DbPackage>> {
+    // Older databases split each entry into `desc` and `depends`; parse them together.
+    let mut entries: BTreeMap<String, String> = BTreeMap::new();
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let Some((dir, file)) = path.rsplit_once('/') else {
+            continue;
+        };
+        if file != "desc" && file != "depends" {
+            continue;
+        }
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+        let text = entries.entry(dir.to_string()).or_default();
+        text.push_str(&content);
+        text.push('\n');
+    }
+
+    Ok(entries
+        .values()
+        .filter_map(|content| pacman_db::parse_desc(content))
+        .collect())
+}
+
+/// Repository names in the order pacman.conf lists them (`[options]` excluded).
+pub fn repositories_from_conf(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            line.strip_prefix('[')?.strip_suffix(']').map(str::trim)
+        })
+        .filter(|name| !name.is_empty() && *name != "options")
+        .map(str::to_string)
+        .collect()
+}
+
+/// Load the `*.db` files in `dir`. With the repositories from pacman.conf, only
+/// those are loaded, in that order, so databases left behind by removed repos
+/// are ignored; without them every database is loaded alphabetically.
+/// Unreadable databases are skipped.
+pub fn load_index(dir: &Path, configured: Option<&[String]>) -> io::Result<SyncIndex> {
+    let mut repositories: Vec<(String, PathBuf)> = db_files(dir)?
+        .into_iter()
+        .map(|(path, _)| {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (name, path)
+        })
+        .collect();
+    match configured {
+        Some(order) => {
+            let rank = |name: &String| order.iter().position(|r| r == name);
+            repositories.retain(|(name, _)| rank(name).is_some());
+            repositories.sort_by_key(|(name, _)| rank(name));
+        }
+        None => repositories.sort(),
+    }
+
+    let mut packages = Vec::new();
+    for (repository, path) in repositories {
+        match read_sync_db(&path) {
+            Ok(entries) => packages.extend(entries.into_iter().map(|package| SyncPackage {
+                repository: repository.clone(),
+                package,
+            })),
+            Err(e) => crate::logger::log_warning(&format!(
+                "Skipping sync database {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+    Ok(SyncIndex::new(packages))
+}
+
+fn db_files(dir: &Path) -> io::Result<Vec<(PathBuf, Option<SystemTime>)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "db") && path.is_file() {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            files.push((path, modified));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+struct CachedIndex {
+    dir: PathBuf,
+    pacman_conf: PathBuf,
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+    index: Arc<SyncIndex>,
+}
+
+static INDEX_CACHE: OnceLock<Mutex<Option<CachedIndex>>> = OnceLock::new();
+
+/// The index for `dir`, limited to the repositories `pacman_conf` enables (all
+/// of them if it can't be read), rebuilt only when a database file was added,
+/// removed or refreshed (`pacman -Sy`) since the last call.
+pub fn cached_index(dir: &Path, pacman_conf: &Path) -> io::Result<Arc<SyncIndex>> {
+    let stamps = db_files(dir)?;
+    let cache = INDEX_CACHE.get_or_init(|| Mutex::new(None));
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(cached) = cache.as_ref()
+        && cached.dir == dir
+        && cached.pacman_conf == pacman_conf
+        && cached.stamps == stamps
+    {
+        return Ok(cached.index.clone());
+    }
+
+    let configured = fs::read_to_string(pacman_conf)
+        .ok()
+        .map(|conf| repositories_from_conf(&conf));
+    let index = Arc::new(load_index(dir, configured.as_deref())?);
+    *cache = Some(CachedIndex {
+        dir: dir.to_path_buf(),
+        pacman_conf: pacman_conf.to_path_buf(),
+        stamps,
+        index: index.clone(),
+    });
+    Ok(index)
+}
+
+#[cfg(test)]
+pub mod testing {
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::fs::File;
+    use std::path::Path;
+
+    /// Write a gzip-compressed sync database containing the given `desc` files.
+    pub fn write_sync_db(path: &Path, entries: &[(&str, &str)]) {
+        let file = File::create(path).expect("create sync db");
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::fast()));
+        for (dir, desc) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(desc.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("{}/desc", dir), desc.as_bytes())
+                .expect("append desc");
+        }
+        builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .expect("finish sync db");
+    }
+
+    /// A fresh, empty directory under the system temp dir.
+    pub fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("parut-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{temp_dir, write_sync_db};
+    use super::{load_index, read_sync_db, repositories_from_conf};
+
+    const RIPGREP: &str = "%FILENAME%\nripgrep-14.1.1-1-x86_64.pkg.tar.zst\n\n%NAME%\nripgrep\n\n%VERSION%\n14.1.1-1\n\n%DESC%\nA search tool that combines the usability of ag with the raw speed of grep\n\n%CSIZE%\n1632213\n\n%ISIZE%\n5264587\n";
+    const GREP: &str = "%NAME%\ngrep\n\n%VERSION%\n3.11-1\n\n%DESC%\nA string search utility\n\n%ISIZE%\n1102546\n";
+    const BASH: &str = "%NAME%\nbash\n\n%VERSION%\n5.2.037-1\n\n%DESC%\nThe GNU Bourne Again shell\n\n%PROVIDES%\nsh\n";
+
+    #[test]
+    fn reads_gzip_sync_database() {
+        let dir = temp_dir("sync-read");
+        let path = dir.join("extra.db");
+        write_sync_db(&path, &[("ripgrep-14.1.1-1", RIPGREP)]);
+
+        let packages = read_sync_db(&path).expect("read sync db");
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name, "ripgrep");
+        assert_eq!(packages[0].filename, "ripgrep-14.1.1-1-x86_64.pkg.tar.zst");
+        assert_eq!(packages[0].size, 5_264_587);
+        assert_eq!(packages[0].download_size, 1_632_213);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn searches_all_terms_in_repository_order() {
+        let dir = temp_dir("sync-search");
+        write_sync_db(&dir.join("extra.db"), &[("ripgrep-14.1.1-1", RIPGREP)]);
+        write_sync_db(
+            &dir.join("core.db"),
+            &[("grep-3.11-1", GREP), ("bash-5.2.037-1", BASH)],
+        );
+
+        let order = repositories_from_conf(
+            "[options]\nHoldPkg = pacman\n\n[core]\nInclude = x\n#[testing]\n[extra]\n",
+        );
+        assert_eq!(order, vec!["core", "extra"]);
+
+        let index = load_index(&dir, Some(&order)).expect("load index");
+        assert_eq!(index.len(), 3);
+
+        let names: Vec<_> = index
+            .search("GREP")
+            .iter()
+            .map(|entry| format!("{}/{}", entry.repository, entry.package.name))
+            .collect();
+        assert_eq!(names, vec!["core/grep", "extra/ripgrep"]);
+
+        let speed: Vec<_> = index
+            .search("grep speed")
+            .iter()
+            .map(|e| e.package.name.as_str())
+            .collect();
+        assert_eq!(speed, vec!["ripgrep"]);
+        assert_eq!(index.search("sh")[0].package.name, "bash");
+        assert!(index.search("   ").is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn loads_only_configured_repositories() {
+        let dir = temp_dir("sync-configured");
+        write_sync_db(&dir.join("extra.db"), &[("ripgrep-14.1.1-1", RIPGREP)]);
+        write_sync_db(&dir.join("community.db"), &[("grep-3.11-1", GREP)]);
+
+        let configured = vec!["core".to_string(), "extra".to_string()];
+        let index = load_index(&dir, Some(&configured)).expect("load index");
+        assert_eq!(index.len(), 1);
+        assert!(!index.contains("grep"));
+
+        let index = load_index(&dir, None).expect("load index");
+        assert_eq!(index.repository_of("grep"), Some("community"));
+        assert_eq!(index.repository_of("ripgrep"), Some("extra"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::logger::log_warning;
+use crate::task_queue::{Task, TaskStatus};
+use crate::task_type::TaskType;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, LineWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// A finished task as kept in the history, next to its compressed output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedTask {
+    pub id: usize,
+    pub task_type: TaskType,
+    pub package_name: String,
+    pub status: TaskStatus,
+    pub started_at_unix: Option<u64>,
+    pub finished_at_unix: Option<u64>,
+    /// Number of lines in the archived output.
+    pub lines: usize,
+    /// The `.log.gz` file next to this entry's `.json`.
+    #[serde(skip)]
+    pub log_path: PathBuf,
+}
+
+/// Which archived tasks to show; empty fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Part of the package name, case-insensitive.
+    pub package: String,
+    pub task_type: Option<TaskType>,
+    /// A [`TaskStatus::label`].
+    pub status: Option<&'static str>,
+    /// Only tasks finished at or after this Unix time.
+    pub since: Option<u64>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &ArchivedTask) -> bool {
+        let package = self.package.trim().to_lowercase();
+        (package.is_empty() || entry.package_name.to_lowercase().contains(&package))
+            && self
+                .task_type
+                .as_ref()
+                .is_none_or(|t| *t == entry.task_type)
+            && self.status.is_none_or(|s| s == entry.status.label())
+            && self
+                .since
+                .is_none_or(|since| entry.finished_at_unix.is_some_and(|at| at >= since))
+    }
+}
+
+/// `$XDG_DATA_HOME/parut/tasks`, where finished tasks are archived.
+pub fn history_dir() -> PathBuf {
+    let mut path = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("parut");
+    path.push("tasks");
+    path
+}
+
+/// Keeps the complete output of running tasks on disk, in `<dir>/<id>.log`, and
+/// archives it once they finish as `<finished>-<id>.log.gz` plus the task's
+/// metadata in `<finished>-<id>.json`.
+pub struct OutputSpool {
+    dir: PathBuf,
+    writers: Mutex<HashMap<usize, LineWriter<File>>>,
+}
+
+impl OutputSpool {
+    pub fn new(dir: PathBuf) -> Self {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            log_warning(&format!(
+                "Failed to create task history directory {}: {}",
+                dir.display(),
+                e
+            ));
+        }
+        Self {
+            dir,
+            writers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn spool_path(&self, task_id: usize) -> PathBuf {
+        self.dir.join(format!("{}.log", task_id))
+    }
+
+    pub fn append(&self, task_id: usize, line: &str) {
+        let mut writers = self.writers.lock().unwrap();
+        let writer = match writers.entry(task_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = self.spool_path(task_id);
+                match OpenOptions::new().create(true).append(true).open(&path) {
+                    Ok(file) => entry.insert(LineWriter::new(file)),
+                    Err(e) => {
+                        log_warning(&format!("Failed to open {}: {}", path.display(), e));
+                        return;
+                    }
+                }
+            }
+        };
+        let _ = writeln!(writer, "{}", line);
+    }
+
+    /// Compress `task`'s output into the archive and write its metadata. Tasks
+    /// that never printed anything here (e.g. canceled while queued) are
+    /// archived with the output they have.
+    pub fn finish(&self, task: &Task) -> io::Result<ArchivedTask> {
+        if let Some(mut writer) = self.writers.lock().unwrap().remove(&task.id) {
+            writer.flush()?;
+        }
+        let spool = self.spool_path(task.id);
+        let mut text = match fs::read_to_string(&spool) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        if text.is_empty() && !task.output.is_empty() {
+            text = task.output.join("\n");
+            text.push('\n');
+        }
+
+        let name = format!("{}-{}", task.finished_at_unix.unwrap_or_default(), task.id);
+        let log_path = self.dir.join(format!("{}.log.gz", name));
+        let mut encoder = GzEncoder::new(File::create(&log_path)?, Compression::default());
+        encoder.write_all(text.as_bytes())?;
+        encoder.finish()?;
+
+        let entry = ArchivedTask {
+            id: task.id,
+            task_type: task.task_type.clone(),
+            package_name: task.package_name.clone(),
+            status: task.status.clone(),
+            started_at_unix: task.started_at_unix,
+            finished_at_unix: task.finished_at_unix,
+            lines: text.lines().count(),
+            log_path,
+        };
+        let raw = serde_json::to_vec_pretty(&entry).map_err(io::Error::other)?;
+        fs::write(self.dir.join(format!("{}.json", name)), raw)?;
+        let _ = fs::remove_file(spool);
+        Ok(entry)
+    }
+}
+
+/// Every archived task in `dir`, most recently finished first.
+pub fn load_history(dir: &Path) -> Vec<ArchivedTask> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut history: Vec<ArchivedTask> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let raw = fs::read_to_string(&path).ok()?;
+            let mut entry: ArchivedTask = serde_json::from_str(&raw).ok()?;
+            entry.log_path = path.with_extension("log.gz");
+            Some(entry)
+        })
+        .collect();
+    history.sort_by(|a, b| {
+        b.finished_at_unix
+            .cmp(&a.finished_at_unix)
+            .then(b.id.cmp(&a.id))
+    });
+    history
+}
+
+/// The complete output of an archived task.
+pub fn read_log(entry: &ArchivedTask) -> io::Result<String> {
+    let mut text = String::new();
+    GzDecoder::new(File::open(&entry.log_path)?).read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Write the complete output of an archived task to `dest` as plain text.
+pub fn export_log(entry: &ArchivedTask, dest: &Path) -> io::Result<()> {
+    fs::write(dest, read_log(entry)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HistoryFilter, OutputSpool, export_log, load_history, read_log};
+    use crate::sync_db::testing::temp_dir;
+    use crate::task_queue::{Task, TaskStatus};
+    use crate::task_type::TaskType;
+
+    fn finished(id: usize, task_type: TaskType, name: &str, status: TaskStatus, at: u64) -> Task {
+        let mut task = Task::new(id, task_type, name.to_string());
+        task.status = status;
+        task.started_at_unix = Some(at - 60);
+        task.finished_at_unix = Some(at);
+        task
+    }
+
+    #[test]
+    fn archives_full_output_and_filters_history() {
+        let dir = temp_dir("task-history");
+        let spool = OutputSpool::new(dir.clone());
+        for n in 0..500 {
+            spool.append(1, &format!("line {}", n));
+        }
+        let install = finished(
+            1,
+            TaskType::Install,
+            "ripgrep",
+            TaskStatus::Completed,
+            2_000,
+        );
+        spool.finish(&install).expect("archive install");
+        assert!(!dir.join("1.log").exists());
+
+        let mut update = finished(
+            2,
+            TaskType::Update,
+            "system",
+            TaskStatus::Failed("paru exited with code 1".to_string()),
+            3_000,
+        );
+        update.output = vec!["error: failed to commit transaction".to_string()];
+        spool.finish(&update).expect("archive update");
+
+        let history = load_history(&dir);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].id, 2);
+        assert_eq!(history[1].lines, 500);
+        let log = read_log(&history[1]).expect("read log");
+        assert!(log.starts_with("line 0\n") && log.ends_with("line 499\n"));
+        assert_eq!(
+            read_log(&history[0]).unwrap(),
+            "error: failed to commit transaction\n"
+        );
+
+        let failed = HistoryFilter {
+            status: Some("Failed"),
+            ..HistoryFilter::default()
+        };
+        let grep = HistoryFilter {
+            package: "RIP".to_string(),
+            task_type: Some(TaskType::Install),
+            ..HistoryFilter::default()
+        };
+        let recent = HistoryFilter {
+            since: Some(2_500),
+            ..HistoryFilter::default()
+        };
+        let ids = |filter: &HistoryFilter| -> Vec<usize> {
+            history
+                .iter()
+                .filter(|e| filter.matches(e))
+                .map(|e| e.id)
+                .collect()
+        };
+        assert_eq!(ids(&failed), vec![2]);
+        assert_eq!(ids(&grep), vec![1]);
+        assert_eq!(ids(&recent), vec![2]);
+        assert_eq!(ids(&HistoryFilter::default()), vec![2, 1]);
+
+        let exported = dir.join("export.log");
+        export_log(&history[1], &exported).expect("export");
+        assert_eq!(std::fs::read_to_string(&exported).unwrap(), log);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
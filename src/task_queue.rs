@@ -1,27 +1,63 @@
-use std::collections::HashSet;
+use crate::db_lock::LockState;
+use crate::diagnosis;
+use crate::error::{BackendError, ErrorKind};
+use crate::logger::{log_info, log_warning};
+use crate::progress::ProgressDetails;
+use crate::prompts::{Prompt, PromptHandler, PromptKind};
+use crate::settings::Routine;
+use crate::task_history::OutputSpool;
+use crate::task_type::TaskType;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum TaskType {
-    Install,
-    Remove,
-    Update,
-    UpdatePackage,
-    CleanCache,
-    RemoveOrphans,
-}
+/// Output lines arrive many times a second; write them out at most this often.
+const OUTPUT_PERSIST_INTERVAL: Duration = Duration::from_secs(2);
+/// Upper bound on packages combined into one paru transaction.
+const MAX_BATCH_SIZE: usize = 25;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TaskStatus {
     Queued,
     Running,
     Completed,
     Canceled,
     Failed(String),
+    /// Was running when parut exited. Never restarted on its own; see
+    /// [`TaskQueue::requeue_interrupted_task`].
+    Interrupted,
 }
 
-#[derive(Debug, Clone)]
+impl TaskStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TaskStatus::Queued => "Queued",
+            TaskStatus::Running => "Running",
+            TaskStatus::Completed => "Completed",
+            TaskStatus::Canceled => "Canceled",
+            TaskStatus::Failed(_) => "Failed",
+            TaskStatus::Interrupted => "Interrupted",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Completed
+                | TaskStatus::Canceled
+                | TaskStatus::Failed(_)
+                | TaskStatus::Interrupted
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub id: usize,
     pub task_type: TaskType,
@@ -30,11 +66,91 @@ pub struct Task {
     pub output: Vec<String>,
     pub progress: Option<f64>, // 0.0 to 1.0
     pub phase: Option<String>,
+    /// Package, counters and download figures of the current phase.
+    #[serde(default)]
+    pub progress_details: ProgressDetails,
     pub started_at_unix: Option<u64>,
+    /// When the current phase began; `progress` only covers that phase.
+    #[serde(default)]
+    pub phase_started_at_unix: Option<u64>,
     pub finished_at_unix: Option<u64>,
+    /// Why the task failed, when `status` is `Failed`.
+    pub error: Option<BackendError>,
+    /// Tasks that must finish before this one is claimed.
+    #[serde(default)]
+    pub wait_for: Vec<usize>,
+    /// Package files produced by a `BuildAur` task.
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Name of the routine this task is a step of.
+    #[serde(default)]
+    pub routine: Option<String>,
+    /// For routine steps: let the next step run even if this one fails.
+    #[serde(default)]
+    pub continue_on_failure: bool,
+    /// Started by a schedule with nobody there to answer: paru gets `--noconfirm`
+    /// and no dialogs are shown.
+    #[serde(default)]
+    pub unattended: bool,
+}
+
+/// What [`TaskQueue::add_task`] did with a new task after comparing it with the
+/// queued and running ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddOutcome {
+    Added(usize),
+    /// The same work is already queued or running as `existing`; nothing was added.
+    Deduplicated {
+        existing: usize,
+        reason: String,
+    },
+    /// Not added: `existing` is running and the new task would undo it.
+    Rejected {
+        existing: usize,
+        reason: String,
+    },
+    /// Not added: the new task would undo the queued `existing`. Queue it with
+    /// [`TaskQueue::add_task_unchecked`] if the user confirms.
+    NeedsConfirmation {
+        existing: usize,
+        reason: String,
+    },
+}
+
+impl AddOutcome {
+    /// The task that will do the work: the new one, or the one it was merged into.
+    pub fn task_id(&self) -> Option<usize> {
+        match self {
+            AddOutcome::Added(id) | AddOutcome::Deduplicated { existing: id, .. } => Some(*id),
+            AddOutcome::Rejected { .. } | AddOutcome::NeedsConfirmation { .. } => None,
+        }
+    }
+
+    /// Why the task was not added.
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            AddOutcome::Added(_) => None,
+            AddOutcome::Deduplicated { reason, .. }
+            | AddOutcome::Rejected { reason, .. }
+            | AddOutcome::NeedsConfirmation { reason, .. } => Some(reason),
+        }
+    }
 }
 
 impl Task {
+    /// Whether running this task installs `package`.
+    fn installs(&self, package: &str) -> bool {
+        match self.task_type {
+            TaskType::Install | TaskType::BuildAur => self.package_name == package,
+            TaskType::InstallBuilt => self.package_name.split_whitespace().any(|p| p == package),
+            TaskType::InstallFile | TaskType::Downgrade => Path::new(&self.package_name)
+                .file_name()
+                .and_then(|file| crate::paru::ParuBackend::package_name_from_file(file.to_str()?))
+                .is_some_and(|name| name == package),
+            _ => false,
+        }
+    }
+
     pub fn new(id: usize, task_type: TaskType, package_name: String) -> Self {
         Self {
             id,
@@ -44,18 +160,115 @@ impl Task {
             output: Vec::new(),
             progress: None,
             phase: None,
+            progress_details: ProgressDetails::default(),
             started_at_unix: None,
+            phase_started_at_unix: None,
             finished_at_unix: None,
+            error: None,
+            wait_for: Vec::new(),
+            artifacts: Vec::new(),
+            routine: None,
+            continue_on_failure: false,
+            unattended: false,
         }
     }
 }
 
-#[allow(clippy::type_complexity)]
+/// A change to the queue, delivered to every [`TaskQueue::subscribe`]r.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskEvent {
+    TaskAdded(Box<Task>),
+    /// The task's status changed; its timestamps and error may have changed with it.
+    StatusChanged {
+        id: usize,
+        status: TaskStatus,
+    },
+    OutputAppended {
+        id: usize,
+        line: String,
+    },
+    ProgressChanged {
+        id: usize,
+        progress: Option<f64>,
+        phase: Option<String>,
+    },
+    Removed(usize),
+    /// Queued tasks were moved; re-read the order with `get_tasks`.
+    Reordered,
+    /// `add_task` turned a task down; `outcome` says why.
+    NotAdded {
+        task_type: TaskType,
+        package_name: String,
+        outcome: AddOutcome,
+    },
+    /// Dispatch was paused (`true`) or resumed; see [`TaskQueue::set_paused`].
+    PausedChanged(bool),
+    /// Someone else's pacman lock started or stopped holding up tasks.
+    DbLockChanged(LockState),
+}
+
+impl TaskEvent {
+    /// Frequent events that don't need the queue written out right away.
+    fn is_incremental(&self) -> bool {
+        matches!(
+            self,
+            TaskEvent::OutputAppended { .. }
+                | TaskEvent::ProgressChanged { .. }
+                | TaskEvent::NotAdded { .. }
+                | TaskEvent::DbLockChanged(_)
+        )
+    }
+}
+
+/// A question from a running task that is waiting for the user.
+#[derive(Debug, Clone)]
+pub struct PendingPrompt {
+    pub id: usize,
+    pub task_id: usize,
+    pub prompt: Prompt,
+}
+
+/// What `TaskQueue` writes to disk.
+#[derive(Serialize, Deserialize, Default)]
+struct QueueSnapshot {
+    next_id: usize,
+    tasks: Vec<Task>,
+    #[serde(default)]
+    paused: bool,
+}
+
+/// `$XDG_DATA_HOME/parut/tasks.json`, where the queue and finished tasks are kept.
+pub fn queue_file_path() -> PathBuf {
+    let mut path = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("parut");
+    let _ = fs::create_dir_all(&path);
+    path.push("tasks.json");
+    path
+}
+
+struct PromptSlot {
+    pending: PendingPrompt,
+    shown: bool,
+    answer_tx: mpsc::Sender<Option<String>>,
+}
+
 pub struct TaskQueue {
     tasks: Arc<Mutex<Vec<Task>>>,
     next_id: Arc<Mutex<usize>>,
-    update_callback: Arc<Mutex<Option<Box<dyn Fn() + Send>>>>,
+    subscribers: Mutex<Vec<mpsc::Sender<TaskEvent>>>,
     cancel_requested: Arc<Mutex<HashSet<usize>>>,
+    prompts: Arc<Mutex<Vec<PromptSlot>>>,
+    next_prompt_id: Arc<Mutex<usize>>,
+    /// Running tasks canceled by [`TaskQueue::cancel_overdue_tasks`], with their timeout.
+    timed_out: Mutex<HashMap<usize, u64>>,
+    paused: AtomicBool,
+    /// The pacman lock as last seen by the worker; tasks that need it wait
+    /// while it is anything but free.
+    db_lock: Mutex<LockState>,
+    /// Full output of every task, archived once it finishes.
+    spool: Option<OutputSpool>,
+    store_path: Option<PathBuf>,
+    last_persisted: Mutex<Option<Instant>>,
 }
 
 impl TaskQueue {
@@ -63,94 +276,531 @@ impl TaskQueue {
         Self {
             tasks: Arc::new(Mutex::new(Vec::new())),
             next_id: Arc::new(Mutex::new(0)),
-            update_callback: Arc::new(Mutex::new(None)),
+            subscribers: Mutex::new(Vec::new()),
             cancel_requested: Arc::new(Mutex::new(HashSet::new())),
+            prompts: Arc::new(Mutex::new(Vec::new())),
+            next_prompt_id: Arc::new(Mutex::new(0)),
+            timed_out: Mutex::new(HashMap::new()),
+            paused: AtomicBool::new(false),
+            db_lock: Mutex::new(LockState::Free),
+            spool: None,
+            store_path: None,
+            last_persisted: Mutex::new(None),
         }
     }
 
-    #[allow(dead_code)]
-    pub fn set_update_callback<F>(&self, callback: F)
-    where
-        F: Fn() + Send + 'static,
-    {
-        let mut cb = self.update_callback.lock().unwrap();
-        *cb = Some(Box::new(callback));
+    /// A queue saved to `path` on every change. Tasks from the previous session are
+    /// restored; those that were still running are marked `Interrupted`. The full
+    /// output of finished tasks is archived in the `tasks` directory next to `path`.
+    pub fn persistent(path: PathBuf) -> Self {
+        let snapshot = Self::load_snapshot(&path);
+        let queue = Self {
+            spool: path.parent().map(|dir| OutputSpool::new(dir.join("tasks"))),
+            store_path: Some(path),
+            ..Self::new()
+        };
+        let Some(mut snapshot) = snapshot else {
+            return queue;
+        };
+
+        let now = Self::now_unix();
+        let note = "Interrupted: parut exited while this task was running.";
+        for task in &mut snapshot.tasks {
+            if task.status == TaskStatus::Running {
+                task.status = TaskStatus::Interrupted;
+                task.phase = None;
+                task.finished_at_unix = now;
+                task.output.push(note.to_string());
+                if let Some(spool) = &queue.spool {
+                    spool.append(task.id, note);
+                    Self::archive(spool, task);
+                }
+            }
+        }
+        let next_id = snapshot
+            .tasks
+            .iter()
+            .map(|t| t.id + 1)
+            .max()
+            .unwrap_or(0)
+            .max(snapshot.next_id);
+        *queue.next_id.lock().unwrap() = next_id;
+        *queue.tasks.lock().unwrap() = snapshot.tasks;
+        queue.paused.store(snapshot.paused, Ordering::SeqCst);
+        queue.persist();
+        queue
     }
 
-    pub fn add_task(&self, task_type: TaskType, package_name: String) -> usize {
-        let mut next_id = self.next_id.lock().unwrap();
-        let id = *next_id;
-        *next_id += 1;
+    fn load_snapshot(path: &Path) -> Option<QueueSnapshot> {
+        let raw = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&raw)
+            .inspect_err(|e| {
+                log_warning(&format!(
+                    "Ignoring unreadable task queue {}: {}",
+                    path.display(),
+                    e
+                ))
+            })
+            .ok()
+    }
 
-        let task = Task::new(id, task_type, package_name);
+    /// Write the queue to disk. The file is replaced atomically, so a crash
+    /// mid-write leaves the previous snapshot intact.
+    fn persist(&self) {
+        let Some(path) = &self.store_path else {
+            return;
+        };
+        let snapshot = QueueSnapshot {
+            next_id: *self.next_id.lock().unwrap(),
+            tasks: self.get_tasks(),
+            paused: self.is_paused(),
+        };
+        *self.last_persisted.lock().unwrap() = Some(Instant::now());
 
-        let mut tasks = self.tasks.lock().unwrap();
-        tasks.push(task);
+        let tmp = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&snapshot)
+            .map_err(std::io::Error::other)
+            .and_then(|raw| fs::write(&tmp, raw))
+            .and_then(|_| fs::rename(&tmp, path));
+        if let Err(e) = result {
+            log_warning(&format!(
+                "Failed to save task queue to {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    }
+
+    fn persist_throttled(&self) {
+        let due = self
+            .last_persisted
+            .lock()
+            .unwrap()
+            .is_none_or(|at| at.elapsed() >= OUTPUT_PERSIST_INTERVAL);
+        if due {
+            self.persist();
+        }
+    }
+
+    fn now_unix() -> Option<u64> {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs())
+    }
+
+    fn archive(spool: &OutputSpool, task: &Task) {
+        if let Err(e) = spool.finish(task) {
+            log_warning(&format!(
+                "Failed to archive the output of task {}: {}",
+                task.id, e
+            ));
+        }
+    }
+
+    /// Receive every change to the queue from now on. Dropping the receiver
+    /// unsubscribes.
+    pub fn subscribe(&self) -> mpsc::Receiver<TaskEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Save the queue and tell subscribers what changed. Call with no locks held.
+    fn emit(&self, events: Vec<TaskEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        if events.iter().all(TaskEvent::is_incremental) {
+            self.persist_throttled();
+        } else {
+            self.persist();
+        }
+        if let Some(spool) = &self.spool {
+            for event in &events {
+                match event {
+                    TaskEvent::OutputAppended { id, line } => spool.append(*id, line),
+                    TaskEvent::StatusChanged { id, status } if status.is_finished() => {
+                        if let Some(task) = self.get_task(*id) {
+                            Self::archive(spool, &task);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut subscribers = self.subscribers.lock().unwrap();
+        for event in events {
+            subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+        }
+    }
+
+    pub fn get_task(&self, task_id: usize) -> Option<Task> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|t| t.id == task_id)
+            .cloned()
+    }
 
-        // Trigger UI update
-        if let Some(callback) = self.update_callback.lock().unwrap().as_ref() {
-            callback();
+    /// Queue a task unless it repeats or undoes one that is queued or running
+    /// (see [`TaskQueue::check_new_task`]). Tasks that are turned down are also
+    /// reported to subscribers as [`TaskEvent::NotAdded`].
+    pub fn add_task(&self, task_type: TaskType, package_name: String) -> AddOutcome {
+        if let Some(outcome) = self.refuse(&task_type, &package_name) {
+            return outcome;
         }
+        AddOutcome::Added(self.add_task_unchecked(task_type, package_name))
+    }
+
+    /// Queue a task without comparing it with the others, e.g. once the user
+    /// confirmed it.
+    pub fn add_task_unchecked(&self, task_type: TaskType, package_name: String) -> usize {
+        self.add_task_after(task_type, package_name, Vec::new())
+    }
 
+    /// How a new task would clash with the queued and running ones, if it does:
+    /// - the same task, an install of a package already being installed, or a
+    ///   package update while a full system update is pending, is deduplicated;
+    /// - a task undoing a running one (installing a package being removed, or
+    ///   the reverse) is rejected;
+    /// - a task undoing a queued one needs the user's confirmation.
+    pub fn check_new_task(&self, task_type: &TaskType, package_name: &str) -> Option<AddOutcome> {
+        let update_covers_all = crate::settings::get().default_update_scope == "all";
+        let tasks = self.tasks.lock().unwrap();
+        tasks
+            .iter()
+            .filter(|t| matches!(t.status, TaskStatus::Queued | TaskStatus::Running))
+            .find_map(|task| {
+                let running = task.status == TaskStatus::Running;
+                let existing = format!(
+                    "{} {} (task #{}) is already {}",
+                    task.task_type.label(),
+                    task.package_name,
+                    task.id,
+                    if running { "running" } else { "queued" }
+                );
+                let duplicate = (task.task_type == *task_type && task.package_name == package_name)
+                    || (*task_type == TaskType::Install && task.installs(package_name));
+                if duplicate {
+                    return Some(AddOutcome::Deduplicated {
+                        existing: task.id,
+                        reason: format!("{}.", existing),
+                    });
+                }
+                if *task_type == TaskType::UpdatePackage
+                    && task.task_type == TaskType::Update
+                    && update_covers_all
+                {
+                    return Some(AddOutcome::Deduplicated {
+                        existing: task.id,
+                        reason: format!("{} and updates {} too.", existing, package_name),
+                    });
+                }
+
+                let opposes = match task_type {
+                    TaskType::Install | TaskType::UpdatePackage => {
+                        task.task_type == TaskType::Remove && task.package_name == package_name
+                    }
+                    TaskType::Remove => {
+                        task.installs(package_name)
+                            || (task.task_type == TaskType::UpdatePackage
+                                && task.package_name == package_name)
+                    }
+                    _ => false,
+                };
+                if !opposes {
+                    return None;
+                }
+                Some(if running {
+                    AddOutcome::Rejected {
+                        existing: task.id,
+                        reason: format!("{}. Try again once it has finished.", existing),
+                    }
+                } else {
+                    AddOutcome::NeedsConfirmation {
+                        existing: task.id,
+                        reason: format!(
+                            "{}, and {} {} would undo it.",
+                            existing,
+                            task_type.label(),
+                            package_name
+                        ),
+                    }
+                })
+            })
+    }
+
+    /// [`TaskQueue::check_new_task`], reporting a clash to subscribers.
+    fn refuse(&self, task_type: &TaskType, package_name: &str) -> Option<AddOutcome> {
+        let outcome = self.check_new_task(task_type, package_name)?;
+        log_info(&format!(
+            "Not queueing {} {}: {}",
+            task_type.label(),
+            package_name,
+            outcome.reason().unwrap_or_default()
+        ));
+        self.emit(vec![TaskEvent::NotAdded {
+            task_type: task_type.clone(),
+            package_name: package_name.to_string(),
+            outcome: outcome.clone(),
+        }]);
+        Some(outcome)
+    }
+
+    /// Like `add_task`, but the task stays queued until every task in
+    /// `wait_for` has finished (or was cleared).
+    fn add_task_after(
+        &self,
+        task_type: TaskType,
+        package_name: String,
+        wait_for: Vec<usize>,
+    ) -> usize {
+        let mut task = Task::new(0, task_type, package_name);
+        task.wait_for = wait_for;
+        self.push_task(task)
+    }
+
+    fn push_task(&self, mut task: Task) -> usize {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+
+        task.id = id;
+        self.tasks.lock().unwrap().push(task.clone());
+
+        self.emit(vec![TaskEvent::TaskAdded(Box::new(task))]);
         id
     }
 
+    /// Queue AUR installs so the packages build side by side: one task installs
+    /// all build dependencies, then one `BuildAur` task per package runs, and
+    /// a final task installs whatever was built in a single transaction.
+    /// Without `parallel_aur_builds`, with `max_parallel_tasks` at 1 (the builds
+    /// could not overlap) or for a single package, these are plain installs.
+    /// Packages that clash with queued or running tasks are left out, as with `add_task`.
+    pub fn add_aur_installs(&self, names: Vec<String>) -> Vec<usize> {
+        let settings = crate::settings::get();
+        self.add_aur_installs_with(
+            names,
+            settings.parallel_aur_builds && settings.max_parallel_tasks >= 2,
+        )
+    }
+
+    /// [`Self::add_aur_installs`], building side by side only if `parallel`.
+    fn add_aur_installs_with(&self, names: Vec<String>, parallel: bool) -> Vec<usize> {
+        let names: Vec<String> = names
+            .into_iter()
+            .filter(|name| self.refuse(&TaskType::Install, name).is_none())
+            .collect();
+        if names.len() < 2 || !parallel {
+            return names
+                .into_iter()
+                .map(|name| self.add_task_unchecked(TaskType::Install, name))
+                .collect();
+        }
+
+        let joined = names.join(" ");
+        let deps = self.add_task_unchecked(TaskType::InstallBuildDeps, joined.clone());
+        let mut ids = vec![deps];
+        for name in names {
+            ids.push(self.add_task_after(TaskType::BuildAur, name, vec![deps]));
+        }
+        let builds = ids[1..].to_vec();
+        ids.push(self.add_task_after(TaskType::InstallBuilt, joined, builds));
+        ids
+    }
+
+    /// Mark the queued builds of `installed`, which the build dependencies task
+    /// `deps_id` installed already, as done, so they are not built again.
+    fn skip_installed_builds(&self, deps_id: usize, installed: &[String]) {
+        let skipped: Vec<usize> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|t| {
+                t.task_type == TaskType::BuildAur
+                    && t.status == TaskStatus::Queued
+                    && t.wait_for.contains(&deps_id)
+                    && installed.contains(&t.package_name)
+            })
+            .map(|t| t.id)
+            .collect();
+        for id in skipped {
+            self.append_output(
+                id,
+                "Installed with the build dependencies, as other packages need it to build"
+                    .to_string(),
+            );
+            self.update_task_status(id, TaskStatus::Completed);
+        }
+    }
+
+    /// Queue every step of `routine`, each waiting for the one before. When a step
+    /// fails without `continue_on_failure`, or is canceled, the steps after it are
+    /// skipped (marked canceled).
+    pub fn add_routine(&self, routine: &Routine) -> Vec<usize> {
+        let mut ids: Vec<usize> = Vec::new();
+        for step in &routine.steps {
+            let mut task = Task::new(0, step.task_type.clone(), step.package_name.clone());
+            task.wait_for = ids.last().copied().into_iter().collect();
+            task.routine = Some(routine.name.clone());
+            task.continue_on_failure = step.continue_on_failure;
+            ids.push(self.push_task(task));
+        }
+        ids
+    }
+
+    /// Cancel queued routine steps whose previous step did not let them run.
+    fn skip_blocked_routine_steps(&self) {
+        let mut events = Vec::new();
+        {
+            let mut tasks = self.tasks.lock().unwrap();
+            let now = Self::now_unix();
+            // Steps come after the step they wait for, so one pass also skips
+            // the rest of a chain.
+            for idx in 0..tasks.len() {
+                if tasks[idx].status != TaskStatus::Queued || tasks[idx].routine.is_none() {
+                    continue;
+                }
+                let blocker = tasks[idx].wait_for.iter().find_map(|id| {
+                    tasks.iter().find(|t| {
+                        t.id == *id
+                            && t.status.is_finished()
+                            && t.status != TaskStatus::Completed
+                            && !(matches!(t.status, TaskStatus::Failed(_)) && t.continue_on_failure)
+                    })
+                });
+                let Some(blocker) = blocker else {
+                    continue;
+                };
+                let note = format!(
+                    "Skipped: the previous step ({} {}) did not complete.",
+                    blocker.task_type.label(),
+                    blocker.package_name
+                );
+                let task = &mut tasks[idx];
+                task.status = TaskStatus::Canceled;
+                task.finished_at_unix = now;
+                task.output.push(note.clone());
+                events.push(TaskEvent::OutputAppended {
+                    id: task.id,
+                    line: note,
+                });
+                events.push(TaskEvent::StatusChanged {
+                    id: task.id,
+                    status: TaskStatus::Canceled,
+                });
+            }
+        }
+        self.emit(events);
+    }
+
+    /// Keep the package files a build produced, for the install step after it.
+    pub fn set_artifacts(&self, task_id: usize, artifacts: Vec<String>) {
+        {
+            let mut tasks = self.tasks.lock().unwrap();
+            if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
+                task.artifacts = artifacts;
+            }
+        }
+        self.persist();
+    }
+
     pub fn get_tasks(&self) -> Vec<Task> {
         self.tasks.lock().unwrap().clone()
     }
 
     pub fn update_task_status(&self, task_id: usize, status: TaskStatus) {
-        let mut tasks = self.tasks.lock().unwrap();
-        if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
+        {
+            let mut tasks = self.tasks.lock().unwrap();
+            let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) else {
+                return;
+            };
             if status == TaskStatus::Running {
-                task.started_at_unix = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .ok()
-                    .map(|d| d.as_secs());
+                task.started_at_unix = Self::now_unix();
+                task.phase_started_at_unix = task.started_at_unix;
                 task.phase = Some("Preparing".to_string());
                 task.finished_at_unix = None;
-            } else if matches!(
-                status,
-                TaskStatus::Completed | TaskStatus::Canceled | TaskStatus::Failed(_)
-            ) {
-                task.finished_at_unix = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .ok()
-                    .map(|d| d.as_secs());
+            } else if status.is_finished() {
+                task.finished_at_unix = Self::now_unix();
             }
-            task.status = status;
+            task.status = status.clone();
         }
 
-        // Trigger UI update
-        if let Some(callback) = self.update_callback.lock().unwrap().as_ref() {
-            callback();
+        self.emit(vec![TaskEvent::StatusChanged {
+            id: task_id,
+            status,
+        }]);
+    }
+
+    /// Mark a task as failed, keeping the typed error for the UI.
+    pub fn fail_task(&self, task_id: usize, error: BackendError) {
+        let message = error.to_string();
+        if let Some(task) = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|t| t.id == task_id)
+        {
+            task.error = Some(error);
         }
+        self.update_task_status(task_id, TaskStatus::Failed(message));
     }
 
     pub fn append_output(&self, task_id: usize, line: String) {
+        let mut events = Vec::new();
         let mut tasks = self.tasks.lock().unwrap();
         if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
             // Parse progress from common patterns
-            let progress = Self::parse_progress(&line);
-            if let Some(p) = progress {
-                task.progress = Some(p);
+            let phase = Self::parse_phase(&line).or_else(|| task.phase.clone());
+            let mut details_changed = false;
+            if phase != task.phase && task.progress_details != ProgressDetails::default() {
+                task.progress_details = ProgressDetails::default();
+                details_changed = true;
             }
-            if let Some(phase) = Self::parse_phase(&line) {
-                task.phase = Some(phase);
+            details_changed |= task.progress_details.update(&line);
+            // The last phase's progress says nothing about this one.
+            let previous = if phase == task.phase {
+                task.progress
+            } else {
+                None
+            };
+            let progress = task
+                .progress_details
+                .fraction()
+                .or_else(|| Self::parse_progress(&line))
+                .or(previous);
+            if phase != task.phase {
+                task.phase_started_at_unix = Self::now_unix();
+            }
+            if progress != task.progress || phase != task.phase || details_changed {
+                task.progress = progress;
+                task.phase = phase.clone();
+                events.push(TaskEvent::ProgressChanged {
+                    id: task_id,
+                    progress,
+                    phase,
+                });
             }
 
-            task.output.push(line);
+            task.output.push(line.clone());
             let limit = crate::settings::get().task_output_lines_limit.max(50);
             if task.output.len() > limit {
                 let drain = task.output.len() - limit;
                 task.output.drain(0..drain);
             }
+            events.insert(0, TaskEvent::OutputAppended { id: task_id, line });
         }
+        drop(tasks);
 
-        // Trigger UI update
-        if let Some(callback) = self.update_callback.lock().unwrap().as_ref() {
-            callback();
-        }
+        self.emit(events);
     }
 
     fn parse_progress(line: &str) -> Option<f64> {
@@ -211,40 +861,113 @@ impl TaskQueue {
     }
 
     pub fn clear_completed(&self) {
-        let mut tasks = self.tasks.lock().unwrap();
-        tasks.retain(|t| {
-            !matches!(
-                t.status,
-                TaskStatus::Completed | TaskStatus::Canceled | TaskStatus::Failed(_)
-            )
+        let mut removed = Vec::new();
+        self.tasks.lock().unwrap().retain(|t| {
+            if t.status.is_finished() {
+                removed.push(TaskEvent::Removed(t.id));
+            }
+            !t.status.is_finished()
         });
 
-        // Trigger UI update
-        if let Some(callback) = self.update_callback.lock().unwrap().as_ref() {
-            callback();
-        }
+        self.emit(removed);
     }
 
-    pub fn claim_next_queued_task(&self) -> Option<Task> {
+    /// Claim the next queued task that can start now, plus any queued tasks of the
+    /// same kind right behind it when it is an install or removal, and mark them
+    /// running. While a task holds the database lock, tasks that need it wait and
+    /// later tasks that don't may go first. The same goes for tasks still waiting
+    /// on others (`wait_for`).
+    pub fn claim_next_batch(&self, max_batch: usize) -> Vec<Task> {
+        if self.is_paused() {
+            return Vec::new();
+        }
+        self.skip_blocked_routine_steps();
         let mut tasks = self.tasks.lock().unwrap();
-        let task = tasks
-            .iter_mut()
-            .find(|t| t.status == TaskStatus::Queued)
-            .map(|task| {
-                task.started_at_unix = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .ok()
-                    .map(|d| d.as_secs());
+        let db_locked =
+            Self::holds_db_lock(&tasks) || *self.db_lock.lock().unwrap() != LockState::Free;
+        let Some(first) = tasks.iter().position(|t| {
+            t.status == TaskStatus::Queued
+                && !(db_locked && t.task_type.needs_db_lock())
+                && !Self::is_waiting(&tasks, t)
+        }) else {
+            return Vec::new();
+        };
+
+        let mut picked = vec![first];
+        let kind = tasks[first].task_type.clone();
+        if matches!(
+            kind,
+            TaskType::Install | TaskType::Remove | TaskType::InstallFile
+        ) {
+            for (idx, task) in tasks.iter().enumerate().skip(first + 1) {
+                if picked.len() >= max_batch {
+                    break;
+                }
+                if task.status != TaskStatus::Queued {
+                    continue;
+                }
+                if task.task_type != kind {
+                    break;
+                }
+                // Picked tasks are still queued, so this also keeps a task out of
+                // the batch of one it waits for.
+                if Self::is_waiting(&tasks, task) {
+                    continue;
+                }
+                picked.push(idx);
+            }
+        }
+
+        let now = Self::now_unix();
+        let claimed = picked
+            .into_iter()
+            .map(|idx| {
+                let task = &mut tasks[idx];
+                task.started_at_unix = now;
+                task.phase_started_at_unix = now;
                 task.phase = Some("Preparing".to_string());
                 task.finished_at_unix = None;
                 task.status = TaskStatus::Running;
                 task.clone()
-            });
+            })
+            .collect::<Vec<Task>>();
         drop(tasks);
-        if task.is_some() {
-            self.notify_update();
+        self.emit(
+            claimed
+                .iter()
+                .map(|task| TaskEvent::StatusChanged {
+                    id: task.id,
+                    status: TaskStatus::Running,
+                })
+                .collect(),
+        );
+        claimed
+    }
+
+    /// Return a claimed task to the queue, in place, noting why in its output.
+    fn requeue_claimed(&self, task_id: usize, note: String) {
+        {
+            let mut tasks = self.tasks.lock().unwrap();
+            if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
+                task.status = TaskStatus::Queued;
+                task.started_at_unix = None;
+                task.phase_started_at_unix = None;
+                task.phase = None;
+                task.progress = None;
+                task.progress_details = ProgressDetails::default();
+                task.output.push(note.clone());
+            }
         }
-        task
+        self.emit(vec![
+            TaskEvent::OutputAppended {
+                id: task_id,
+                line: note,
+            },
+            TaskEvent::StatusChanged {
+                id: task_id,
+                status: TaskStatus::Queued,
+            },
+        ]);
     }
 
     #[allow(dead_code)]
@@ -265,11 +988,11 @@ impl TaskQueue {
         let before = tasks.len();
         tasks.retain(|t| !(t.id == task_id && t.status == TaskStatus::Queued));
         let changed = tasks.len() != before;
+        drop(tasks);
 
-        if changed && let Some(callback) = self.update_callback.lock().unwrap().as_ref() {
-            callback();
+        if changed {
+            self.emit(vec![TaskEvent::Removed(task_id)]);
         }
-
         changed
     }
 
@@ -289,7 +1012,7 @@ impl TaskQueue {
         };
         tasks.swap(idx, prev_idx);
         drop(tasks);
-        self.notify_update();
+        self.emit(vec![TaskEvent::Reordered]);
         true
     }
 
@@ -308,7 +1031,7 @@ impl TaskQueue {
         };
         tasks.swap(idx, next_idx);
         drop(tasks);
-        self.notify_update();
+        self.emit(vec![TaskEvent::Reordered]);
         true
     }
 
@@ -332,7 +1055,7 @@ impl TaskQueue {
         let task = tasks.remove(idx);
         tasks.insert(first_queued_idx, task);
         drop(tasks);
-        self.notify_update();
+        self.emit(vec![TaskEvent::Reordered]);
         true
     }
 
@@ -360,9 +1083,208 @@ impl TaskQueue {
         self.cancel_requested.lock().unwrap().remove(&task_id)
     }
 
-    fn notify_update(&self) {
-        if let Some(callback) = self.update_callback.lock().unwrap().as_ref() {
-            callback();
+    /// Stop starting queued tasks, or start again. Tasks already running go on.
+    pub fn set_paused(&self, paused: bool) {
+        if self.paused.swap(paused, Ordering::SeqCst) != paused {
+            log_info(if paused {
+                "Task queue paused"
+            } else {
+                "Task queue resumed"
+            });
+            self.emit(vec![TaskEvent::PausedChanged(paused)]);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    fn holds_db_lock(tasks: &[Task]) -> bool {
+        tasks
+            .iter()
+            .any(|t| t.status == TaskStatus::Running && t.task_type.needs_db_lock())
+    }
+
+    /// Whether `task` still waits for tasks it was queued after.
+    fn is_waiting(tasks: &[Task], task: &Task) -> bool {
+        task.wait_for.iter().any(|id| {
+            tasks
+                .iter()
+                .any(|other| other.id == *id && !other.status.is_finished())
+        })
+    }
+
+    /// Whether a queued task could start now if nobody else held the pacman
+    /// lock, so the worker knows when to look at `db.lck`.
+    pub fn wants_db_lock(&self) -> bool {
+        if self.is_paused() {
+            return false;
+        }
+        let tasks = self.tasks.lock().unwrap();
+        !Self::holds_db_lock(&tasks)
+            && tasks.iter().any(|t| {
+                t.status == TaskStatus::Queued
+                    && t.task_type.needs_db_lock()
+                    && !Self::is_waiting(&tasks, t)
+            })
+    }
+
+    /// Record the state of the pacman lock. Tasks that need it are not started
+    /// until it is [`LockState::Free`].
+    pub fn set_db_lock(&self, state: LockState) {
+        let mut current = self.db_lock.lock().unwrap();
+        if *current == state {
+            return;
+        }
+        match &state {
+            LockState::Free => log_info("Package database lock released"),
+            LockState::Held { pid, program } => log_info(&format!(
+                "Waiting for {} (process {}) to release the package database lock",
+                program, pid
+            )),
+            LockState::Stale => {
+                log_warning("Package database is locked, but no package manager is running")
+            }
+            LockState::Unknown => log_info(
+                "Package database is locked and running processes could not be checked; waiting",
+            ),
+        }
+        *current = state.clone();
+        drop(current);
+        self.emit(vec![TaskEvent::DbLockChanged(state)]);
+    }
+
+    pub fn db_lock(&self) -> LockState {
+        self.db_lock.lock().unwrap().clone()
+    }
+
+    /// Request cancellation of running tasks that started longer ago than
+    /// `timeout_minutes` allows for their type. They are failed with
+    /// [`ErrorKind::TimedOut`] once they stop. Returns the newly canceled ids.
+    pub fn cancel_overdue_tasks(
+        &self,
+        timeout_minutes: impl Fn(&TaskType) -> Option<u64>,
+        now: u64,
+    ) -> Vec<usize> {
+        let overdue: Vec<(usize, u64)> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|t| t.status == TaskStatus::Running)
+            .filter_map(|t| {
+                let minutes = timeout_minutes(&t.task_type)?;
+                let started = t.started_at_unix?;
+                (now.saturating_sub(started) >= minutes * 60).then_some((t.id, minutes))
+            })
+            .collect();
+
+        let mut canceled = Vec::new();
+        for (id, minutes) in overdue {
+            if self.timed_out.lock().unwrap().insert(id, minutes).is_some() {
+                continue;
+            }
+            log_warning(&format!(
+                "Task {} timed out after {} minute(s)",
+                id, minutes
+            ));
+            self.append_output(id, format!("Timed out after {} minute(s).", minutes));
+            self.request_cancel(id);
+            canceled.push(id);
+        }
+        canceled
+    }
+
+    /// The timeout `task_id` was canceled for, if it was.
+    pub fn take_timeout(&self, task_id: usize) -> Option<u64> {
+        self.timed_out.lock().unwrap().remove(&task_id)
+    }
+
+    /// Block the calling task thread until the UI answers `prompt`.
+    /// Returns `None` if the prompt was dismissed or the task was canceled.
+    pub fn ask_prompt(&self, task_id: usize, prompt: Prompt) -> Option<String> {
+        let (answer_tx, answer_rx) = mpsc::channel();
+        let prompt_id = {
+            let mut next_id = self.next_prompt_id.lock().unwrap();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+
+        let (previous_phase, progress) = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks
+                .iter_mut()
+                .find(|t| t.id == task_id)
+                .map(|task| {
+                    let previous = task.phase.replace("Waiting for input".to_string());
+                    (previous, task.progress)
+                })
+                .unwrap_or_default()
+        };
+        self.prompts.lock().unwrap().push(PromptSlot {
+            pending: PendingPrompt {
+                id: prompt_id,
+                task_id,
+                prompt,
+            },
+            shown: false,
+            answer_tx,
+        });
+        self.emit(vec![TaskEvent::ProgressChanged {
+            id: task_id,
+            progress,
+            phase: Some("Waiting for input".to_string()),
+        }]);
+
+        let answer = loop {
+            match answer_rx.recv_timeout(std::time::Duration::from_millis(250)) {
+                Ok(answer) => break answer,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if self.is_cancel_requested(task_id) {
+                        self.prompts
+                            .lock()
+                            .unwrap()
+                            .retain(|slot| slot.pending.id != prompt_id);
+                        break None;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break None,
+            }
+        };
+
+        {
+            let mut tasks = self.tasks.lock().unwrap();
+            if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
+                task.phase = previous_phase.clone();
+            }
+        }
+        self.emit(vec![TaskEvent::ProgressChanged {
+            id: task_id,
+            progress,
+            phase: previous_phase,
+        }]);
+        answer
+    }
+
+    /// Prompts the UI has not presented yet; each prompt is returned once.
+    pub fn take_unshown_prompts(&self) -> Vec<PendingPrompt> {
+        let mut prompts = self.prompts.lock().unwrap();
+        prompts
+            .iter_mut()
+            .filter(|slot| !slot.shown)
+            .map(|slot| {
+                slot.shown = true;
+                slot.pending.clone()
+            })
+            .collect()
+    }
+
+    pub fn answer_prompt(&self, prompt_id: usize, answer: Option<String>) {
+        let mut prompts = self.prompts.lock().unwrap();
+        if let Some(idx) = prompts.iter().position(|slot| slot.pending.id == prompt_id) {
+            let slot = prompts.remove(idx);
+            let _ = slot.answer_tx.send(answer);
         }
     }
 
@@ -378,40 +1300,73 @@ impl TaskQueue {
             .unwrap_or(0)
             .saturating_sub(minutes.saturating_mul(60));
 
-        let mut tasks = self.tasks.lock().unwrap();
-        let before = tasks.len();
-        tasks.retain(|t| {
-            if !matches!(
-                t.status,
-                TaskStatus::Completed | TaskStatus::Canceled | TaskStatus::Failed(_)
-            ) {
-                return true;
-            }
-            t.finished_at_unix
-                .map(|done| done >= cutoff)
-                .unwrap_or(true)
+        let mut removed = Vec::new();
+        self.tasks.lock().unwrap().retain(|t| {
+            let keep = !t.status.is_finished()
+                || t.finished_at_unix
+                    .map(|done| done >= cutoff)
+                    .unwrap_or(true);
+            if !keep {
+                removed.push(TaskEvent::Removed(t.id));
+            }
+            keep
         });
-        let changed = before != tasks.len();
-        drop(tasks);
-        if changed {
-            self.notify_update();
-        }
+        self.emit(removed);
     }
 
     pub fn retry_failed_task(&self, task_id: usize) -> Option<usize> {
-        let (task_type, package_name) = {
+        self.requeue_copy(task_id, Vec::new(), |status| {
+            matches!(status, TaskStatus::Failed(_))
+        })
+    }
+
+    /// Like `retry_failed_task`, but the retry waits for `fix_id`, a task queued
+    /// to fix what made it fail, instead of sharing a transaction with it.
+    pub fn retry_failed_task_after(&self, task_id: usize, fix_id: usize) -> Option<usize> {
+        self.requeue_copy(task_id, vec![fix_id], |status| {
+            matches!(status, TaskStatus::Failed(_))
+        })
+    }
+
+    /// Queue a fresh copy of a task that was cut off when parut last exited.
+    pub fn requeue_interrupted_task(&self, task_id: usize) -> Option<usize> {
+        self.requeue_copy(task_id, Vec::new(), |status| {
+            *status == TaskStatus::Interrupted
+        })
+    }
+
+    fn requeue_copy(
+        &self,
+        task_id: usize,
+        after: Vec<usize>,
+        eligible: fn(&TaskStatus) -> bool,
+    ) -> Option<usize> {
+        let (task_type, package_name, wait_for) = {
             let tasks = self.tasks.lock().unwrap();
 
             tasks.iter().find(|t| t.id == task_id).and_then(|t| {
-                if matches!(t.status, TaskStatus::Failed(_)) {
-                    Some((t.task_type.clone(), t.package_name.clone()))
+                if eligible(&t.status) {
+                    Some((
+                        t.task_type.clone(),
+                        t.package_name.clone(),
+                        t.wait_for.clone(),
+                    ))
                 } else {
                     None
                 }
             })?
         };
 
-        Some(self.add_task(task_type, package_name))
+        Some(match task_type {
+            // The install step after a failed build has already run without it.
+            TaskType::BuildAur => self.add_task_after(TaskType::Install, package_name, after),
+            // Retrying the install reuses the packages its builds left behind.
+            TaskType::InstallBuilt => {
+                let wait_for = wait_for.into_iter().chain(after).collect();
+                self.add_task_after(task_type, package_name, wait_for)
+            }
+            _ => self.add_task_after(task_type, package_name, after),
+        })
     }
 }
 
@@ -426,93 +1381,356 @@ impl TaskWorker {
 
     pub fn start(&self) {
         let queue = self.queue.clone();
+        let running_jobs = Arc::new(AtomicUsize::new(0));
 
         thread::spawn(move || {
             loop {
                 queue.auto_clear_by_settings();
+                Self::queue_due_schedules(&queue);
 
-                let max_parallel = crate::settings::get().max_parallel_tasks.max(1);
-                if queue.running_count() >= max_parallel {
+                let settings = crate::settings::get();
+                if let Some(now) = TaskQueue::now_unix() {
+                    queue.cancel_overdue_tasks(|kind| settings.timeout_minutes(kind), now);
+                }
+                let max_parallel = settings.max_parallel_tasks.max(1);
+                // A batch is one paru process, so it takes a single slot.
+                if running_jobs.load(Ordering::SeqCst) >= max_parallel {
                     thread::sleep(std::time::Duration::from_millis(500));
                     continue;
                 }
+                let batch_limit = if settings.batch_package_tasks {
+                    MAX_BATCH_SIZE
+                } else {
+                    1
+                };
+
+                // Tasks that lock the database wait for anyone else's pacman.
+                queue.set_db_lock(if queue.wants_db_lock() {
+                    crate::db_lock::check()
+                } else {
+                    LockState::Free
+                });
 
-                // Atomically claim and mark one queued task as running.
+                // Atomically claim and mark queued tasks as running.
                 // This prevents duplicate dispatch of the same task when
                 // the scheduler loop spins quickly.
-                if let Some(task) = queue.claim_next_queued_task() {
-                    let queue_for_task = queue.clone();
-                    thread::spawn(move || {
-                        let result = Self::execute_task(&queue_for_task, &task);
-                        match result {
-                            Ok(_) => {
-                                queue_for_task.update_task_status(task.id, TaskStatus::Completed);
-                            }
-                            Err(e) => {
-                                if queue_for_task.take_cancel_request(task.id) {
-                                    queue_for_task
-                                        .update_task_status(task.id, TaskStatus::Canceled);
-                                } else {
-                                    queue_for_task
-                                        .update_task_status(task.id, TaskStatus::Failed(e));
-                                }
-                            }
-                        }
-                    });
-                } else {
+                let batch = queue.claim_next_batch(batch_limit);
+                if batch.is_empty() {
                     // No tasks, sleep a bit
                     thread::sleep(std::time::Duration::from_secs(1));
+                    continue;
                 }
+
+                let queue_for_task = queue.clone();
+                let jobs = running_jobs.clone();
+                jobs.fetch_add(1, Ordering::SeqCst);
+                thread::spawn(move || {
+                    let result = Self::execute_task(&queue_for_task, &batch);
+                    Self::finish_batch(&queue_for_task, &batch, result);
+                    jobs.fetch_sub(1, Ordering::SeqCst);
+                });
             }
         });
     }
 
-    fn execute_task(queue: &Arc<TaskQueue>, task: &Task) -> Result<(), String> {
+    fn queue_due_schedules(queue: &TaskQueue) {
+        let Some(now) = TaskQueue::now_unix() else {
+            return;
+        };
+        for schedule in crate::data_store::take_due_schedules(now as i64) {
+            // Nobody is there to answer a dialog; skip runs the queue already covers.
+            if let Some(outcome) = queue.check_new_task(&schedule.task_type, &schedule.package_name)
+            {
+                log_info(&format!(
+                    "Skipping scheduled task: {}",
+                    outcome.reason().unwrap_or_default()
+                ));
+                continue;
+            }
+            log_info(&format!(
+                "Queueing scheduled task: {} {}",
+                schedule.task_type.label(),
+                schedule.package_name
+            ));
+            let mut task = Task::new(0, schedule.task_type, schedule.package_name);
+            task.unattended = true;
+            let id = queue.push_task(task);
+            queue.append_output(
+                id,
+                format!("Started by schedule ({})", schedule.recurrence.label()),
+            );
+        }
+    }
+
+    fn finish_batch(queue: &TaskQueue, batch: &[Task], result: Result<(), BackendError>) {
+        let timeout = batch
+            .iter()
+            .filter_map(|task| queue.take_timeout(task.id))
+            .max();
+        let e = match result {
+            Ok(()) => {
+                for task in batch {
+                    queue.update_task_status(task.id, TaskStatus::Completed);
+                }
+                // A batch's run time says little about how long one task takes.
+                if let [task] = batch
+                    && let (Some(started), Some(now)) =
+                        (task.started_at_unix, TaskQueue::now_unix())
+                {
+                    crate::data_store::record_run_duration(
+                        &task.task_type,
+                        now.saturating_sub(started),
+                    );
+                }
+                return;
+            }
+            Err(e) => e,
+        };
+
+        // Take every request, so none is left behind for a task that gets re-queued.
+        let cancel_requests = batch
+            .iter()
+            .filter(|task| queue.take_cancel_request(task.id))
+            .count();
+        if let Some(minutes) = timeout {
+            for task in batch {
+                queue.fail_task(task.id, BackendError::timed_out(minutes));
+            }
+            return;
+        }
+        // A dismissed prompt cancels without a cancel request.
+        if cancel_requests > 0 || e.kind == ErrorKind::Canceled {
+            for task in batch {
+                queue.update_task_status(task.id, TaskStatus::Canceled);
+            }
+            return;
+        }
+
+        // pacman aborts the whole transaction when one target is bad. Fail just the
+        // rejected packages and give the others another go.
+        let output = queue
+            .get_tasks()
+            .into_iter()
+            .find(|t| t.id == batch[0].id)
+            .map(|t| t.output)
+            .unwrap_or_default();
+        let rejected = diagnosis::failed_targets(&output);
+        let (culprits, innocent): (Vec<&Task>, Vec<&Task>) = batch
+            .iter()
+            .partition(|task| rejected.contains(&task.package_name));
+        if culprits.is_empty() || innocent.is_empty() {
+            for task in batch {
+                queue.fail_task(task.id, e.clone());
+            }
+            return;
+        }
+
+        let names: Vec<&str> = culprits.iter().map(|t| t.package_name.as_str()).collect();
+        for task in culprits {
+            queue.fail_task(task.id, e.clone());
+        }
+        for task in innocent {
+            queue.requeue_claimed(
+                task.id,
+                format!(
+                    "Transaction aborted because of {}; queued again.",
+                    names.join(", ")
+                ),
+            );
+        }
+    }
+
+    /// Run `batch` (one task, or several adjacent installs or removals) as a
+    /// single paru transaction. Output goes to every task in the batch.
+    fn execute_task(queue: &Arc<TaskQueue>, batch: &[Task]) -> Result<(), BackendError> {
+        use crate::hooks;
         use crate::paru::ParuBackend;
-        use crate::settings;
+        use crate::settings::{self, HookStage};
         use crate::utils;
 
-        let task_id = task.id;
+        let task = &batch[0];
+        let ids: Vec<usize> = batch.iter().map(|t| t.id).collect();
+        let names: Vec<&str> = batch.iter().map(|t| t.package_name.as_str()).collect();
+        if batch.len() > 1 {
+            for id in &ids {
+                queue.append_output(
+                    *id,
+                    format!("Combined into one transaction: {}", names.join(" ")),
+                );
+            }
+        }
+
         let queue_clone = queue.clone();
         let queue_for_cancel = queue.clone();
+        let output_ids = ids.clone();
 
         let output_callback = move |line: String| {
-            queue_clone.append_output(task_id, line);
+            for id in &output_ids {
+                queue_clone.append_output(*id, line.clone());
+            }
         };
         let cancel_requested: std::sync::Arc<dyn Fn() -> bool + Send + Sync> =
-            std::sync::Arc::new(move || queue_for_cancel.is_cancel_requested(task_id));
+            std::sync::Arc::new(move || {
+                ids.iter()
+                    .any(|id| queue_for_cancel.is_cancel_requested(*id))
+            });
+        let queue_for_prompt = queue.clone();
+        let task_id = task.id;
+        let prompt_handler: PromptHandler = if task.unattended {
+            // Take paru's default where --noconfirm leaves a question, and give
+            // up rather than wait for a password nobody will type.
+            Arc::new(|prompt: Prompt| match prompt.kind {
+                PromptKind::Password => None,
+                _ => Some(prompt.default_answer()),
+            })
+        } else {
+            Arc::new(move |prompt| queue_for_prompt.ask_prompt(task_id, prompt))
+        };
 
-        match task.task_type {
-            TaskType::Install => ParuBackend::install_package(
-                &task.package_name,
+        let task_hooks = settings::get().task_hooks;
+        let hook_output = |line: String| {
+            for task in batch {
+                queue.append_output(task.id, line.clone());
+            }
+        };
+        let run_hooks = |stage| {
+            hooks::run_hooks(
+                &task_hooks,
+                stage,
+                &task.task_type,
+                &names,
+                &hook_output,
+                &*cancel_requested,
+            )
+        };
+        run_hooks(HookStage::Before)?;
+
+        let run = || match task.task_type {
+            TaskType::Install => ParuBackend::install_packages(
+                &names,
                 output_callback,
                 cancel_requested.clone(),
+                prompt_handler,
             ),
-            TaskType::Remove => ParuBackend::remove_package(
-                &task.package_name,
+            TaskType::Remove => ParuBackend::remove_packages(
+                &names,
                 output_callback,
                 cancel_requested.clone(),
+                prompt_handler,
+            ),
+            TaskType::Update => ParuBackend::update_system(
+                output_callback,
+                cancel_requested.clone(),
+                prompt_handler,
             ),
-            TaskType::Update => {
-                ParuBackend::update_system(output_callback, cancel_requested.clone())
-            }
             TaskType::UpdatePackage => ParuBackend::update_package(
                 &task.package_name,
                 output_callback,
                 cancel_requested.clone(),
+                prompt_handler,
             ),
             TaskType::CleanCache => {
-                ParuBackend::clean_cache(output_callback, cancel_requested.clone())
+                ParuBackend::clean_cache(output_callback, cancel_requested.clone(), prompt_handler)
             }
-            TaskType::RemoveOrphans => {
-                ParuBackend::remove_orphans(output_callback, cancel_requested.clone())
+            TaskType::RemoveOrphans => ParuBackend::remove_orphans(
+                output_callback,
+                cancel_requested.clone(),
+                prompt_handler,
+            ),
+            TaskType::InstallBuildDeps => {
+                let packages: Vec<&str> = task.package_name.split_whitespace().collect();
+                ParuBackend::install_build_dependencies(
+                    &packages,
+                    output_callback,
+                    cancel_requested.clone(),
+                    prompt_handler,
+                )
+                .map(|installed| queue.skip_installed_builds(task.id, &installed))
             }
+            TaskType::BuildAur => {
+                let tasks = queue.get_tasks();
+                let unmet = tasks
+                    .iter()
+                    .any(|t| task.wait_for.contains(&t.id) && t.status != TaskStatus::Completed);
+                if unmet {
+                    Err(BackendError::new(
+                        ErrorKind::Other,
+                        "Build dependencies were not installed",
+                    ))
+                } else {
+                    ParuBackend::build_aur_package(
+                        &task.package_name,
+                        output_callback,
+                        cancel_requested.clone(),
+                        prompt_handler,
+                    )
+                    .map(|files| queue.set_artifacts(task.id, files))
+                }
+            }
+            TaskType::InstallFile => ParuBackend::install_package_files(
+                &names,
+                output_callback,
+                cancel_requested.clone(),
+                prompt_handler,
+            ),
+            TaskType::Downgrade => ParuBackend::install_package_files(
+                &[task.package_name.as_str()],
+                output_callback,
+                cancel_requested.clone(),
+                prompt_handler,
+            ),
+            TaskType::BuildLocal => ParuBackend::build_local_package(
+                &task.package_name,
+                output_callback,
+                cancel_requested.clone(),
+                prompt_handler,
+            ),
+            TaskType::InstallBuilt => {
+                let builds: Vec<Task> = queue
+                    .get_tasks()
+                    .into_iter()
+                    .filter(|t| task.wait_for.contains(&t.id))
+                    .collect();
+                for build in builds.iter().filter(|t| t.status != TaskStatus::Completed) {
+                    output_callback(format!("Skipping {}: it was not built", build.package_name));
+                }
+                let files: Vec<&str> = builds
+                    .iter()
+                    .filter(|t| t.status == TaskStatus::Completed)
+                    .flat_map(|t| t.artifacts.iter().map(String::as_str))
+                    .collect();
+                if files.is_empty() && builds.iter().all(|t| t.status == TaskStatus::Completed) {
+                    output_callback(
+                        "Every package was installed with the build dependencies.".to_string(),
+                    );
+                    Ok(())
+                } else if files.is_empty() {
+                    Err(BackendError::new(
+                        ErrorKind::Other,
+                        "None of the packages were built",
+                    ))
+                } else {
+                    ParuBackend::install_package_files(
+                        &files,
+                        output_callback,
+                        cancel_requested.clone(),
+                        prompt_handler,
+                    )
+                }
+            }
+        };
+        if task.unattended {
+            ParuBackend::unattended(run)
+        } else {
+            run()
         }
+        .and_then(|()| run_hooks(HookStage::After))
         .inspect(|_| {
-            if settings::get().notify_on_task_complete {
+            // Each build finishing on its own is not worth a notification.
+            if settings::get().notify_on_task_complete && task.task_type != TaskType::BuildAur {
                 utils::send_notification(
                     "Parut Task Completed",
-                    &format!("{:?} {}", task.task_type, task.package_name),
+                    &format!("{:?} {}", task.task_type, names.join(", ")),
                 );
             }
         })
@@ -520,9 +1738,620 @@ impl TaskWorker {
             if settings::get().notify_on_task_failed {
                 utils::send_notification(
                     "Parut Task Failed",
-                    &format!("{:?} {}: {}", task.task_type, task.package_name, err),
+                    &format!("{:?} {}: {}", task.task_type, names.join(", "), err),
                 );
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AddOutcome, TaskEvent, TaskQueue, TaskStatus, TaskType, TaskWorker};
+    use crate::db_lock::LockState;
+    use crate::error::{BackendError, ErrorKind};
+    use crate::settings::{Routine, RoutineStep};
+    use crate::sync_db::testing::temp_dir;
+
+    #[test]
+    fn restores_queue_and_marks_running_tasks_interrupted() {
+        let dir = temp_dir("queue-restore");
+        let path = dir.join("tasks.json");
+
+        let queue = TaskQueue::persistent(path.clone());
+        let done = queue
+            .add_task(TaskType::Install, "ripgrep".to_string())
+            .task_id()
+            .unwrap();
+        let running = queue
+            .add_task(TaskType::Update, "system".to_string())
+            .task_id()
+            .unwrap();
+        let queued = queue
+            .add_task(TaskType::Remove, "foo".to_string())
+            .task_id()
+            .unwrap();
+        queue.append_output(done, "installing ripgrep...".to_string());
+        queue.update_task_status(done, TaskStatus::Completed);
+        queue.update_task_status(running, TaskStatus::Running);
+        drop(queue);
+
+        let restored = TaskQueue::persistent(path);
+        let tasks = restored.get_tasks();
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[0].status, TaskStatus::Completed);
+        assert_eq!(tasks[0].output, vec!["installing ripgrep..."]);
+        assert_eq!(tasks[1].status, TaskStatus::Interrupted);
+        assert!(tasks[1].finished_at_unix.is_some());
+        assert_eq!(tasks[2].status, TaskStatus::Queued);
+
+        // Interrupted tasks only run again when asked to, as a new task.
+        assert_eq!(restored.requeue_interrupted_task(done), None);
+        let requeued = restored
+            .requeue_interrupted_task(running)
+            .expect("requeue interrupted task");
+        assert!(requeued > queued);
+        let copy = restored
+            .get_tasks()
+            .into_iter()
+            .find(|t| t.id == requeued)
+            .unwrap();
+        assert_eq!(copy.task_type, TaskType::Update);
+        assert_eq!(copy.status, TaskStatus::Queued);
+
+        // Both finished tasks were archived with their full output.
+        let history = crate::task_history::load_history(&dir.join("tasks"));
+        let statuses: Vec<&str> = history.iter().map(|e| e.status.label()).collect();
+        assert_eq!(statuses.len(), 2);
+        assert!(statuses.contains(&"Completed") && statuses.contains(&"Interrupted"));
+        let install = history.iter().find(|e| e.id == done).unwrap();
+        assert_eq!(
+            crate::task_history::read_log(install).unwrap(),
+            "installing ripgrep...\n"
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn batches_adjacent_installs_and_removals() {
+        let queue = TaskQueue::new();
+        let a = queue
+            .add_task(TaskType::Install, "a".to_string())
+            .task_id()
+            .unwrap();
+        let b = queue
+            .add_task(TaskType::Install, "b".to_string())
+            .task_id()
+            .unwrap();
+        let c = queue
+            .add_task(TaskType::Remove, "c".to_string())
+            .task_id()
+            .unwrap();
+        let update = queue
+            .add_task(TaskType::Update, "system".to_string())
+            .task_id()
+            .unwrap();
+        let d = queue
+            .add_task(TaskType::Install, "d".to_string())
+            .task_id()
+            .unwrap();
+        let e = queue
+            .add_task(TaskType::Install, "e".to_string())
+            .task_id()
+            .unwrap();
+
+        // Each batch holds the database lock, so finish it before claiming the next.
+        let claim = |max_batch| {
+            let batch = queue.claim_next_batch(max_batch);
+            let ids: Vec<usize> = batch.iter().map(|t| t.id).collect();
+            TaskWorker::finish_batch(&queue, &batch, Ok(()));
+            ids
+        };
+        assert_eq!(claim(25), vec![a, b]);
+        assert_eq!(claim(25), vec![c]);
+        assert_eq!(claim(25), vec![update]);
+        assert_eq!(claim(1), vec![d]);
+        assert_eq!(claim(25), vec![e]);
+
+        // A task waiting for another is not batched with it.
+        let f = queue
+            .add_task(TaskType::Install, "f".to_string())
+            .task_id()
+            .unwrap();
+        let g = queue.add_task_after(TaskType::Install, "g".to_string(), vec![f]);
+        let h = queue
+            .add_task(TaskType::Install, "h".to_string())
+            .task_id()
+            .unwrap();
+        assert_eq!(claim(25), vec![f, h]);
+        assert_eq!(claim(25), vec![g]);
+        assert!(claim(25).is_empty());
+        assert!(
+            queue
+                .get_tasks()
+                .iter()
+                .all(|t| t.status == TaskStatus::Completed)
+        );
+    }
+
+    #[test]
+    fn batches_local_files_and_builds_directories_alone() {
+        let queue = TaskQueue::new();
+        let files: Vec<usize> = [
+            "/tmp/ripgrep-14.1.1-1-x86_64.pkg.tar.zst",
+            "/tmp/fd-10.2.0-1-x86_64.pkg.tar.zst",
+        ]
+        .iter()
+        .map(|f| queue.add_task(TaskType::InstallFile, f.to_string()))
+        .map(|outcome| outcome.task_id().unwrap())
+        .collect();
+        let build = queue
+            .add_task(TaskType::BuildLocal, "/home/me/src/foo".to_string())
+            .task_id()
+            .unwrap();
+
+        // Removing a package a queued file installs would undo it.
+        assert!(matches!(
+            queue.check_new_task(&TaskType::Remove, "ripgrep"),
+            Some(AddOutcome::NeedsConfirmation { .. })
+        ));
+
+        let batch = queue.claim_next_batch(25);
+        assert_eq!(batch.iter().map(|t| t.id).collect::<Vec<_>>(), files);
+        TaskWorker::finish_batch(&queue, &batch, Ok(()));
+        let batch = queue.claim_next_batch(25);
+        assert_eq!(batch.iter().map(|t| t.id).collect::<Vec<_>>(), vec![build]);
+        TaskWorker::finish_batch(&queue, &batch, Ok(()));
+
+        let downgrade = queue
+            .add_task(
+                TaskType::Downgrade,
+                "/var/cache/pacman/pkg/fd-10.1.0-1-x86_64.pkg.tar.zst".to_string(),
+            )
+            .task_id()
+            .unwrap();
+        queue.add_task(
+            TaskType::InstallFile,
+            "/tmp/bat-0.25.0-1-x86_64.pkg.tar.zst".to_string(),
+        );
+        assert!(matches!(
+            queue.check_new_task(&TaskType::Remove, "fd"),
+            Some(AddOutcome::NeedsConfirmation { .. })
+        ));
+        let batch = queue.claim_next_batch(25);
+        assert_eq!(
+            batch.iter().map(|t| t.id).collect::<Vec<_>>(),
+            vec![downgrade]
+        );
+    }
+
+    #[test]
+    fn failed_batch_blames_rejected_targets_only() {
+        let queue = TaskQueue::new();
+        queue.add_task(TaskType::Install, "ripgrep".to_string());
+        queue.add_task(TaskType::Install, "nosuchpkg".to_string());
+        queue.add_task(TaskType::Install, "fd".to_string());
+        let batch = queue.claim_next_batch(25);
+        assert_eq!(batch.len(), 3);
+        queue.append_output(
+            batch[0].id,
+            "error: target not found: nosuchpkg".to_string(),
+        );
+
+        let err = BackendError::new(ErrorKind::PackageNotFound, "paru exited with code 1");
+        TaskWorker::finish_batch(&queue, &batch, Err(err));
+
+        let statuses: Vec<TaskStatus> = queue.get_tasks().into_iter().map(|t| t.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                TaskStatus::Queued,
+                TaskStatus::Failed("paru exited with code 1".to_string()),
+                TaskStatus::Queued,
+            ]
+        );
+        let retried = queue.claim_next_batch(25);
+        assert_eq!(retried.len(), 2);
+        assert!(
+            retried[0]
+                .output
+                .last()
+                .unwrap()
+                .contains("because of nosuchpkg")
+        );
+
+        TaskWorker::finish_batch(&queue, &retried, Ok(()));
+        assert_eq!(queue.get_tasks()[2].status, TaskStatus::Completed);
+    }
+
+    #[test]
+    fn retry_after_a_fix_runs_in_its_own_batch() {
+        let queue = TaskQueue::new();
+        queue.add_task(TaskType::Install, "glibc".to_string());
+        let batch = queue.claim_next_batch(25);
+        let err = BackendError::new(ErrorKind::CommandFailed, "invalid signature");
+        TaskWorker::finish_batch(&queue, &batch, Err(err));
+
+        // "Update Keyring & Retry": the keyring must be installed before the retry.
+        let fix = queue
+            .add_task(TaskType::Install, "archlinux-keyring".to_string())
+            .task_id()
+            .unwrap();
+        let retry = queue.retry_failed_task_after(batch[0].id, fix).unwrap();
+
+        let batch = queue.claim_next_batch(25);
+        assert_eq!(batch.iter().map(|t| t.id).collect::<Vec<_>>(), vec![fix]);
+        TaskWorker::finish_batch(&queue, &batch, Ok(()));
+        let batch = queue.claim_next_batch(25);
+        assert_eq!(batch.iter().map(|t| t.id).collect::<Vec<_>>(), vec![retry]);
+    }
+
+    #[test]
+    fn runs_database_locking_tasks_one_at_a_time() {
+        let queue = TaskQueue::new();
+        let update = queue
+            .add_task(TaskType::Update, "system".to_string())
+            .task_id()
+            .unwrap();
+        let install = queue
+            .add_task(TaskType::Install, "ripgrep".to_string())
+            .task_id()
+            .unwrap();
+        let clean = queue
+            .add_task(TaskType::CleanCache, "cache".to_string())
+            .task_id()
+            .unwrap();
+
+        let first = queue.claim_next_batch(25);
+        assert_eq!(first[0].id, update);
+        // Free parallel slots don't matter while the lock is taken.
+        assert!(queue.claim_next_batch(25).is_empty());
+
+        TaskWorker::finish_batch(&queue, &first, Ok(()));
+        assert_eq!(queue.claim_next_batch(25)[0].id, install);
+        assert!(queue.claim_next_batch(25).is_empty());
+        queue.update_task_status(install, TaskStatus::Completed);
+        assert_eq!(queue.claim_next_batch(25)[0].id, clean);
+    }
+
+    #[test]
+    fn aur_builds_wait_for_dependencies_then_run_side_by_side() {
+        let queue = TaskQueue::new();
+        let ids = queue.add_aur_installs_with(vec!["foo".to_string(), "bar".to_string()], true);
+        let (deps, foo, bar, install) = (ids[0], ids[1], ids[2], ids[3]);
+        let repo = queue
+            .add_task(TaskType::Install, "ripgrep".to_string())
+            .task_id()
+            .unwrap();
+
+        let first = queue.claim_next_batch(25);
+        assert_eq!(first[0].id, deps);
+        assert_eq!(first[0].package_name, "foo bar");
+        // Builds wait for their dependencies; the unrelated install waits for the lock.
+        assert!(queue.claim_next_batch(25).is_empty());
+
+        TaskWorker::finish_batch(&queue, &first, Ok(()));
+        assert_eq!(queue.claim_next_batch(25)[0].id, foo);
+        assert_eq!(queue.claim_next_batch(25)[0].id, bar);
+        assert_eq!(queue.claim_next_batch(25)[0].id, repo);
+        queue.update_task_status(repo, TaskStatus::Completed);
+        assert!(queue.claim_next_batch(25).is_empty());
+
+        queue.set_artifacts(foo, vec!["/tmp/foo-1-1-any.pkg.tar.zst".to_string()]);
+        queue.update_task_status(foo, TaskStatus::Completed);
+        assert!(queue.claim_next_batch(25).is_empty());
+        queue.fail_task(
+            bar,
+            BackendError::new(ErrorKind::CommandFailed, "build failed"),
+        );
+        assert_eq!(queue.claim_next_batch(25)[0].id, install);
+
+        // A failed build is retried as a plain install.
+        let retried = queue.retry_failed_task(bar).expect("retry build");
+        let copy = queue
+            .get_tasks()
+            .into_iter()
+            .find(|t| t.id == retried)
+            .unwrap();
+        assert_eq!(copy.task_type, TaskType::Install);
+    }
+
+    #[test]
+    fn aur_installs_stay_plain_with_one_parallel_task() {
+        let queue = TaskQueue::new();
+        let ids = queue.add_aur_installs(vec!["foo".to_string(), "bar".to_string()]);
+        assert_eq!(ids.len(), 2);
+        assert!(
+            queue
+                .get_tasks()
+                .iter()
+                .all(|t| t.task_type == TaskType::Install)
+        );
+    }
+
+    #[test]
+    fn aur_builds_skip_packages_installed_with_dependencies() {
+        let queue = TaskQueue::new();
+        let ids = queue.add_aur_installs_with(vec!["foo".to_string(), "libfoo".to_string()], true);
+        let (deps, foo, libfoo, install) = (ids[0], ids[1], ids[2], ids[3]);
+
+        // foo builds against libfoo, so the dependency step installed libfoo.
+        let first = queue.claim_next_batch(25);
+        queue.skip_installed_builds(deps, &["libfoo".to_string()]);
+        TaskWorker::finish_batch(&queue, &first, Ok(()));
+
+        let status = |id| queue.get_task(id).unwrap().status;
+        assert_eq!(status(libfoo), TaskStatus::Completed);
+        assert_eq!(queue.claim_next_batch(25)[0].id, foo);
+        assert!(queue.claim_next_batch(25).is_empty());
+        queue.update_task_status(foo, TaskStatus::Completed);
+        assert_eq!(queue.claim_next_batch(25)[0].id, install);
+    }
+
+    #[test]
+    fn routine_steps_run_in_order_and_stop_on_failure() {
+        let routine = |continue_on_failure| Routine {
+            name: "Maintenance".to_string(),
+            steps: vec![
+                RoutineStep {
+                    task_type: TaskType::Update,
+                    package_name: "system".to_string(),
+                    continue_on_failure,
+                },
+                RoutineStep {
+                    task_type: TaskType::RemoveOrphans,
+                    package_name: "system".to_string(),
+                    continue_on_failure: true,
+                },
+                RoutineStep {
+                    task_type: TaskType::CleanCache,
+                    package_name: "system".to_string(),
+                    continue_on_failure: false,
+                },
+            ],
+        };
+        let failed = || BackendError::new(ErrorKind::Network, "failed to retrieve some files");
+
+        let queue = TaskQueue::new();
+        let strict = queue.add_routine(&routine(false));
+        let lenient = queue.add_routine(&routine(true));
+
+        let first = queue.claim_next_batch(25);
+        assert_eq!(first[0].id, strict[0]);
+        queue.fail_task(strict[0], failed());
+        // The rest of the strict routine is skipped; the lenient one starts.
+        assert_eq!(queue.claim_next_batch(25)[0].id, lenient[0]);
+        let tasks = queue.get_tasks();
+        assert_eq!(tasks[1].status, TaskStatus::Canceled);
+        assert_eq!(tasks[2].status, TaskStatus::Canceled);
+        assert!(tasks[2].output[0].starts_with("Skipped"));
+
+        queue.fail_task(lenient[0], failed());
+        assert_eq!(queue.claim_next_batch(25)[0].id, lenient[1]);
+        assert!(queue.claim_next_batch(25).is_empty());
+        queue.update_task_status(lenient[1], TaskStatus::Completed);
+        assert_eq!(queue.claim_next_batch(25)[0].id, lenient[2]);
+    }
+
+    #[test]
+    fn subscribers_receive_typed_events() {
+        let queue = TaskQueue::new();
+        let events = queue.subscribe();
+        let dropped = queue.subscribe();
+        drop(dropped);
+
+        let id = queue
+            .add_task(TaskType::Install, "ripgrep".to_string())
+            .task_id()
+            .unwrap();
+        queue.claim_next_batch(25);
+        queue.append_output(id, "installing ripgrep...".to_string());
+        queue.append_output(id, "(1/1) checking keys in keyring  100%".to_string());
+        queue.update_task_status(id, TaskStatus::Completed);
+        queue.clear_completed();
+
+        let received: Vec<TaskEvent> = events.try_iter().collect();
+        assert!(matches!(&received[0], TaskEvent::TaskAdded(task) if task.id == id));
+        assert_eq!(
+            received[1..],
+            [
+                TaskEvent::StatusChanged {
+                    id,
+                    status: TaskStatus::Running
+                },
+                TaskEvent::OutputAppended {
+                    id,
+                    line: "installing ripgrep...".to_string()
+                },
+                TaskEvent::ProgressChanged {
+                    id,
+                    progress: None,
+                    phase: Some("Installing".to_string())
+                },
+                TaskEvent::OutputAppended {
+                    id,
+                    line: "(1/1) checking keys in keyring  100%".to_string()
+                },
+                TaskEvent::ProgressChanged {
+                    id,
+                    progress: Some(1.0),
+                    phase: Some("Checking keys".to_string())
+                },
+                TaskEvent::StatusChanged {
+                    id,
+                    status: TaskStatus::Completed
+                },
+                TaskEvent::Removed(id),
+            ]
+        );
+        assert_eq!(queue.subscribers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn progress_starts_over_with_each_phase() {
+        let queue = TaskQueue::new();
+        queue.add_task(TaskType::Install, "ripgrep".to_string());
+        let task = queue.claim_next_batch(1).remove(0);
+        assert_eq!(task.phase_started_at_unix, task.started_at_unix);
+
+        queue.append_output(task.id, ":: Retrieving packages...".to_string());
+        queue.append_output(
+            task.id,
+            " ripgrep-14.1.1-1-x86_64  1024.0 KiB  512.0 KiB/s 00:02 [#####-----]  50%".to_string(),
+        );
+        let downloading = queue.get_task(task.id).unwrap();
+        assert_eq!(downloading.phase.as_deref(), Some("Downloading"));
+        assert_eq!(downloading.progress, Some(0.5));
+
+        queue.append_output(task.id, "loading package files...".to_string());
+        let loading = queue.get_task(task.id).unwrap();
+        assert_eq!(loading.phase.as_deref(), Some("Loading package files"));
+        assert_eq!(loading.progress, None);
+        assert!(loading.phase_started_at_unix >= downloading.phase_started_at_unix);
+        assert!(loading.phase_started_at_unix.is_some());
+    }
+
+    #[test]
+    fn paused_queue_holds_tasks_and_overdue_tasks_fail() {
+        let dir = temp_dir("queue-pause");
+        let path = dir.join("tasks.json");
+        let queue = TaskQueue::persistent(path.clone());
+        let update = queue
+            .add_task(TaskType::Update, "system".to_string())
+            .task_id()
+            .unwrap();
+        let clean = queue
+            .add_task(TaskType::CleanCache, "system".to_string())
+            .task_id()
+            .unwrap();
+
+        queue.set_paused(true);
+        assert!(queue.claim_next_batch(25).is_empty());
+        assert!(TaskQueue::persistent(path.clone()).is_paused());
+        queue.set_paused(false);
+
+        let batch = queue.claim_next_batch(25);
+        let started = batch[0].started_at_unix.unwrap();
+        let timeouts = |kind: &TaskType| (*kind == TaskType::Update).then_some(30);
+        assert!(
+            queue
+                .cancel_overdue_tasks(timeouts, started + 29 * 60)
+                .is_empty()
+        );
+        assert_eq!(
+            queue.cancel_overdue_tasks(timeouts, started + 30 * 60),
+            vec![update]
+        );
+        assert!(
+            queue
+                .cancel_overdue_tasks(timeouts, started + 31 * 60)
+                .is_empty()
+        );
+        assert!(queue.is_cancel_requested(update));
+
+        TaskWorker::finish_batch(&queue, &batch, Err(BackendError::canceled()));
+        let task = queue.get_task(update).unwrap();
+        assert_eq!(
+            task.status,
+            TaskStatus::Failed("Timed out after 30 minute(s)".to_string())
+        );
+        assert_eq!(task.error.unwrap().kind, ErrorKind::TimedOut);
+        assert!(!queue.is_cancel_requested(update));
+        assert_eq!(queue.get_task(clean).unwrap().status, TaskStatus::Queued);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn foreign_db_lock_holds_only_tasks_that_need_it() {
+        let queue = TaskQueue::new();
+        let install = queue
+            .add_task(TaskType::Install, "ripgrep".to_string())
+            .task_id()
+            .unwrap();
+        let build = queue
+            .add_task(TaskType::BuildAur, "paru-bin".to_string())
+            .task_id()
+            .unwrap();
+        let events = queue.subscribe();
+        assert!(queue.wants_db_lock());
+
+        queue.set_db_lock(LockState::Stale);
+        queue.set_db_lock(LockState::Stale);
+        let changes: Vec<TaskEvent> = events.try_iter().collect();
+        assert_eq!(changes, vec![TaskEvent::DbLockChanged(LockState::Stale)]);
+        let batch = queue.claim_next_batch(25);
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].id, build);
+        assert!(queue.claim_next_batch(25).is_empty());
+
+        queue.set_db_lock(LockState::Free);
+        assert_eq!(queue.claim_next_batch(25)[0].id, install);
+        // Our own transaction holds the lock now; no need to look at it.
+        assert!(!queue.wants_db_lock());
+    }
+
+    #[test]
+    fn add_task_dedupes_rejects_or_asks_about_clashing_tasks() {
+        let queue = TaskQueue::new();
+        let events = queue.subscribe();
+        let update = queue
+            .add_task(TaskType::Update, "system".to_string())
+            .task_id()
+            .unwrap();
+        assert_eq!(
+            queue.add_task(TaskType::Update, "system".to_string()),
+            AddOutcome::Deduplicated {
+                existing: update,
+                reason: "Update system (task #0) is already queued.".to_string()
+            }
+        );
+        assert_eq!(
+            queue
+                .add_task(TaskType::UpdatePackage, "bar".to_string())
+                .task_id(),
+            Some(update)
+        );
+
+        let remove = queue
+            .add_task(TaskType::Remove, "foo".to_string())
+            .task_id()
+            .unwrap();
+        let outcome = queue.add_task(TaskType::Install, "foo".to_string());
+        assert!(
+            matches!(outcome, AddOutcome::NeedsConfirmation { existing, .. } if existing == remove)
+        );
+        assert_eq!(
+            outcome.reason(),
+            Some("Remove foo (task #1) is already queued, and Install foo would undo it.")
+        );
+        assert_eq!(queue.get_tasks().len(), 2);
+
+        // Builds of queued AUR installs count as installs.
+        let aur = queue.add_aur_installs_with(vec!["a".to_string(), "b".to_string()], true);
+        assert_eq!(aur.len(), 4);
+        assert_eq!(
+            queue.add_task(TaskType::Install, "b".to_string()).task_id(),
+            Some(aur[2])
+        );
+        assert_eq!(
+            queue.add_aur_installs_with(vec!["a".to_string(), "c".to_string()], true),
+            vec![aur[3] + 1]
+        );
+
+        let refused = events
+            .try_iter()
+            .filter(|event| matches!(event, TaskEvent::NotAdded { .. }))
+            .count();
+        assert_eq!(refused, 5);
+
+        let busy = TaskQueue::new();
+        busy.add_task(TaskType::Install, "foo".to_string());
+        busy.claim_next_batch(25);
+        assert!(matches!(
+            busy.add_task(TaskType::Remove, "foo".to_string()),
+            AddOutcome::Rejected { .. }
+        ));
+        busy.add_task_unchecked(TaskType::Remove, "foo".to_string());
+        assert_eq!(busy.get_tasks().len(), 2);
+    }
+}
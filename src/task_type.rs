@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TaskType {
+    Install,
+    Remove,
+    Update,
+    UpdatePackage,
+    CleanCache,
+    RemoveOrphans,
+    /// Install the build dependencies of the AUR packages in `package_name`
+    /// (space-separated) ahead of building them.
+    InstallBuildDeps,
+    /// Build one AUR package without installing it; see [`crate::task_queue::TaskQueue::add_aur_installs`].
+    BuildAur,
+    /// Install the packages built by the tasks this one waits for, in one transaction.
+    InstallBuilt,
+    /// Install the local package file at `package_name` (`-U`).
+    InstallFile,
+    /// Build the PKGBUILD in the local directory `package_name` and install the
+    /// result (`-Bi`).
+    BuildLocal,
+    /// Install an older version of a package from the cached file at `package_name`.
+    Downgrade,
+}
+
+impl TaskType {
+    pub const ALL: [TaskType; 12] = [
+        TaskType::Install,
+        TaskType::Remove,
+        TaskType::Update,
+        TaskType::UpdatePackage,
+        TaskType::CleanCache,
+        TaskType::RemoveOrphans,
+        TaskType::InstallBuildDeps,
+        TaskType::BuildAur,
+        TaskType::InstallBuilt,
+        TaskType::InstallFile,
+        TaskType::BuildLocal,
+        TaskType::Downgrade,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TaskType::Install => "Install",
+            TaskType::Remove => "Remove",
+            TaskType::Update => "Update",
+            TaskType::UpdatePackage => "Update Package",
+            TaskType::CleanCache => "Clean Cache",
+            TaskType::RemoveOrphans => "Remove Orphans",
+            TaskType::InstallBuildDeps => "Build Dependencies",
+            TaskType::BuildAur => "Build",
+            TaskType::InstallBuilt => "Install Built",
+            TaskType::InstallFile => "Install File",
+            TaskType::BuildLocal => "Build PKGBUILD",
+            TaskType::Downgrade => "Downgrade",
+        }
+    }
+
+    /// Whether the task runs a pacman transaction and so holds
+    /// `/var/lib/pacman/db.lck` while it runs. At most one such task runs at a time.
+    pub fn needs_db_lock(&self) -> bool {
+        match self {
+            // `-Sc` also opens a transaction to clean the sync database directory.
+            TaskType::Install
+            | TaskType::Remove
+            | TaskType::Update
+            | TaskType::UpdatePackage
+            | TaskType::CleanCache
+            | TaskType::RemoveOrphans
+            | TaskType::InstallBuildDeps
+            | TaskType::InstallBuilt
+            | TaskType::InstallFile
+            // paru installs the build dependencies and then the package.
+            | TaskType::BuildLocal
+            | TaskType::Downgrade => true,
+            // makepkg only touches the clone directory: everything the build
+            // needs, even other packages of the same run, was installed before.
+            TaskType::BuildAur => false,
+        }
+    }
+}
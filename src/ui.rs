@@ -1,6 +1,12 @@
-use crate::logger::{log_error, log_info};
+use crate::db_lock::LockState;
+use crate::diagnosis::Remedy;
+use crate::error::BackendError;
+use crate::logger::{log_error, log_info, log_warning};
 use crate::paru::{AurComment, NewsItem, Package, ParuBackend};
-use crate::task_queue::{TaskQueue, TaskStatus, TaskType, TaskWorker};
+use crate::preview::PreviewRequest;
+use crate::prompts::PromptKind;
+use crate::task_queue::{AddOutcome, PendingPrompt, TaskEvent, TaskQueue, TaskStatus, TaskWorker};
+use crate::task_type::TaskType;
 use adw::prelude::*;
 use adw::{
     ActionRow, ComboRow, HeaderBar, PreferencesGroup, PreferencesPage, StatusPage, StyleManager,
@@ -10,8 +16,9 @@ use gtk4::{
     Box, Button, CheckButton, DropDown, Entry, Image, Label, ListBox, Orientation, ProgressBar,
     ScrolledWindow, SearchEntry, Separator, Spinner, StringList, TextView, Window, gio, glib,
 };
-use std::cell::RefCell;
-use std::collections::HashSet;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -42,11 +49,14 @@ impl ParuGui {
         let main_box = Box::new(Orientation::Vertical, 0);
 
         // Create task queue
-        let task_queue = Arc::new(TaskQueue::new());
+        let task_queue = Arc::new(TaskQueue::persistent(crate::task_queue::queue_file_path()));
 
         // Start the worker thread
         let worker = TaskWorker::new(task_queue.clone());
         worker.start();
+        Self::start_prompt_bridge(&main_box, task_queue.clone());
+        Self::start_conflict_bridge(&main_box, task_queue.clone());
+        Self::start_db_lock_bridge(&main_box, task_queue.clone());
 
         // Create header bar with modern styling
         let header_bar = HeaderBar::new();
@@ -82,6 +92,10 @@ impl ParuGui {
         queue_box.append(&queue_icon);
         let queue_label = Label::new(Some("Queue"));
         queue_box.append(&queue_label);
+        let queue_badge = Label::new(None);
+        queue_badge.add_css_class("badge");
+        queue_box.append(&queue_badge);
+        Self::start_queue_badge(&queue_badge, task_queue.clone());
 
         let queue_btn = Button::new();
         queue_btn.set_child(Some(&queue_box));
@@ -126,6 +140,34 @@ impl ParuGui {
         });
         header_bar.pack_end(&settings_btn);
 
+        // Local packages: built package files and PKGBUILD directories
+        let local_btn = Button::from_icon_name("document-open-symbolic");
+        local_btn.add_css_class("flat");
+        local_btn.set_tooltip_text(Some(
+            "Install a package file or build a local PKGBUILD (or drop them onto the window)",
+        ));
+        let local_list = Box::new(Orientation::Vertical, 4);
+        let local_popover = gtk4::Popover::new();
+        local_popover.set_child(Some(&local_list));
+        local_popover.set_parent(&local_btn);
+        for (label, folders) in [("Package Files...", false), ("PKGBUILD Directory...", true)] {
+            let item = Button::with_label(label);
+            item.add_css_class("flat");
+            let popover_weak = local_popover.downgrade();
+            let tq = task_queue.clone();
+            item.connect_clicked(move |item| {
+                if let Some(popover) = popover_weak.upgrade() {
+                    popover.popdown();
+                }
+                if let Some(window) = item.root().and_then(|w| w.downcast::<Window>().ok()) {
+                    Self::choose_local_packages(&window, folders, tq.clone());
+                }
+            });
+            local_list.append(&item);
+        }
+        local_btn.connect_clicked(move |_| local_popover.popup());
+        header_bar.pack_end(&local_btn);
+
         main_box.append(&header_bar);
 
         // Check if paru is installed
@@ -150,6 +192,8 @@ impl ParuGui {
             };
         }
 
+        Self::setup_local_package_drop(&main_box, task_queue.clone());
+
         // Create main content with ViewStack for better navigation
         let content_box = Box::new(Orientation::Vertical, 0);
 
@@ -559,10 +603,53 @@ impl ParuGui {
     fn show_confirmation_dialog<F>(parent: &gtk4::Window, title: &str, body: &str, on_confirm: F)
     where
         F: Fn() + 'static,
+    {
+        Self::show_preview_confirmation(parent, title, body, None, on_confirm);
+    }
+
+    /// Queue a full system update, first showing what it will do when the
+    /// settings ask for confirmation.
+    fn start_system_update(btn: &Button, task_queue: Arc<TaskQueue>, log_message: &'static str) {
+        let needs_confirm =
+            crate::settings::get().confirm_update_all || crate::settings::get().confirm_actions;
+        if !needs_confirm {
+            log_info(log_message);
+            task_queue.add_task(TaskType::Update, "system".to_string());
+            return;
+        }
+        if let Some(window) = btn.root().and_then(|w| w.downcast::<gtk4::Window>().ok()) {
+            let preview = ParuBackend::system_update_preview();
+            Self::show_preview_confirmation(
+                &window,
+                "Confirm System Update",
+                if preview.is_some() {
+                    "Update all packages now? The list below uses the current package databases; AUR updates are not included."
+                } else {
+                    "Update all AUR packages now?"
+                },
+                preview,
+                move || {
+                    log_info(log_message);
+                    task_queue.add_task(TaskType::Update, "system".to_string());
+                },
+            );
+        }
+    }
+
+    /// A confirmation that also lists what the transaction in `preview` would
+    /// install and remove, resolved by pacman while the dialog is open.
+    fn show_preview_confirmation<F>(
+        parent: &gtk4::Window,
+        title: &str,
+        body: &str,
+        preview: Option<PreviewRequest>,
+        on_confirm: F,
+    ) where
+        F: Fn() + 'static,
     {
         let dialog = Window::builder()
             .title(title)
-            .default_width(420)
+            .default_width(if preview.is_some() { 520 } else { 420 })
             .default_height(170)
             .modal(true)
             .transient_for(parent)
@@ -578,6 +665,9 @@ impl ParuGui {
         label.set_wrap(true);
         label.set_xalign(0.0);
         vbox.append(&label);
+        if let Some(request) = preview {
+            vbox.append(&Self::build_transaction_preview(request));
+        }
 
         let buttons = Box::new(Orientation::Horizontal, 8);
         buttons.set_halign(gtk4::Align::End);
@@ -607,6 +697,494 @@ impl ParuGui {
         dialog.present();
     }
 
+    fn build_transaction_preview(request: PreviewRequest) -> Box {
+        let container = Box::new(Orientation::Vertical, 8);
+        let status = Box::new(Orientation::Horizontal, 8);
+        let spinner = Spinner::new();
+        spinner.start();
+        status.append(&spinner);
+        let status_label = Label::new(Some("Resolving the transaction..."));
+        status_label.add_css_class("dim-label");
+        status_label.set_wrap(true);
+        status_label.set_xalign(0.0);
+        status.append(&status_label);
+        container.append(&status);
+
+        let list = ListBox::new();
+        list.add_css_class("boxed-list");
+        list.set_selection_mode(gtk4::SelectionMode::None);
+        let scroller = ScrolledWindow::new();
+        scroller.set_child(Some(&list));
+        scroller.set_max_content_height(280);
+        scroller.set_propagate_natural_height(true);
+        scroller.set_visible(false);
+        container.append(&scroller);
+
+        Self::run_blocking(
+            move || ParuBackend::preview_transaction(&request),
+            move |result| {
+                spinner.stop();
+                spinner.set_visible(false);
+                let preview = match result {
+                    Ok(preview) => preview,
+                    Err(err) => {
+                        status_label
+                            .set_text(&format!("Could not preview the transaction: {}", err));
+                        return;
+                    }
+                };
+                if preview.is_empty() {
+                    status_label.set_text("Nothing to do.");
+                    return;
+                }
+
+                let dependencies = preview
+                    .install
+                    .iter()
+                    .chain(&preview.remove)
+                    .filter(|p| p.dependency)
+                    .count();
+                let mut summary = Vec::new();
+                if !preview.install.is_empty() {
+                    summary.push(format!("{} to install", preview.install.len()));
+                }
+                if !preview.remove.is_empty() {
+                    summary.push(format!("{} to remove", preview.remove.len()));
+                }
+                if !preview.aur.is_empty() {
+                    summary.push(format!("{} to build from the AUR", preview.aur.len()));
+                }
+                if dependencies > 0 {
+                    summary.push(format!("{} as dependencies", dependencies));
+                }
+                if preview.download_size() > 0 {
+                    summary.push(format!(
+                        "Download {}",
+                        Self::format_bytes(preview.download_size())
+                    ));
+                }
+                let change = preview.size_change();
+                summary.push(format!(
+                    "Installed size {}{}",
+                    if change < 0 { "-" } else { "+" },
+                    Self::format_bytes(change.unsigned_abs())
+                ));
+                status_label.set_text(&summary.join(" \u{2022} "));
+                status_label.remove_css_class("dim-label");
+
+                for (package, removing) in preview
+                    .install
+                    .iter()
+                    .map(|p| (p, false))
+                    .chain(preview.remove.iter().map(|p| (p, true)))
+                {
+                    let row = ActionRow::new();
+                    row.set_title(&package.name);
+                    let version = match &package.old_version {
+                        Some(old) if *old != package.version => {
+                            format!("{} \u{2192} {}", old, package.version)
+                        }
+                        _ => package.version.clone(),
+                    };
+                    let mut subtitle = vec![
+                        if removing { "Remove" } else { "Install" }.to_string(),
+                        version,
+                    ];
+                    if !removing {
+                        subtitle.push(package.repository.clone());
+                    }
+                    if package.dependency {
+                        subtitle.push("dependency".to_string());
+                    }
+                    row.set_subtitle(&subtitle.join(" \u{2022} "));
+                    let size = Label::new(Some(&format!(
+                        "{}{}",
+                        if package.size_change < 0 { "-" } else { "+" },
+                        Self::format_bytes(package.size_change.unsigned_abs())
+                    )));
+                    size.add_css_class("dim-label");
+                    size.add_css_class("caption");
+                    row.add_suffix(&size);
+                    list.append(&row);
+                }
+                for name in &preview.aur {
+                    let row = ActionRow::new();
+                    row.set_title(name);
+                    row.set_subtitle("Build from the AUR \u{2022} dependencies resolved by paru");
+                    list.append(&row);
+                }
+                scroller.set_visible(true);
+            },
+        );
+        container
+    }
+
+    /// Keep the header badge at the number of queued and running tasks.
+    fn start_queue_badge(badge: &Label, task_queue: Arc<TaskQueue>) {
+        let update = |badge: &Label, task_queue: &TaskQueue| {
+            let active = task_queue
+                .get_tasks()
+                .iter()
+                .filter(|t| !t.status.is_finished())
+                .count();
+            badge.set_text(&active.to_string());
+            badge.set_visible(active > 0);
+            badge.set_tooltip_text(if task_queue.is_paused() {
+                Some("Queue paused")
+            } else if task_queue.db_lock() != LockState::Free {
+                Some("Waiting for the package database lock")
+            } else {
+                None
+            });
+        };
+        update(badge, &task_queue);
+
+        let events = task_queue.subscribe();
+        let badge_weak = badge.downgrade();
+        glib::timeout_add_local(Duration::from_millis(500), move || {
+            let Some(badge) = badge_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            let changed = events.try_iter().any(|event| {
+                matches!(
+                    event,
+                    TaskEvent::TaskAdded(_)
+                        | TaskEvent::StatusChanged { .. }
+                        | TaskEvent::Removed(_)
+                        | TaskEvent::PausedChanged(_)
+                        | TaskEvent::DbLockChanged(_)
+                )
+            });
+            if changed {
+                update(&badge, &task_queue);
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    /// The password dialog shown when parut runs as sudo's askpass program.
+    /// Prints the password for sudo and exits with failure if canceled.
+    pub fn run_askpass(prompt: &str) -> glib::ExitCode {
+        if gtk4::init().is_err() || adw::init().is_err() {
+            return glib::ExitCode::FAILURE;
+        }
+        let main_loop = glib::MainLoop::new(None, false);
+        let password: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+
+        let window = Window::builder()
+            .title("Authentication Required")
+            .default_width(380)
+            .resizable(false)
+            .build();
+        let vbox = Box::new(Orientation::Vertical, 12);
+        vbox.set_margin_start(16);
+        vbox.set_margin_end(16);
+        vbox.set_margin_top(16);
+        vbox.set_margin_bottom(16);
+
+        let title = Label::new(Some("Parut needs your password to change packages"));
+        title.add_css_class("heading");
+        title.set_wrap(true);
+        title.set_xalign(0.0);
+        vbox.append(&title);
+        let prompt_label = Label::new(Some(prompt.trim()));
+        prompt_label.add_css_class("dim-label");
+        prompt_label.set_wrap(true);
+        prompt_label.set_xalign(0.0);
+        vbox.append(&prompt_label);
+
+        let entry = gtk4::PasswordEntry::new();
+        entry.set_show_peek_icon(true);
+        vbox.append(&entry);
+
+        let buttons = Box::new(Orientation::Horizontal, 8);
+        buttons.set_halign(gtk4::Align::End);
+        let cancel = Button::with_label("Cancel");
+        let authenticate = Button::with_label("Authenticate");
+        authenticate.add_css_class("suggested-action");
+        buttons.append(&cancel);
+        buttons.append(&authenticate);
+        vbox.append(&buttons);
+        window.set_child(Some(&vbox));
+
+        let submit = {
+            let window = window.clone();
+            let entry = entry.clone();
+            let password = password.clone();
+            move || {
+                *password.borrow_mut() = Some(entry.text().to_string());
+                window.close();
+            }
+        };
+        let submit_on_click = submit.clone();
+        authenticate.connect_clicked(move |_| submit_on_click());
+        entry.connect_activate(move |_| submit());
+        let window_for_cancel = window.clone();
+        cancel.connect_clicked(move |_| window_for_cancel.close());
+        let loop_for_close = main_loop.clone();
+        window.connect_close_request(move |_| {
+            loop_for_close.quit();
+            glib::Propagation::Proceed
+        });
+
+        window.present();
+        entry.grab_focus();
+        main_loop.run();
+
+        match password.take() {
+            Some(password) => {
+                println!("{}", password);
+                glib::ExitCode::SUCCESS
+            }
+            None => glib::ExitCode::FAILURE,
+        }
+    }
+
+    /// Present questions from running tasks (providers, conflicts, sudo) as dialogs.
+    fn start_prompt_bridge(parent: &Box, task_queue: Arc<TaskQueue>) {
+        let parent_weak = parent.downgrade();
+        glib::timeout_add_local(Duration::from_millis(300), move || {
+            let Some(parent) = parent_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            for pending in task_queue.take_unshown_prompts() {
+                Self::show_prompt_dialog(&parent, pending, task_queue.clone());
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    /// Explain tasks the queue turned down, and ask before queueing one that
+    /// would undo a queued task.
+    fn start_conflict_bridge(parent: &Box, task_queue: Arc<TaskQueue>) {
+        let events = task_queue.subscribe();
+        let parent_weak = parent.downgrade();
+        glib::timeout_add_local(Duration::from_millis(300), move || {
+            let Some(parent) = parent_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            let mut notes = Vec::new();
+            for event in events.try_iter() {
+                let TaskEvent::NotAdded {
+                    task_type,
+                    package_name,
+                    outcome,
+                } = event
+                else {
+                    continue;
+                };
+                let reason = outcome.reason().unwrap_or_default().to_string();
+                if !matches!(outcome, AddOutcome::NeedsConfirmation { .. }) {
+                    notes.push(reason);
+                    continue;
+                }
+                Self::confirm_conflicting_task(
+                    &parent,
+                    task_type,
+                    package_name,
+                    &reason,
+                    task_queue.clone(),
+                    || {},
+                );
+            }
+            if !notes.is_empty() {
+                let dialog = adw::AlertDialog::new(Some("Not Queued"), Some(&notes.join("\n")));
+                dialog.add_response("ok", "OK");
+                dialog.present(Some(&parent));
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    /// Ask before queueing a task that would undo a queued one; `on_queued`
+    /// runs once the user queues it anyway.
+    fn confirm_conflicting_task(
+        parent: &impl IsA<gtk4::Widget>,
+        task_type: TaskType,
+        package_name: String,
+        reason: &str,
+        task_queue: Arc<TaskQueue>,
+        on_queued: impl Fn() + 'static,
+    ) {
+        let dialog = adw::AlertDialog::new(
+            Some(&format!("Queue {} {}?", task_type.label(), package_name)),
+            Some(reason),
+        );
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("queue", "Queue Anyway");
+        dialog.set_response_appearance("queue", adw::ResponseAppearance::Destructive);
+        dialog.set_close_response("cancel");
+        dialog.connect_response(Some("queue"), move |_, _| {
+            task_queue.add_task_unchecked(task_type.clone(), package_name.clone());
+            on_queued();
+        });
+        dialog.present(Some(parent));
+    }
+
+    /// Offer to remove a stale pacman lock as soon as the worker runs into one.
+    fn start_db_lock_bridge(parent: &Box, task_queue: Arc<TaskQueue>) {
+        let events = task_queue.subscribe();
+        let parent_weak = parent.downgrade();
+        glib::timeout_add_local(Duration::from_millis(500), move || {
+            let Some(parent) = parent_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            let stale = events
+                .try_iter()
+                .filter(|event| matches!(event, TaskEvent::DbLockChanged(_)))
+                .last()
+                .is_some_and(|event| event == TaskEvent::DbLockChanged(LockState::Stale));
+            if stale {
+                Self::confirm_remove_stale_lock(&parent);
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    fn confirm_remove_stale_lock(parent: &impl IsA<gtk4::Widget>) {
+        let dialog = adw::AlertDialog::new(
+            Some("Remove Stale Database Lock?"),
+            Some(&format!(
+                "{} exists, but no pacman process is running. It was most likely left behind when pacman crashed or was killed, and queued tasks wait until it is gone.\n\nOnly remove it if no package manager is running elsewhere, for example in another session or container.",
+                crate::db_lock::DB_LOCK_PATH
+            )),
+        );
+        dialog.add_response("cancel", "Keep Waiting");
+        dialog.add_response("remove", "Remove Lock");
+        dialog.set_response_appearance("remove", adw::ResponseAppearance::Destructive);
+        dialog.set_close_response("cancel");
+        let parent_weak = parent.as_ref().downgrade();
+        dialog.connect_response(Some("remove"), move |_, _| {
+            let parent_weak = parent_weak.clone();
+            Self::run_blocking(crate::db_lock::remove_stale, move |result| {
+                if let Err(err) = result {
+                    log_error(&format!("Failed to remove stale lock: {}", err));
+                    if let Some(parent) = parent_weak.upgrade() {
+                        let dialog =
+                            adw::AlertDialog::new(Some("Lock Not Removed"), Some(&err.to_string()));
+                        dialog.add_response("ok", "OK");
+                        dialog.present(Some(&parent));
+                    }
+                }
+            });
+        });
+        dialog.present(Some(parent));
+    }
+
+    fn show_prompt_dialog(parent: &Box, pending: PendingPrompt, task_queue: Arc<TaskQueue>) {
+        let task_label = task_queue
+            .get_tasks()
+            .into_iter()
+            .find(|t| t.id == pending.task_id)
+            .map(|t| format!("{:?} {}", t.task_type, t.package_name))
+            .unwrap_or_else(|| "paru".to_string());
+        let prompt = pending.prompt;
+
+        let dialog = adw::AlertDialog::new(
+            Some(&prompt.question),
+            Some(&format!("Task: {}", task_label)),
+        );
+        dialog.add_response("cancel", "Cancel Task");
+        dialog.set_response_appearance("cancel", adw::ResponseAppearance::Destructive);
+        dialog.set_close_response("cancel");
+
+        let extra = Box::new(Orientation::Vertical, 8);
+        if !prompt.details.is_empty() {
+            let details = Label::new(Some(&prompt.details.join("\n")));
+            details.set_xalign(0.0);
+            details.set_wrap(true);
+            details.add_css_class("monospace");
+            details.add_css_class("caption");
+            extra.append(&details);
+        }
+
+        let default_answer = prompt.default_answer();
+        let read_answer: Rc<dyn Fn() -> String> = match &prompt.kind {
+            PromptKind::Confirm { default_yes } => {
+                dialog.add_response("no", "No");
+                dialog.add_response("yes", "Yes");
+                dialog.set_response_appearance(
+                    if *default_yes { "yes" } else { "no" },
+                    adw::ResponseAppearance::Suggested,
+                );
+                dialog.set_default_response(Some(if *default_yes { "yes" } else { "no" }));
+                Rc::new(move || default_answer.clone())
+            }
+            PromptKind::Choice { options, .. } if !options.is_empty() => {
+                let selected = Rc::new(RefCell::new(default_answer.clone()));
+                let list = Box::new(Orientation::Vertical, 4);
+                let mut group: Option<CheckButton> = None;
+                for option in options {
+                    let label = match &option.repository {
+                        Some(repo) => format!("{} ({})", option.label, repo),
+                        None => option.label.clone(),
+                    };
+                    let check = CheckButton::with_label(&label);
+                    if let Some(first) = &group {
+                        check.set_group(Some(first));
+                    } else {
+                        group = Some(check.clone());
+                    }
+                    check.set_active(option.value == default_answer);
+                    let selected = selected.clone();
+                    let value = option.value.clone();
+                    check.connect_toggled(move |btn| {
+                        if btn.is_active() {
+                            *selected.borrow_mut() = value.clone();
+                        }
+                    });
+                    list.append(&check);
+                }
+                let scroller = ScrolledWindow::new();
+                scroller.set_child(Some(&list));
+                scroller.set_max_content_height(260);
+                scroller.set_propagate_natural_height(true);
+                extra.append(&scroller);
+                dialog.add_response("ok", "Select");
+                dialog.set_response_appearance("ok", adw::ResponseAppearance::Suggested);
+                dialog.set_default_response(Some("ok"));
+                Rc::new(move || selected.borrow().clone())
+            }
+            PromptKind::Password => {
+                let entry = gtk4::PasswordEntry::new();
+                entry.set_show_peek_icon(true);
+                entry.set_activates_default(true);
+                extra.append(&entry);
+                dialog.add_response("ok", "Authenticate");
+                dialog.set_response_appearance("ok", adw::ResponseAppearance::Suggested);
+                dialog.set_default_response(Some("ok"));
+                Rc::new(move || entry.text().to_string())
+            }
+            PromptKind::Choice { .. } | PromptKind::Text { .. } => {
+                let entry = Entry::new();
+                entry.set_text(&default_answer);
+                entry.set_activates_default(true);
+                extra.append(&entry);
+                dialog.add_response("ok", "Send");
+                dialog.set_response_appearance("ok", adw::ResponseAppearance::Suggested);
+                dialog.set_default_response(Some("ok"));
+                Rc::new(move || entry.text().to_string())
+            }
+        };
+        if extra.first_child().is_some() {
+            dialog.set_extra_child(Some(&extra));
+        }
+
+        let prompt_id = pending.id;
+        let task_id = pending.task_id;
+        dialog.connect_response(None, move |_, response| match response {
+            "yes" => task_queue.answer_prompt(prompt_id, Some("y".to_string())),
+            "no" => task_queue.answer_prompt(prompt_id, Some("n".to_string())),
+            "ok" => task_queue.answer_prompt(prompt_id, Some(read_answer())),
+            _ => {
+                task_queue.request_cancel(task_id);
+                task_queue.answer_prompt(prompt_id, None);
+            }
+        });
+
+        dialog.present(Some(parent));
+    }
+
     fn format_bytes(bytes: u64) -> String {
         const KB: f64 = 1024.0;
         const MB: f64 = KB * 1024.0;
@@ -774,25 +1352,7 @@ impl ParuGui {
         );
         let tq = task_queue.clone();
         update_btn.connect_clicked(move |btn| {
-            let needs_confirm =
-                crate::settings::get().confirm_update_all || crate::settings::get().confirm_actions;
-            if needs_confirm {
-                if let Some(window) = btn.root().and_then(|w| w.downcast::<gtk4::Window>().ok()) {
-                    let tq_confirm = tq.clone();
-                    Self::show_confirmation_dialog(
-                        &window,
-                        "Confirm System Update",
-                        "Update all packages now?",
-                        move || {
-                            log_info("Starting system update from dashboard");
-                            tq_confirm.add_task(TaskType::Update, "system".to_string());
-                        },
-                    );
-                }
-            } else {
-                log_info("Starting system update from dashboard");
-                tq.add_task(TaskType::Update, "system".to_string());
-            }
+            Self::start_system_update(btn, tq.clone(), "Starting system update from dashboard");
         });
         actions_box.append(&update_btn);
 
@@ -838,6 +1398,50 @@ impl ParuGui {
         });
         actions_box.append(&orphan_btn);
 
+        // Routines menu, filled from settings each time it opens
+        let routines_btn = Self::create_action_button(
+            "Routines",
+            "view-list-bullet-symbolic",
+            "Queue a saved sequence of tasks (edit routines in Settings)",
+        );
+        let routines_list = Box::new(Orientation::Vertical, 4);
+        let routines_popover = gtk4::Popover::new();
+        routines_popover.set_child(Some(&routines_list));
+        routines_popover.set_parent(&routines_btn);
+        let tq_routines = task_queue.clone();
+        routines_btn.connect_clicked(move |_| {
+            while let Some(child) = routines_list.first_child() {
+                routines_list.remove(&child);
+            }
+            let routines = crate::settings::get().routines;
+            if routines.is_empty() {
+                let empty = Label::new(Some("No routines yet. Add one in Settings."));
+                empty.add_css_class("dim-label");
+                routines_list.append(&empty);
+            }
+            for routine in routines {
+                let steps: Vec<&str> = routine.steps.iter().map(|s| s.task_type.label()).collect();
+                let item = Button::with_label(&routine.name);
+                item.add_css_class("flat");
+                item.set_tooltip_text(Some(&steps.join(" \u{2192} ")));
+                let tq = tq_routines.clone();
+                let popover_weak = routines_popover.downgrade();
+                item.connect_clicked(move |_| {
+                    log_info(&format!(
+                        "Starting routine from dashboard: {}",
+                        routine.name
+                    ));
+                    tq.add_routine(&routine);
+                    if let Some(popover) = popover_weak.upgrade() {
+                        popover.popdown();
+                    }
+                });
+                routines_list.append(&item);
+            }
+            routines_popover.popup();
+        });
+        actions_box.append(&routines_btn);
+
         vbox.append(&actions_box);
 
         // Arch news section
@@ -1132,7 +1736,7 @@ impl ParuGui {
                     error.add_css_class("dim-label");
                     error.set_halign(gtk4::Align::Start);
                     news_list.borrow().append(&error);
-                    news_status.borrow().set_text(&e);
+                    news_status.borrow().set_text(&e.to_string());
                     log_error(&format!("Arch news fetch failed: {}", e));
                 }
             },
@@ -1145,6 +1749,50 @@ impl ParuGui {
         package_name: &str,
         task_queue: Arc<TaskQueue>,
     ) {
+        let name = package_name.to_string();
+        let pkg = package_name.to_string();
+        Self::show_pkgbuild_review(
+            package_name,
+            &format!("Package: {} (from AUR)", package_name),
+            "AUR packages are user-produced content. Review this PKGBUILD carefully before proceeding.",
+            move || ParuBackend::get_pkgbuild(&name),
+            move || {
+                log_info(&format!("Adding install task for package: {}", pkg));
+                task_queue.add_task(TaskType::Install, pkg.clone());
+            },
+        );
+    }
+
+    /// Show the PKGBUILD in a local directory before building and installing it.
+    fn show_local_pkgbuild_dialog(dir: &Path, task_queue: Arc<TaskQueue>) {
+        let name = dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| dir.display().to_string());
+        let read_dir = dir.to_path_buf();
+        let build_dir = dir.to_string_lossy().to_string();
+        Self::show_pkgbuild_review(
+            &name,
+            &format!("Directory: {}", dir.display()),
+            "Building runs this PKGBUILD on your system. Review it carefully before proceeding.",
+            move || ParuBackend::read_local_pkgbuild(&read_dir),
+            move || {
+                log_info(&format!("Adding local build task for {}", build_dir));
+                task_queue.add_task(TaskType::BuildLocal, build_dir.clone());
+            },
+        );
+    }
+
+    fn show_pkgbuild_review<L, I>(
+        package_name: &str,
+        source: &str,
+        warning: &str,
+        load: L,
+        on_install: I,
+    ) where
+        L: FnOnce() -> Result<String, BackendError> + Send + 'static,
+        I: Fn() + 'static,
+    {
         // Create dialog window
         let dialog = Window::builder()
             .title(format!("Review PKGBUILD - {}", package_name))
@@ -1176,9 +1824,7 @@ impl ParuGui {
         warning_title.set_halign(gtk4::Align::Start);
         warning_text_box.append(&warning_title);
 
-        let warning_desc = Label::new(Some(
-            "AUR packages are user-produced content. Review this PKGBUILD carefully before proceeding.",
-        ));
+        let warning_desc = Label::new(Some(warning));
         warning_desc.set_wrap(true);
         warning_desc.set_xalign(0.0);
         warning_desc.add_css_class("dim-label");
@@ -1193,7 +1839,7 @@ impl ParuGui {
         pkg_icon.set_pixel_size(20);
         info_box.append(&pkg_icon);
 
-        let info_label = Label::new(Some(&format!("Package: {} (from AUR)", package_name)));
+        let info_label = Label::new(Some(source));
         info_label.add_css_class("heading");
         info_box.append(&info_label);
         main_box.append(&info_box);
@@ -1256,24 +1902,20 @@ impl ParuGui {
 
         // Fetch PKGBUILD content
         let text_buffer = text_view.buffer();
-        let package_name_clone = package_name.to_string();
         let install_btn_clone = install_btn.clone();
         let loading_box_clone = loading_box_rc.clone();
 
-        Self::run_blocking(
-            move || ParuBackend::get_pkgbuild(&package_name_clone),
-            move |result| match result {
-                Ok(content) => {
-                    text_buffer.set_text(&content);
-                    install_btn_clone.set_sensitive(true);
-                    loading_box_clone.borrow().set_visible(false);
-                }
-                Err(e) => {
-                    text_buffer.set_text(&format!("Error loading PKGBUILD:\n\n{}", e));
-                    loading_box_clone.borrow().set_visible(false);
-                }
-            },
-        );
+        Self::run_blocking(load, move |result| match result {
+            Ok(content) => {
+                text_buffer.set_text(&content);
+                install_btn_clone.set_sensitive(true);
+                loading_box_clone.borrow().set_visible(false);
+            }
+            Err(e) => {
+                text_buffer.set_text(&format!("Error loading PKGBUILD:\n\n{}", e));
+                loading_box_clone.borrow().set_visible(false);
+            }
+        });
 
         // Connect buttons
         let dialog_weak = dialog.downgrade();
@@ -1284,10 +1926,8 @@ impl ParuGui {
         });
 
         let dialog_weak2 = dialog.downgrade();
-        let pkg_clone = package_name.to_string();
         install_btn.connect_clicked(move |_| {
-            log_info(&format!("Adding install task for package: {}", pkg_clone));
-            task_queue.add_task(TaskType::Install, pkg_clone.clone());
+            on_install();
             if let Some(dialog) = dialog_weak2.upgrade() {
                 dialog.close();
             }
@@ -1296,7 +1936,137 @@ impl ParuGui {
         dialog.present();
     }
 
-    fn show_package_details_dialog(window: &impl IsA<gtk4::Window>, package_name: &str) {
+    /// Pick package files, or a PKGBUILD directory when `folders` is set, to install.
+    fn choose_local_packages(parent: &Window, folders: bool, task_queue: Arc<TaskQueue>) {
+        let chooser = gtk4::FileChooserNative::new(
+            Some(if folders {
+                "Build PKGBUILD Directory"
+            } else {
+                "Install Package Files"
+            }),
+            Some(parent),
+            if folders {
+                gtk4::FileChooserAction::SelectFolder
+            } else {
+                gtk4::FileChooserAction::Open
+            },
+            Some(if folders { "Build" } else { "Install" }),
+            Some("Cancel"),
+        );
+        if !folders {
+            chooser.set_select_multiple(true);
+            let filter = gtk4::FileFilter::new();
+            filter.set_name(Some("Packages"));
+            filter.add_pattern("*.pkg.tar*");
+            chooser.add_filter(&filter);
+        }
+        let parent = parent.clone();
+        chooser.connect_response(move |chooser, response| {
+            if response == gtk4::ResponseType::Accept {
+                let files = chooser.files();
+                let paths: Vec<PathBuf> = (0..files.n_items())
+                    .filter_map(|i| files.item(i))
+                    .filter_map(|item| item.downcast::<gio::File>().ok())
+                    .filter_map(|file| file.path())
+                    .collect();
+                Self::queue_local_packages(&parent, paths, task_queue.clone());
+            }
+            chooser.destroy();
+        });
+        chooser.show();
+    }
+
+    /// Accept package files and PKGBUILD directories dropped onto the main window.
+    fn setup_local_package_drop(main_box: &Box, task_queue: Arc<TaskQueue>) {
+        let target = gtk4::DropTarget::new(
+            gtk4::gdk::FileList::static_type(),
+            gtk4::gdk::DragAction::COPY,
+        );
+        target.connect_drop(move |target, value, _, _| {
+            let Ok(list) = value.get::<gtk4::gdk::FileList>() else {
+                return false;
+            };
+            let paths: Vec<PathBuf> = list.files().iter().filter_map(|f| f.path()).collect();
+            let Some(window) = target
+                .widget()
+                .and_then(|w| w.root())
+                .and_then(|r| r.downcast::<Window>().ok())
+            else {
+                return false;
+            };
+            Self::queue_local_packages(&window, paths, task_queue.clone());
+            true
+        });
+        main_box.add_controller(target);
+    }
+
+    /// Queue package files with `-U` and PKGBUILD directories with `-Bi`, after
+    /// confirming the files and reviewing each PKGBUILD.
+    fn queue_local_packages(parent: &Window, paths: Vec<PathBuf>, task_queue: Arc<TaskQueue>) {
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        let mut ignored = Vec::new();
+        for path in paths {
+            if path.is_dir() && path.join("PKGBUILD").is_file() {
+                dirs.push(path);
+            } else if path.is_file() && ParuBackend::is_package_file(&path) {
+                files.push(path);
+            } else {
+                ignored.push(path.display().to_string());
+            }
+        }
+
+        if !ignored.is_empty() {
+            let dialog = adw::AlertDialog::new(
+                Some("Not a Package"),
+                Some(&format!(
+                    "These are neither package files nor directories with a PKGBUILD:\n{}",
+                    ignored.join("\n")
+                )),
+            );
+            dialog.add_response("ok", "OK");
+            dialog.present(Some(parent));
+        }
+
+        for dir in dirs {
+            if crate::settings::get().aur_pkgbuild_required {
+                Self::show_local_pkgbuild_dialog(&dir, task_queue.clone());
+            } else {
+                log_info(&format!("Adding local build task for {}", dir.display()));
+                task_queue.add_task(TaskType::BuildLocal, dir.to_string_lossy().to_string());
+            }
+        }
+
+        if files.is_empty() {
+            return;
+        }
+        let names: Vec<String> = files
+            .iter()
+            .filter_map(|f| f.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .collect();
+        Self::show_confirmation_dialog(
+            parent,
+            "Install Package Files",
+            &format!(
+                "Install {} local package file(s)? Only install packages you trust.\n\n{}",
+                files.len(),
+                names.join("\n")
+            ),
+            move || {
+                for file in &files {
+                    log_info(&format!("Adding install task for {}", file.display()));
+                    task_queue.add_task(TaskType::InstallFile, file.to_string_lossy().to_string());
+                }
+            },
+        );
+    }
+
+    fn show_package_details_dialog(
+        window: &impl IsA<gtk4::Window>,
+        package_name: &str,
+        task_queue: Arc<TaskQueue>,
+    ) {
         let dialog = Window::builder()
             .title(format!("Package Details - {}", package_name))
             .default_width(600)
@@ -1359,9 +2129,85 @@ impl ParuGui {
         grid.set_row_spacing(12);
         content_box.append(&grid);
 
+        // Versions in pacman's cache, to roll back to
+        let cache_box = Box::new(Orientation::Vertical, 8);
+        cache_box.set_visible(false);
+        let cache_title = Label::new(Some("Cached Versions"));
+        cache_title.add_css_class("heading");
+        cache_title.set_halign(gtk4::Align::Start);
+        cache_box.append(&cache_title);
+        let cache_list = ListBox::new();
+        cache_list.add_css_class("boxed-list");
+        cache_list.set_selection_mode(gtk4::SelectionMode::None);
+        cache_box.append(&cache_list);
+        content_box.append(&cache_box);
+
         dialog.set_child(Some(&vbox));
         dialog.present();
 
+        let cache_name = package_name.to_string();
+        let dialog_for_cache = dialog.clone();
+        Self::run_blocking(
+            {
+                let name = cache_name.clone();
+                move || {
+                    let installed = crate::pacman_db::cached_local_db(Path::new(
+                        crate::pacman_db::LOCAL_DB_PATH,
+                    ))
+                    .ok()
+                    .and_then(|db| {
+                        db.iter()
+                            .find(|p| p.name == name)
+                            .map(|p| p.version.clone())
+                    });
+                    (crate::package_cache::cached_versions(&name), installed)
+                }
+            },
+            move |(versions, installed)| {
+                for cached in versions {
+                    let row = ActionRow::new();
+                    row.set_title(&cached.version);
+                    row.set_subtitle(&format!(
+                        "{} \u{2022} {}",
+                        Self::format_bytes(cached.size),
+                        cached.path.display()
+                    ));
+                    let newer = installed
+                        .as_deref()
+                        .map(|v| crate::package_cache::vercmp(&cached.version, v));
+                    if newer == Some(std::cmp::Ordering::Equal) {
+                        let current = Label::new(Some("Installed"));
+                        current.add_css_class("dim-label");
+                        row.add_suffix(&current);
+                    } else {
+                        let downgrade = newer == Some(std::cmp::Ordering::Less);
+                        let btn = Button::with_label(if downgrade {
+                            "Downgrade to this version"
+                        } else {
+                            "Install this version"
+                        });
+                        btn.add_css_class("flat");
+                        btn.set_valign(gtk4::Align::Center);
+                        let parent = dialog_for_cache.clone();
+                        let name = cache_name.clone();
+                        let task_queue = task_queue.clone();
+                        btn.connect_clicked(move |_| {
+                            Self::confirm_cached_install(
+                                &parent,
+                                &name,
+                                cached.clone(),
+                                downgrade,
+                                task_queue.clone(),
+                            );
+                        });
+                        row.add_suffix(&btn);
+                    }
+                    cache_list.append(&row);
+                    cache_box.set_visible(true);
+                }
+            },
+        );
+
         let loading_label_clone = loading_label.clone();
         let name = package_name.to_string();
 
@@ -1374,7 +2220,7 @@ impl ParuGui {
                     // Configure external button if AUR
                     if details.repository == "aur" {
                         let aur_url =
-                            format!("https://aur.archlinux.org/packages/{}/", details.name);
+                            crate::aur::AurClient::from_settings().package_url(&details.name);
                         external_btn.set_visible(true);
                         external_btn.connect_clicked(move |_| {
                             let _ = gio::AppInfo::launch_default_for_uri(
@@ -1384,11 +2230,21 @@ impl ParuGui {
                         });
                     }
 
+                    // Only worth showing for split packages.
+                    let package_base = if details.package_base == details.name {
+                        String::new()
+                    } else {
+                        details.package_base.clone()
+                    };
                     let fields = [
                         ("Description", &details.description),
                         ("Repository", &details.repository),
                         ("Votes", &details.votes),
                         ("Popularity", &details.popularity),
+                        ("Maintainer", &details.maintainer),
+                        ("Package Base", &package_base),
+                        ("Out of Date", &details.out_of_date),
+                        ("Last Modified", &details.last_modified),
                         ("URL", &details.url),
                         ("Licenses", &details.licenses),
                         ("Groups", &details.groups),
@@ -1581,6 +2437,86 @@ impl ParuGui {
         );
     }
 
+    /// Queue the cached `cached` file of `name`, optionally adding the package to
+    /// the ignored updates so the next system update doesn't undo it.
+    fn confirm_cached_install(
+        parent: &Window,
+        name: &str,
+        cached: crate::package_cache::CachedPackage,
+        downgrade: bool,
+        task_queue: Arc<TaskQueue>,
+    ) {
+        let dialog = adw::AlertDialog::new(
+            Some(&if downgrade {
+                format!("Downgrade {}?", name)
+            } else {
+                format!("Install {} {}?", name, cached.version)
+            }),
+            Some(&format!(
+                "{} {} will be installed from {}. Packages that depend on a newer version may stop working.",
+                name,
+                cached.version,
+                cached.path.display()
+            )),
+        );
+        let already_ignored = crate::settings::get()
+            .ignored_updates
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(name));
+        let ignore_check =
+            CheckButton::with_label(&format!("Ignore updates of {} afterwards", name));
+        ignore_check.set_active(already_ignored);
+        ignore_check.set_sensitive(!already_ignored);
+        dialog.set_extra_child(Some(&ignore_check));
+        dialog.add_response("cancel", "Cancel");
+        dialog.add_response("install", if downgrade { "Downgrade" } else { "Install" });
+        dialog.set_response_appearance("install", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        let name = name.to_string();
+        let window = parent.clone();
+        dialog.connect_response(Some("install"), move |_, _| {
+            log_info(&format!(
+                "Adding {} task for {} {}",
+                if downgrade { "downgrade" } else { "install" },
+                name,
+                cached.version
+            ));
+            // Pin the version only once it is actually going to be installed.
+            let pin = ignore_check.is_active() && !already_ignored;
+            let name = name.clone();
+            let pin_version = move || {
+                if pin {
+                    crate::settings::update(|s| {
+                        s.ignored_updates.push(name.clone());
+                        s.ignored_updates.sort();
+                        s.ignored_updates.dedup();
+                    });
+                }
+            };
+            let task_type = if downgrade {
+                TaskType::Downgrade
+            } else {
+                TaskType::InstallFile
+            };
+            let path = cached.path.to_string_lossy().to_string();
+            if let Some(AddOutcome::NeedsConfirmation { reason, .. }) =
+                task_queue.check_new_task(&task_type, &path)
+            {
+                Self::confirm_conflicting_task(
+                    &window,
+                    task_type,
+                    path,
+                    &reason,
+                    task_queue.clone(),
+                    pin_version,
+                );
+            } else if task_queue.add_task(task_type, path).task_id().is_some() {
+                pin_version();
+            }
+        });
+        dialog.present(Some(parent));
+    }
+
     fn show_queue_window(task_queue: Arc<TaskQueue>) {
         let window = Window::builder()
             .title("Task Queue")
@@ -1617,17 +2553,139 @@ impl ParuGui {
         clear_btn.set_child(Some(&clear_box));
         clear_btn.add_css_class("flat");
         clear_btn.set_tooltip_text(Some(
-            "Remove completed, canceled, and failed tasks from the queue",
+            "Remove completed, canceled, failed, and interrupted tasks from the queue",
         ));
 
         let task_queue_clone = task_queue.clone();
         clear_btn.connect_clicked(move |_| {
             task_queue_clone.clear_completed();
         });
+
+        let schedule_btn_box = Box::new(Orientation::Horizontal, 6);
+        schedule_btn_box.append(&Image::from_icon_name("alarm-symbolic"));
+        schedule_btn_box.append(&Label::new(Some("Schedule...")));
+        let schedule_btn = Button::new();
+        schedule_btn.set_child(Some(&schedule_btn_box));
+        schedule_btn.add_css_class("flat");
+        schedule_btn.set_tooltip_text(Some("Run a maintenance task later or on a recurring basis"));
+        let window_weak = window.downgrade();
+        schedule_btn.connect_clicked(move |_| {
+            if let Some(window) = window_weak.upgrade() {
+                Self::show_schedule_dialog(&window);
+            }
+        });
+        let pause_icon = Image::new();
+        let pause_label = Label::new(None);
+        let pause_btn_box = Box::new(Orientation::Horizontal, 6);
+        pause_btn_box.append(&pause_icon);
+        pause_btn_box.append(&pause_label);
+        let pause_btn = Button::new();
+        pause_btn.set_child(Some(&pause_btn_box));
+        pause_btn.add_css_class("flat");
+        let task_queue_clone = task_queue.clone();
+        pause_btn.connect_clicked(move |_| {
+            task_queue_clone.set_paused(!task_queue_clone.is_paused());
+        });
+        let history_btn_box = Box::new(Orientation::Horizontal, 6);
+        history_btn_box.append(&Image::from_icon_name("document-open-recent-symbolic"));
+        history_btn_box.append(&Label::new(Some("History")));
+        let history_btn = Button::new();
+        history_btn.set_child(Some(&history_btn_box));
+        history_btn.add_css_class("flat");
+        history_btn.set_tooltip_text(Some("Search finished tasks and their complete output"));
+        let window_weak = window.downgrade();
+        history_btn.connect_clicked(move |_| {
+            if let Some(window) = window_weak.upgrade() {
+                Self::show_history_window(&window);
+            }
+        });
+        header_box.append(&history_btn);
+        header_box.append(&schedule_btn);
+        header_box.append(&pause_btn);
         header_box.append(&clear_btn);
 
         vbox.append(&header_box);
 
+        let paused_box = Box::new(Orientation::Horizontal, 8);
+        paused_box.append(&Image::from_icon_name("media-playback-pause-symbolic"));
+        let paused_label = Label::new(Some(
+            "Queue paused. Running tasks continue; queued tasks wait until you resume.",
+        ));
+        paused_label.set_wrap(true);
+        paused_label.set_xalign(0.0);
+        paused_box.append(&paused_label);
+        paused_box.add_css_class("warning");
+        vbox.append(&paused_box);
+
+        let lock_box = Box::new(Orientation::Horizontal, 8);
+        lock_box.append(&Image::from_icon_name("changes-prevent-symbolic"));
+        let lock_label = Label::new(None);
+        lock_label.set_wrap(true);
+        lock_label.set_xalign(0.0);
+        lock_label.set_hexpand(true);
+        lock_box.append(&lock_label);
+        let remove_lock_btn = Button::with_label("Remove Stale Lock...");
+        remove_lock_btn.add_css_class("destructive-action");
+        remove_lock_btn.set_valign(gtk4::Align::Center);
+        let window_weak = window.downgrade();
+        remove_lock_btn.connect_clicked(move |_| {
+            if let Some(window) = window_weak.upgrade() {
+                Self::confirm_remove_stale_lock(&window);
+            }
+        });
+        lock_box.append(&remove_lock_btn);
+        lock_box.add_css_class("warning");
+        vbox.append(&lock_box);
+
+        let show_db_lock = move |state: &LockState| {
+            match state {
+                LockState::Free => {}
+                LockState::Held { pid, program } => lock_label.set_text(&format!(
+                    "Waiting for {} (process {}) to release the package database lock.",
+                    program, pid
+                )),
+                LockState::Stale => lock_label.set_text(
+                    "The package database is locked, but no package manager is running. The lock was probably left behind by a crash.",
+                ),
+                LockState::Unknown => lock_label
+                    .set_text("Waiting for another program to release the package database lock."),
+            }
+            remove_lock_btn.set_visible(*state == LockState::Stale);
+            lock_box.set_visible(*state != LockState::Free);
+        };
+        show_db_lock(&task_queue.db_lock());
+
+        let show_paused = move |paused: bool| {
+            if paused {
+                pause_icon.set_icon_name(Some("media-playback-start-symbolic"));
+                pause_label.set_text("Resume");
+                pause_btn.set_tooltip_text(Some("Start queued tasks again"));
+            } else {
+                pause_icon.set_icon_name(Some("media-playback-pause-symbolic"));
+                pause_label.set_text("Pause");
+                pause_btn.set_tooltip_text(Some(
+                    "Stop starting queued tasks; running tasks are not interrupted",
+                ));
+            }
+            paused_box.set_visible(paused);
+        };
+        show_paused(task_queue.is_paused());
+
+        // Scheduled tasks, hidden while there are none
+        let schedule_section = Box::new(Orientation::Vertical, 8);
+        let schedule_title = Label::new(Some("Scheduled"));
+        schedule_title.add_css_class("heading");
+        schedule_title.set_halign(gtk4::Align::Start);
+        schedule_section.append(&schedule_title);
+        let schedule_list = ListBox::new();
+        schedule_list.add_css_class("boxed-list");
+        schedule_list.set_selection_mode(gtk4::SelectionMode::None);
+        schedule_section.append(&schedule_list);
+        schedule_section.set_visible(false);
+        vbox.append(&schedule_section);
+        let shown_schedules: Rc<RefCell<Option<Vec<crate::schedule::Schedule>>>> =
+            Rc::new(RefCell::new(None));
+
         // Task list
         let scrolled = ScrolledWindow::new();
         scrolled.set_vexpand(true);
@@ -1637,57 +2695,475 @@ impl ParuGui {
         scrolled.set_child(Some(&list_box));
         vbox.append(&scrolled);
 
-        window.set_child(Some(&vbox));
+        let empty_box = Box::new(Orientation::Vertical, 12);
+        empty_box.set_margin_top(48);
+        empty_box.set_margin_bottom(48);
+        empty_box.set_halign(gtk4::Align::Center);
 
-        // Update task list
-        let list_box_rc = Rc::new(RefCell::new(list_box));
-        let list_box_clone = list_box_rc.clone();
+        let empty_icon = Image::from_icon_name("view-list-symbolic");
+        empty_icon.set_pixel_size(64);
+        empty_icon.add_css_class("dim-label");
+        empty_box.append(&empty_icon);
+
+        let empty = Label::new(Some("No tasks in queue"));
+        empty.add_css_class("dim-label");
+        empty_box.append(&empty);
+        list_box.set_placeholder(Some(&empty_box));
 
-        let update_list = move || {
-            let tasks = task_queue.get_tasks();
-            let list_box = list_box_clone.borrow();
+        window.set_child(Some(&vbox));
 
-            // Clear existing
-            while let Some(child) = list_box.first_child() {
-                list_box.remove(&child);
+        // One row per task, rebuilt only when that task changes
+        let rows: Rc<RefCell<HashMap<usize, gtk4::ListBoxRow>>> =
+            Rc::new(RefCell::new(HashMap::new()));
+        let add_row = {
+            let list_box = list_box.clone();
+            let rows = rows.clone();
+            let task_queue = task_queue.clone();
+            move |task: &crate::task_queue::Task| {
+                let row = gtk4::ListBoxRow::new();
+                row.set_child(Some(&Self::create_task_row(task, task_queue.clone())));
+                list_box.append(&row);
+                rows.borrow_mut().insert(task.id, row);
+            }
+        };
+        let rebuild_all = {
+            let list_box = list_box.clone();
+            let rows = rows.clone();
+            let task_queue = task_queue.clone();
+            let add_row = add_row.clone();
+            move || {
+                for (_, row) in rows.borrow_mut().drain() {
+                    list_box.remove(&row);
+                }
+                for task in &task_queue.get_tasks() {
+                    add_row(task);
+                }
             }
+        };
 
-            if tasks.is_empty() {
-                let empty_box = Box::new(Orientation::Vertical, 12);
-                empty_box.set_margin_top(48);
-                empty_box.set_margin_bottom(48);
-                empty_box.set_halign(gtk4::Align::Center);
+        // Subscribe before the first fill so no change falls in between.
+        let events = task_queue.subscribe();
+        rebuild_all();
 
-                let empty_icon = Image::from_icon_name("view-list-symbolic");
-                empty_icon.set_pixel_size(64);
-                empty_icon.add_css_class("dim-label");
-                empty_box.append(&empty_icon);
+        // Apply changes in batches; a busy task appends many lines a second.
+        let window_weak = window.downgrade();
+        glib::timeout_add_local(Duration::from_millis(250), move || {
+            if window_weak.upgrade().is_none() {
+                // Dropping `events` unsubscribes.
+                return glib::ControlFlow::Break;
+            }
 
-                let empty = Label::new(Some("No tasks in queue"));
-                empty.add_css_class("dim-label");
-                empty_box.append(&empty);
+            // Only rebuild the schedule rows when they changed, so their buttons stay clickable.
+            let schedules = crate::data_store::schedules();
+            if shown_schedules.borrow().as_ref() != Some(&schedules) {
+                Self::fill_schedule_list(&schedule_list, &schedules);
+                schedule_section.set_visible(!schedules.is_empty());
+                *shown_schedules.borrow_mut() = Some(schedules);
+            }
+
+            let mut dirty: HashSet<usize> = HashSet::new();
+            let mut reordered = false;
+            for event in events.try_iter() {
+                match event {
+                    TaskEvent::TaskAdded(task) => add_row(&task),
+                    TaskEvent::StatusChanged { id, .. }
+                    | TaskEvent::OutputAppended { id, .. }
+                    | TaskEvent::ProgressChanged { id, .. } => {
+                        dirty.insert(id);
+                    }
+                    TaskEvent::Removed(id) => {
+                        if let Some(row) = rows.borrow_mut().remove(&id) {
+                            list_box.remove(&row);
+                        }
+                        dirty.remove(&id);
+                    }
+                    TaskEvent::Reordered => reordered = true,
+                    TaskEvent::PausedChanged(paused) => show_paused(paused),
+                    TaskEvent::DbLockChanged(state) => show_db_lock(&state),
+                    TaskEvent::NotAdded { .. } => {}
+                }
+            }
 
-                list_box.append(&empty_box);
+            if reordered {
+                rebuild_all();
             } else {
-                for task in &tasks {
-                    let row = Self::create_task_row(task, task_queue.clone());
-                    list_box.append(&row);
+                let rows = rows.borrow();
+                for id in dirty {
+                    if let (Some(row), Some(task)) = (rows.get(&id), task_queue.get_task(id)) {
+                        row.set_child(Some(&Self::create_task_row(&task, task_queue.clone())));
+                    }
                 }
             }
+            glib::ControlFlow::Continue
+        });
+
+        window.present();
+    }
+
+    fn show_history_window(parent: &Window) {
+        use crate::task_history::{self, ArchivedTask, HistoryFilter};
+        const STATUSES: [&str; 4] = ["Completed", "Failed", "Canceled", "Interrupted"];
+        const PERIODS: [(&str, u64); 3] = [
+            ("Last 24 Hours", 86_400),
+            ("Last 7 Days", 7 * 86_400),
+            ("Last 30 Days", 30 * 86_400),
+        ];
+
+        let window = Window::builder()
+            .title("Task History")
+            .default_width(760)
+            .default_height(560)
+            .transient_for(parent)
+            .build();
+
+        let vbox = Box::new(Orientation::Vertical, 12);
+        vbox.set_margin_start(20);
+        vbox.set_margin_end(20);
+        vbox.set_margin_top(20);
+        vbox.set_margin_bottom(20);
+
+        let title = Label::new(Some("Task History"));
+        title.add_css_class("title-2");
+        title.set_halign(gtk4::Align::Start);
+        vbox.append(&title);
+
+        let filters = Box::new(Orientation::Horizontal, 8);
+        let search = SearchEntry::new();
+        search.set_placeholder_text(Some("Package"));
+        search.set_hexpand(true);
+        filters.append(&search);
+
+        let mut type_labels = vec!["All Types"];
+        type_labels.extend(TaskType::ALL.iter().map(TaskType::label));
+        let type_dropdown = DropDown::from_strings(&type_labels);
+        filters.append(&type_dropdown);
+
+        let mut status_labels = vec!["Any Status"];
+        status_labels.extend(STATUSES);
+        let status_dropdown = DropDown::from_strings(&status_labels);
+        filters.append(&status_dropdown);
+
+        let mut period_labels = vec!["Any Time"];
+        period_labels.extend(PERIODS.iter().map(|(label, _)| *label));
+        let period_dropdown = DropDown::from_strings(&period_labels);
+        filters.append(&period_dropdown);
+        vbox.append(&filters);
+
+        let scrolled = ScrolledWindow::new();
+        scrolled.set_vexpand(true);
+        let list = ListBox::new();
+        list.add_css_class("boxed-list");
+        list.set_selection_mode(gtk4::SelectionMode::None);
+        let placeholder = Label::new(Some("Loading history..."));
+        placeholder.add_css_class("dim-label");
+        placeholder.set_margin_top(48);
+        placeholder.set_margin_bottom(48);
+        list.set_placeholder(Some(&placeholder));
+        scrolled.set_child(Some(&list));
+        vbox.append(&scrolled);
+        window.set_child(Some(&vbox));
+
+        let history: Rc<RefCell<Vec<ArchivedTask>>> = Rc::new(RefCell::new(Vec::new()));
+        let render: Rc<dyn Fn()> = {
+            let history = history.clone();
+            let window = window.clone();
+            let search = search.clone();
+            let type_dropdown = type_dropdown.clone();
+            let status_dropdown = status_dropdown.clone();
+            let period_dropdown = period_dropdown.clone();
+            let list = list.clone();
+            Rc::new(move || {
+                let now = chrono::Local::now().timestamp().max(0) as u64;
+                let filter = HistoryFilter {
+                    package: search.text().to_string(),
+                    task_type: (type_dropdown.selected() as usize)
+                        .checked_sub(1)
+                        .and_then(|i| TaskType::ALL.get(i).cloned()),
+                    status: (status_dropdown.selected() as usize)
+                        .checked_sub(1)
+                        .and_then(|i| STATUSES.get(i).copied()),
+                    since: (period_dropdown.selected() as usize)
+                        .checked_sub(1)
+                        .and_then(|i| PERIODS.get(i))
+                        .map(|(_, secs)| now.saturating_sub(*secs)),
+                };
+                while let Some(child) = list.first_child() {
+                    list.remove(&child);
+                }
+                for entry in history.borrow().iter().filter(|e| filter.matches(e)) {
+                    list.append(&Self::create_history_row(&window, entry));
+                }
+            })
         };
 
-        // Initial update
-        update_list();
+        search.connect_search_changed({
+            let render = render.clone();
+            move |_| render()
+        });
+        for dropdown in [&type_dropdown, &status_dropdown, &period_dropdown] {
+            let render = render.clone();
+            dropdown.connect_selected_notify(move |_| render());
+        }
 
-        // Update periodically
-        glib::timeout_add_seconds_local(1, move || {
-            update_list();
-            glib::ControlFlow::Continue
+        Self::run_blocking(
+            || task_history::load_history(&task_history::history_dir()),
+            move |entries| {
+                placeholder.set_text("No finished tasks match");
+                *history.borrow_mut() = entries;
+                render();
+            },
+        );
+
+        window.present();
+    }
+
+    fn create_history_row(window: &Window, entry: &crate::task_history::ArchivedTask) -> ActionRow {
+        let row = ActionRow::new();
+        row.set_title(&format!(
+            "{}: {}",
+            entry.task_type.label(),
+            entry.package_name
+        ));
+        let finished = entry
+            .finished_at_unix
+            .map(|at| crate::schedule::format_local_time(at as i64))
+            .unwrap_or_default();
+        row.set_subtitle(&format!(
+            "{} \u{2022} {} \u{2022} {} lines",
+            entry.status.label(),
+            finished,
+            entry.lines
+        ));
+
+        let open_btn = Button::with_label("Open");
+        open_btn.add_css_class("flat");
+        open_btn.set_valign(gtk4::Align::Center);
+        let parent = window.clone();
+        let opened = entry.clone();
+        open_btn.connect_clicked(move |_| Self::show_archived_log(&parent, opened.clone()));
+        row.add_suffix(&open_btn);
+
+        let export_btn = Button::with_label("Export...");
+        export_btn.add_css_class("flat");
+        export_btn.set_valign(gtk4::Align::Center);
+        let parent = window.clone();
+        let exported = entry.clone();
+        export_btn.connect_clicked(move |_| {
+            let chooser = gtk4::FileChooserNative::new(
+                Some("Export Task Log"),
+                Some(&parent),
+                gtk4::FileChooserAction::Save,
+                Some("Export"),
+                Some("Cancel"),
+            );
+            chooser.set_current_name(&format!(
+                "parut-{}-{}.log",
+                exported.package_name.replace(' ', "_"),
+                exported.id
+            ));
+            let entry = exported.clone();
+            let parent = parent.clone();
+            chooser.connect_response(move |chooser, response| {
+                if response == gtk4::ResponseType::Accept
+                    && let Some(path) = chooser.file().and_then(|f| f.path())
+                    && let Err(e) = crate::task_history::export_log(&entry, &path)
+                {
+                    log_error(&format!("Failed to export {}: {}", path.display(), e));
+                    let dialog = adw::AlertDialog::new(
+                        Some("Export Failed"),
+                        Some(&format!("Could not write {}: {}", path.display(), e)),
+                    );
+                    dialog.add_response("ok", "OK");
+                    dialog.present(Some(&parent));
+                }
+                chooser.destroy();
+            });
+            chooser.show();
         });
+        row.add_suffix(&export_btn);
+        row
+    }
+
+    fn show_archived_log(parent: &Window, entry: crate::task_history::ArchivedTask) {
+        let window = Window::builder()
+            .title(format!(
+                "{}: {}",
+                entry.task_type.label(),
+                entry.package_name
+            ))
+            .default_width(800)
+            .default_height(600)
+            .transient_for(parent)
+            .build();
+        let scrolled = ScrolledWindow::new();
+        let text_view = TextView::new();
+        text_view.set_editable(false);
+        text_view.set_monospace(true);
+        text_view.set_margin_start(12);
+        text_view.set_margin_end(12);
+        text_view.set_margin_top(12);
+        text_view.set_margin_bottom(12);
+        text_view.buffer().set_text("Loading...");
+        scrolled.set_child(Some(&text_view));
+        window.set_child(Some(&scrolled));
 
+        Self::run_blocking(
+            move || crate::task_history::read_log(&entry),
+            move |result| match result {
+                Ok(text) => text_view.buffer().set_text(&text),
+                Err(e) => text_view
+                    .buffer()
+                    .set_text(&format!("Could not read the archived output: {}", e)),
+            },
+        );
         window.present();
     }
 
+    fn fill_schedule_list(list: &ListBox, schedules: &[crate::schedule::Schedule]) {
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
+        }
+        let now = chrono::Local::now().timestamp();
+        for schedule in schedules {
+            let row = ActionRow::new();
+            row.set_title(&format!(
+                "{}: {}",
+                schedule.task_type.label(),
+                schedule.package_name
+            ));
+            row.set_subtitle(&format!(
+                "{} \u{2022} next run {}",
+                schedule.recurrence.label(),
+                crate::schedule::describe_next_run(schedule.next_run, now)
+            ));
+            row.add_prefix(&Image::from_icon_name("alarm-symbolic"));
+
+            let remove_btn = Button::from_icon_name("user-trash-symbolic");
+            remove_btn.add_css_class("flat");
+            remove_btn.set_valign(gtk4::Align::Center);
+            remove_btn.set_tooltip_text(Some("Delete this schedule"));
+            let id = schedule.id;
+            remove_btn.connect_clicked(move |_| crate::data_store::remove_schedule(id));
+            row.add_suffix(&remove_btn);
+            list.append(&row);
+        }
+    }
+
+    fn show_schedule_dialog(parent: &Window) {
+        // Maintenance tasks that don't need a package name.
+        const TASKS: [(&str, TaskType); 3] = [
+            ("System Update", TaskType::Update),
+            ("Clean Cache", TaskType::CleanCache),
+            ("Remove Orphans", TaskType::RemoveOrphans),
+        ];
+        let dialog = Window::builder()
+            .title("Schedule Task")
+            .default_width(420)
+            .modal(true)
+            .transient_for(parent)
+            .build();
+
+        let vbox = Box::new(Orientation::Vertical, 12);
+        vbox.set_margin_start(16);
+        vbox.set_margin_end(16);
+        vbox.set_margin_top(16);
+        vbox.set_margin_bottom(16);
+
+        let task_names: Vec<&str> = TASKS.iter().map(|(label, _)| *label).collect();
+        let task_dropdown =
+            DropDown::new(Some(StringList::new(&task_names)), None::<gtk4::Expression>);
+        let recurrence_names: Vec<&str> = crate::schedule::Recurrence::ALL
+            .iter()
+            .map(|r| r.label())
+            .collect();
+        let recurrence_dropdown = DropDown::new(
+            Some(StringList::new(&recurrence_names)),
+            None::<gtk4::Expression>,
+        );
+        recurrence_dropdown.set_selected(1);
+
+        // Default to 03:00 tomorrow, when the machine is most likely idle.
+        let tomorrow = chrono::Local::now().date_naive() + chrono::Days::new(1);
+        let time_entry = Entry::new();
+        time_entry.set_text(&format!("{} 03:00", tomorrow.format("%Y-%m-%d")));
+        time_entry.set_placeholder_text(Some("YYYY-MM-DD HH:MM"));
+
+        for (title, widget) in [
+            ("Task", task_dropdown.clone().upcast::<gtk4::Widget>()),
+            ("Repeat", recurrence_dropdown.clone().upcast()),
+            ("First run", time_entry.clone().upcast()),
+        ] {
+            let row = Box::new(Orientation::Horizontal, 12);
+            let label = Label::new(Some(title));
+            label.set_halign(gtk4::Align::Start);
+            label.set_hexpand(true);
+            row.append(&label);
+            widget.set_width_request(200);
+            row.append(&widget);
+            vbox.append(&row);
+        }
+
+        let error_label = Label::new(None);
+        error_label.add_css_class("error");
+        error_label.add_css_class("caption");
+        error_label.set_halign(gtk4::Align::Start);
+        error_label.set_visible(false);
+        vbox.append(&error_label);
+
+        let buttons = Box::new(Orientation::Horizontal, 8);
+        buttons.set_halign(gtk4::Align::End);
+        let cancel = Button::with_label("Cancel");
+        let confirm = Button::with_label("Schedule");
+        confirm.add_css_class("suggested-action");
+        buttons.append(&cancel);
+        buttons.append(&confirm);
+        vbox.append(&buttons);
+        dialog.set_child(Some(&vbox));
+
+        let dialog_weak = dialog.downgrade();
+        cancel.connect_clicked(move |_| {
+            if let Some(d) = dialog_weak.upgrade() {
+                d.close();
+            }
+        });
+
+        let dialog_weak = dialog.downgrade();
+        confirm.connect_clicked(move |_| {
+            let Some(first_run) = crate::schedule::parse_local_time(&time_entry.text()) else {
+                error_label.set_text("Enter the time as YYYY-MM-DD HH:MM");
+                error_label.set_visible(true);
+                return;
+            };
+            let recurrence =
+                crate::schedule::Recurrence::ALL[(recurrence_dropdown.selected() as usize).min(2)];
+            if recurrence == crate::schedule::Recurrence::Once
+                && first_run <= chrono::Local::now().timestamp()
+            {
+                error_label.set_text("That time has already passed");
+                error_label.set_visible(true);
+                return;
+            }
+            let (_, task_type) = &TASKS[(task_dropdown.selected() as usize).min(TASKS.len() - 1)];
+            crate::data_store::add_schedule(
+                task_type.clone(),
+                "system".to_string(),
+                recurrence,
+                first_run,
+            );
+            log_info(&format!(
+                "Scheduled {} ({}) from {}",
+                task_type.label(),
+                recurrence.label(),
+                crate::schedule::format_local_time(first_run)
+            ));
+            if let Some(d) = dialog_weak.upgrade() {
+                d.close();
+            }
+        });
+
+        dialog.present();
+    }
+
     fn create_task_row(task: &crate::task_queue::Task, task_queue: Arc<TaskQueue>) -> Box {
         let row_box = Box::new(Orientation::Vertical, 8);
         row_box.set_margin_start(16);
@@ -1706,19 +3182,16 @@ impl ParuGui {
             TaskType::UpdatePackage => "software-update-urgent-symbolic",
             TaskType::CleanCache => "user-trash-symbolic",
             TaskType::RemoveOrphans => "edit-clear-all-symbolic",
+            TaskType::InstallBuildDeps => "document-save-symbolic",
+            TaskType::BuildAur | TaskType::BuildLocal => "applications-engineering-symbolic",
+            TaskType::InstallBuilt | TaskType::InstallFile => "package-x-generic-symbolic",
+            TaskType::Downgrade => "edit-undo-symbolic",
         };
         let task_icon = Image::from_icon_name(icon_name);
         task_icon.set_pixel_size(20);
         header_box.append(&task_icon);
 
-        let task_type_str = match task.task_type {
-            TaskType::Install => "Install",
-            TaskType::Remove => "Remove",
-            TaskType::Update => "Update",
-            TaskType::UpdatePackage => "Update Package",
-            TaskType::CleanCache => "Clean Cache",
-            TaskType::RemoveOrphans => "Remove Orphans",
-        };
+        let task_type_str = task.task_type.label();
 
         let title_label = Label::new(Some(&format!("{}: {}", task_type_str, task.package_name)));
         title_label.add_css_class("heading");
@@ -1726,6 +3199,14 @@ impl ParuGui {
         title_label.set_hexpand(true);
         header_box.append(&title_label);
 
+        if let Some(routine) = &task.routine {
+            let routine_label = Label::new(Some(routine));
+            routine_label.add_css_class("caption");
+            routine_label.add_css_class("dim-label");
+            routine_label.set_tooltip_text(Some("Step of a routine"));
+            header_box.append(&routine_label);
+        }
+
         // Status indicator
         let status_box = Box::new(Orientation::Horizontal, 6);
 
@@ -1773,61 +3254,83 @@ impl ParuGui {
                 label.add_css_class("error");
                 status_box.append(&label);
             }
+            TaskStatus::Interrupted => {
+                let icon = Image::from_icon_name("dialog-warning-symbolic");
+                icon.set_pixel_size(16);
+                icon.add_css_class("warning");
+                status_box.append(&icon);
+                let label = Label::new(Some("Interrupted"));
+                label.add_css_class("warning");
+                status_box.append(&label);
+                status_box.set_tooltip_text(Some(
+                    "Parut exited while this task was running. It may have partly completed.",
+                ));
+            }
         }
 
         header_box.append(&status_box);
         row_box.append(&header_box);
 
-        // Progress bar if task is running
+        // Progress of the current phase, with whatever the output told us about it
         if task.status == TaskStatus::Running {
-            if let Some(progress) = task.progress {
-                let progress_bar = ProgressBar::new();
-                progress_bar.set_fraction(progress);
-                progress_bar.set_show_text(true);
-                progress_bar.set_text(Some(&format!("{}%", (progress * 100.0) as i32)));
-                row_box.append(&progress_bar);
-
-                let phase_eta = Box::new(Orientation::Horizontal, 8);
-                phase_eta.set_halign(gtk4::Align::Start);
-
-                if let Some(phase) = &task.phase {
-                    let phase_label = Label::new(Some(&format!("Phase: {}", phase)));
-                    phase_label.add_css_class("caption");
-                    phase_label.add_css_class("dim-label");
-                    phase_eta.append(&phase_label);
-                }
-
-                if let Some(started_at) = task.started_at_unix
-                    && progress > 0.01
-                {
-                    let now = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .ok()
-                        .map(|d| d.as_secs())
-                        .unwrap_or(started_at);
-                    let elapsed = now.saturating_sub(started_at) as f64;
-                    let eta_secs = ((elapsed / progress) * (1.0 - progress)).max(0.0) as u64;
-                    let eta_label =
-                        Label::new(Some(&format!("ETA: {}", Self::format_duration(eta_secs))));
-                    eta_label.add_css_class("caption");
-                    eta_label.add_css_class("dim-label");
-                    phase_eta.append(&eta_label);
+            let progress_bar = ProgressBar::new();
+            match task.progress {
+                Some(progress) => {
+                    progress_bar.set_fraction(progress);
+                    progress_bar.set_show_text(true);
+                    progress_bar.set_text(Some(&format!("{}%", (progress * 100.0) as i32)));
                 }
+                None => progress_bar.pulse(),
+            }
+            row_box.append(&progress_bar);
 
-                row_box.append(&phase_eta);
-            } else {
-                let progress_bar = ProgressBar::new();
-                progress_bar.pulse();
-                row_box.append(&progress_bar);
-
-                if let Some(phase) = &task.phase {
-                    let phase_label = Label::new(Some(&format!("Phase: {}", phase)));
-                    phase_label.add_css_class("caption");
-                    phase_label.add_css_class("dim-label");
-                    phase_label.set_halign(gtk4::Align::Start);
-                    row_box.append(&phase_label);
+            let details = &task.progress_details;
+            let mut facts: Vec<String> = Vec::new();
+            if let Some(phase) = &task.phase {
+                facts.push(phase.clone());
+            }
+            if let Some(package) = &details.package {
+                facts.push(package.clone());
+            }
+            if let Some((current, total)) = details.step {
+                facts.push(format!("{} of {}", current, total));
+            }
+            if let Some((done, total)) = details.bytes {
+                facts.push(format!(
+                    "{} of {}",
+                    Self::format_bytes(done),
+                    Self::format_bytes(total)
+                ));
+            }
+            if let Some(rate) = details.rate {
+                facts.push(format!("{}/s", Self::format_bytes(rate)));
+            }
+            if let Some(started_at) = task.started_at_unix {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .ok()
+                    .map(|d| d.as_secs())
+                    .unwrap_or(started_at);
+                let past_runs = crate::data_store::run_durations(&task.task_type);
+                let phase_started_at = task.phase_started_at_unix.unwrap_or(started_at);
+                if let Some(left) = crate::progress::estimate_remaining(
+                    now.saturating_sub(started_at),
+                    now.saturating_sub(phase_started_at),
+                    task.progress,
+                    &past_runs,
+                ) {
+                    facts.push(format!("About {} left", Self::format_duration(left)));
                 }
             }
+            if !facts.is_empty() {
+                let facts_label = Label::new(Some(&facts.join(" · ")));
+                facts_label.add_css_class("caption");
+                facts_label.add_css_class("dim-label");
+                facts_label.set_halign(gtk4::Align::Start);
+                facts_label.set_xalign(0.0);
+                facts_label.set_wrap(true);
+                row_box.append(&facts_label);
+            }
         }
 
         // Show error message if failed
@@ -1841,19 +3344,73 @@ impl ParuGui {
             error_icon.set_margin_start(8);
             error_box.append(&error_icon);
 
-            let error_label = Label::new(Some(&format!("Error: {}", error)));
+            let error_text = Box::new(Orientation::Vertical, 2);
+            error_text.set_margin_top(6);
+            error_text.set_margin_bottom(6);
+            error_text.set_margin_end(8);
+
+            let heading = match &task.error {
+                Some(err) => format!("{}: {}", err.kind.label(), err),
+                None => format!("Error: {}", error),
+            };
+            let error_label = Label::new(Some(&heading));
             error_label.add_css_class("error");
             error_label.set_halign(gtk4::Align::Start);
             error_label.set_wrap(true);
-            error_label.set_margin_end(8);
-            error_box.append(&error_label);
+            error_text.append(&error_label);
+
+            if let Some(err) = &task.error {
+                if let Some(hint) = err.kind.recovery_hint() {
+                    let hint_label = Label::new(Some(hint));
+                    hint_label.add_css_class("caption");
+                    hint_label.add_css_class("dim-label");
+                    hint_label.set_halign(gtk4::Align::Start);
+                    hint_label.set_wrap(true);
+                    error_text.append(&hint_label);
+                }
+                error_box.set_tooltip_text(Some(&Self::backend_error_details(err)));
+            }
 
+            error_box.append(&error_text);
             row_box.append(&error_box);
         }
 
+        let diagnosis = matches!(task.status, TaskStatus::Failed(_))
+            .then(|| crate::diagnosis::diagnose(&task.output))
+            .flatten();
+        if let Some(diagnosis) = &diagnosis {
+            let diagnosis_box = Box::new(Orientation::Horizontal, 8);
+            diagnosis_box.add_css_class("card");
+
+            let hint_icon = Image::from_icon_name("dialog-information-symbolic");
+            hint_icon.set_margin_start(8);
+            diagnosis_box.append(&hint_icon);
+
+            let diagnosis_text = Box::new(Orientation::Vertical, 2);
+            diagnosis_text.set_margin_top(6);
+            diagnosis_text.set_margin_bottom(6);
+            diagnosis_text.set_margin_end(8);
+            let title = Label::new(Some(diagnosis.title));
+            title.add_css_class("heading");
+            title.set_halign(gtk4::Align::Start);
+            diagnosis_text.append(&title);
+            let explanation = Label::new(Some(&diagnosis.explanation));
+            explanation.add_css_class("caption");
+            explanation.set_halign(gtk4::Align::Start);
+            explanation.set_wrap(true);
+            explanation.set_xalign(0.0);
+            diagnosis_text.append(&explanation);
+            diagnosis_box.append(&diagnosis_text);
+
+            row_box.append(&diagnosis_box);
+        }
+
         // Show last few output lines if available
         if !task.output.is_empty()
-            && matches!(task.status, TaskStatus::Running | TaskStatus::Failed(_))
+            && matches!(
+                task.status,
+                TaskStatus::Running | TaskStatus::Failed(_) | TaskStatus::Interrupted
+            )
         {
             let output_box = Box::new(Orientation::Vertical, 2);
             output_box.add_css_class("card");
@@ -1929,8 +3486,26 @@ impl ParuGui {
                 controls.append(&cancel_btn);
             }
             TaskStatus::Failed(_) => {
+                if let Some(diagnosis) = diagnosis
+                    && diagnosis.remedy != Remedy::Retry
+                {
+                    let fix_btn = Button::with_label(diagnosis.remedy.label());
+                    fix_btn.add_css_class("suggested-action");
+                    let task_id = task.id;
+                    let tq = task_queue.clone();
+                    fix_btn.connect_clicked(move |btn| {
+                        Self::apply_remedy(&diagnosis.remedy, task_id, &tq, btn);
+                    });
+                    controls.append(&fix_btn);
+                }
+
                 let retry_btn = Button::with_label("Retry");
-                retry_btn.add_css_class("suggested-action");
+                // Only push retrying when the failure looks transient.
+                if task.error.as_ref().is_none_or(BackendError::is_retryable) {
+                    retry_btn.add_css_class("suggested-action");
+                } else {
+                    retry_btn.add_css_class("flat");
+                }
                 let task_id = task.id;
                 let tq = task_queue.clone();
                 retry_btn.connect_clicked(move |_| {
@@ -1938,6 +3513,16 @@ impl ParuGui {
                 });
                 controls.append(&retry_btn);
             }
+            TaskStatus::Interrupted => {
+                let requeue_btn = Button::with_label("Re-queue");
+                requeue_btn.add_css_class("suggested-action");
+                let task_id = task.id;
+                let tq = task_queue.clone();
+                requeue_btn.connect_clicked(move |_| {
+                    let _ = tq.requeue_interrupted_task(task_id);
+                });
+                controls.append(&requeue_btn);
+            }
             TaskStatus::Completed | TaskStatus::Canceled => {}
         }
 
@@ -1946,6 +3531,80 @@ impl ParuGui {
         row_box
     }
 
+    fn apply_remedy(remedy: &Remedy, task_id: usize, task_queue: &Arc<TaskQueue>, button: &Button) {
+        match remedy {
+            Remedy::Retry => {
+                let _ = task_queue.retry_failed_task(task_id);
+            }
+            Remedy::QueueThenRetry {
+                task_type,
+                package_name,
+                ..
+            } => {
+                // Retrying is pointless if the fix was turned down.
+                if let Some(fix_id) = task_queue
+                    .add_task(task_type.clone(), package_name.clone())
+                    .task_id()
+                {
+                    let _ = task_queue.retry_failed_task_after(task_id, fix_id);
+                }
+            }
+            Remedy::ImportKey(key_id) => {
+                button.set_sensitive(false);
+                let key_id = key_id.clone();
+                let tq = task_queue.clone();
+                let button = button.clone();
+                Self::run_blocking(
+                    move || ParuBackend::import_pgp_key(&key_id),
+                    move |result| match result {
+                        Ok(()) => {
+                            let _ = tq.retry_failed_task(task_id);
+                        }
+                        Err(e) => {
+                            log_error(&format!("{}", e));
+                            button.set_sensitive(true);
+                            let dialog = adw::AlertDialog::new(
+                                Some("Key Not Imported"),
+                                Some(&e.to_string()),
+                            );
+                            dialog.add_response("ok", "OK");
+                            dialog.present(Some(&button));
+                        }
+                    },
+                );
+            }
+            Remedy::CopyText { text, .. } => {
+                button.clipboard().set_text(text);
+                crate::utils::send_notification("Parut", "Copied to clipboard");
+            }
+        }
+    }
+
+    /// Command, exit code and the end of the error output, for the failed-task tooltip.
+    fn backend_error_details(err: &BackendError) -> String {
+        let mut lines = Vec::new();
+        if let Some(command) = &err.command {
+            lines.push(format!("Command: {}", command));
+        }
+        if let Some(code) = err.exit_code {
+            lines.push(format!("Exit code: {}", code));
+        }
+        let stderr: Vec<&str> = err.stderr.lines().collect();
+        if !stderr.is_empty() {
+            lines.push(String::new());
+            lines.extend(
+                stderr[stderr.len().saturating_sub(6)..]
+                    .iter()
+                    .map(|l| l.to_string()),
+            );
+        }
+        if lines.is_empty() {
+            err.to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+
     fn create_search_view(task_queue: Arc<TaskQueue>) -> (Box, PackageVec, ListBoxRc) {
         let vbox = Box::new(Orientation::Vertical, 16);
         vbox.set_margin_start(20);
@@ -2053,6 +3712,8 @@ impl ParuGui {
         let trending_box_clone = trending_box.clone();
         let search_entry_for_suggestions = search_entry.clone();
         let selected_search_clone = selected_search.clone();
+        let search_generation = Rc::new(Cell::new(0u64));
+        let search_generation_clone = search_generation.clone();
 
         // Store the timeout ID
         let timeout_id: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
@@ -2129,9 +3790,12 @@ impl ParuGui {
                     let render = render.clone();
                     let all_packages = all_packages.clone();
                     move || {
-                        for pkg in &to_queue {
+                        let (aur, repo): (Vec<&Package>, Vec<&Package>) =
+                            to_queue.iter().partition(|p| p.repository == "aur");
+                        for pkg in repo {
                             tq.add_task(TaskType::Install, pkg.name.clone());
                         }
+                        tq.add_aur_installs(aur.iter().map(|p| p.name.clone()).collect());
                         selected.borrow_mut().clear();
                         render(&all_packages.borrow());
                     }
@@ -2149,10 +3813,17 @@ impl ParuGui {
                         } else {
                             "Install all selected packages?"
                         };
-                        Self::show_confirmation_dialog(
+                        let (aur, repo): (Vec<&Package>, Vec<&Package>) =
+                            selected_pkgs.iter().partition(|p| p.repository == "aur");
+                        let request = PreviewRequest::Install {
+                            repo: repo.iter().map(|p| p.name.clone()).collect(),
+                            aur: aur.iter().map(|p| p.name.clone()).collect(),
+                        };
+                        Self::show_preview_confirmation(
                             &window,
                             "Batch Install Confirmation",
                             msg,
+                            Some(request),
                             queue_install,
                         );
                     }
@@ -2164,6 +3835,7 @@ impl ParuGui {
 
         search_entry.connect_search_changed(move |entry| {
             let query = entry.text().to_string();
+            search_generation_clone.set(search_generation_clone.get().wrapping_add(1));
 
             // Cancel previous timeout if any
             if let Some(id) = timeout_id_clone.borrow_mut().take() {
@@ -2209,47 +3881,132 @@ impl ParuGui {
             let render_for_timeout = render_search_results_clone.clone();
 
             let timeout_id_inner = timeout_id_clone.clone();
+            // Results of an older query may still arrive; only the latest one is shown.
+            let still_current = {
+                let search_generation = search_generation_clone.clone();
+                let generation = search_generation.get();
+                move || search_generation.get() == generation
+            };
 
             // Debounce: wait 300ms before searching
             let id = glib::timeout_add_local_once(Duration::from_millis(300), move || {
                 // Clear the ID as it is executing so we don't try to remove it later
                 let _ = timeout_id_inner.borrow_mut().take();
+                let limit = crate::settings::get().search_result_limit;
+
+                let show_results: Rc<dyn Fn(Vec<Package>, bool)> = {
+                    let packages = packages.clone();
+                    let loading = loading.clone();
+                    let hint = hint.clone();
+                    let selected = selected_for_timeout.clone();
+                    Rc::new(move |results: Vec<Package>, complete: bool| {
+                        let sort_idx = sort_dropdown.selected();
+                        let sorted = Self::filter_and_sort_packages(&results, "", sort_idx);
+                        loading.borrow().set_visible(!complete);
+                        hint.borrow().set_visible(true);
+                        let text = match (sorted.is_empty(), complete) {
+                            (true, true) => "No packages found matching your query.".to_string(),
+                            (true, false) => {
+                                "No repository packages found, searching AUR...".to_string()
+                            }
+                            (false, true) => format!("Found {} packages", sorted.len()),
+                            (false, false) => format!(
+                                "Found {} packages in repositories, searching AUR...",
+                                sorted.len()
+                            ),
+                        };
+                        hint.borrow().set_text(&text);
+                        // Selections made while AUR results were loading survive the merge.
+                        selected
+                            .borrow_mut()
+                            .retain(|name| sorted.iter().any(|p| &p.name == name));
+                        *packages.borrow_mut() = sorted.clone();
+                        render_for_timeout(&sorted);
+                    })
+                };
 
+                let query_for_aur = query_for_search.clone();
                 Self::run_blocking(
                     move || {
-                        let limit = crate::settings::get().search_result_limit;
-                        Self::smart_search_packages(&query_for_search, limit)
+                        Self::smart_search_packages(
+                            &query_for_search,
+                            limit,
+                            ParuBackend::search_repo_packages,
+                        )
                     },
-                    move |result| match result {
-                        Ok(results) => {
-                            crate::data_store::record_search(&query_for_record);
-                            Self::populate_search_suggestions(
-                                &recent_box_for_timeout,
-                                &trending_box_for_timeout,
-                                &search_entry_for_timeout,
-                            );
-                            let sort_idx = sort_dropdown.selected();
-                            let sorted = Self::filter_and_sort_packages(&results, "", sort_idx);
-                            loading.borrow().set_visible(false);
-                            if sorted.is_empty() {
-                                hint.borrow().set_visible(true);
-                                hint.borrow()
-                                    .set_text("No packages found matching your query.");
-                            } else {
-                                hint.borrow().set_visible(true);
-                                hint.borrow()
-                                    .set_text(&format!("Found {} packages", sorted.len()));
-                            }
-                            *packages.borrow_mut() = sorted.clone();
-                            selected_for_timeout.borrow_mut().clear();
-                            render_for_timeout(&sorted);
+                    move |repo_result| {
+                        if !still_current() {
+                            return;
                         }
-                        Err(e) => {
-                            loading.borrow().set_visible(false);
-                            hint.borrow().set_visible(true);
-                            hint.borrow().set_text(&format!("Search error: {}", e));
-                            log_error(&format!("Search failed: {}", e));
+                        selected_for_timeout.borrow_mut().clear();
+
+                        // Without readable sync databases, fall back to a full paru search.
+                        let offline = repo_result.is_ok();
+                        let repo = repo_result.unwrap_or_else(|e| {
+                            log_warning(&format!("Offline search unavailable: {}", e));
+                            Vec::new()
+                        });
+                        if offline {
+                            show_results(repo.clone(), false);
                         }
+
+                        Self::run_blocking(
+                            move || {
+                                if offline {
+                                    Self::smart_search_packages(
+                                        &query_for_aur,
+                                        limit,
+                                        ParuBackend::search_aur_packages,
+                                    )
+                                } else {
+                                    Self::smart_search_packages(
+                                        &query_for_aur,
+                                        limit,
+                                        ParuBackend::search_packages,
+                                    )
+                                }
+                                .map(|results| {
+                                    Self::merge_search_results(
+                                        &repo,
+                                        results,
+                                        &query_for_aur,
+                                        limit,
+                                    )
+                                })
+                                .map_err(|e| (e, repo))
+                            },
+                            move |result| {
+                                if !still_current() {
+                                    return;
+                                }
+                                match result {
+                                    Ok(results) => {
+                                        crate::data_store::record_search(&query_for_record);
+                                        Self::populate_search_suggestions(
+                                            &recent_box_for_timeout,
+                                            &trending_box_for_timeout,
+                                            &search_entry_for_timeout,
+                                        );
+                                        show_results(results, true);
+                                    }
+                                    Err((e, repo)) if !repo.is_empty() => {
+                                        log_error(&format!("AUR search failed: {}", e));
+                                        let count = repo.len();
+                                        show_results(repo, true);
+                                        hint.borrow().set_text(&format!(
+                                            "Found {} repository packages (AUR search failed: {})",
+                                            count, e
+                                        ));
+                                    }
+                                    Err((e, _)) => {
+                                        loading.borrow().set_visible(false);
+                                        hint.borrow().set_visible(true);
+                                        hint.borrow().set_text(&format!("Search error: {}", e));
+                                        log_error(&format!("Search failed: {}", e));
+                                    }
+                                }
+                            },
+                        );
                     },
                 );
             });
@@ -2458,10 +4215,11 @@ impl ParuGui {
                 if needs_confirm {
                     if let Some(window) = btn.root().and_then(|w| w.downcast::<gtk4::Window>().ok())
                     {
-                        Self::show_confirmation_dialog(
+                        Self::show_preview_confirmation(
                             &window,
                             "Confirm Batch Removal",
                             &format!("Remove {} selected packages?", selected_count),
+                            Some(PreviewRequest::Remove(selected_names)),
                             queue_remove,
                         );
                     }
@@ -2538,25 +4296,7 @@ impl ParuGui {
 
         let task_queue_clone = task_queue.clone();
         update_all_btn.connect_clicked(move |btn| {
-            let needs_confirm =
-                crate::settings::get().confirm_update_all || crate::settings::get().confirm_actions;
-            if needs_confirm {
-                if let Some(window) = btn.root().and_then(|w| w.downcast::<gtk4::Window>().ok()) {
-                    let tq_confirm = task_queue_clone.clone();
-                    Self::show_confirmation_dialog(
-                        &window,
-                        "Confirm System Update",
-                        "Update all packages now?",
-                        move || {
-                            log_info("Starting system update");
-                            tq_confirm.add_task(TaskType::Update, "system".to_string());
-                        },
-                    );
-                }
-            } else {
-                log_info("Starting system update");
-                task_queue_clone.add_task(TaskType::Update, "system".to_string());
-            }
+            Self::start_system_update(btn, task_queue_clone.clone(), "Starting system update");
         });
 
         header_box.append(&clear_selection_btn);
@@ -2897,13 +4637,18 @@ impl ParuGui {
                     let actions = Box::new(Orientation::Horizontal, 8);
                     let details_btn = Button::with_label("Details");
                     let name_for_details = pkg_name.clone();
+                    let tq_details = task_queue.clone();
                     let row_weak = row.downgrade();
                     details_btn.connect_clicked(move |_| {
                         if let Some(r) = row_weak.upgrade()
                             && let Some(window) =
                                 r.root().and_then(|w| w.downcast::<gtk4::Window>().ok())
                         {
-                            Self::show_package_details_dialog(&window, &name_for_details);
+                            Self::show_package_details_dialog(
+                                &window,
+                                &name_for_details,
+                                tq_details.clone(),
+                            );
                         }
                     });
                     actions.append(&details_btn);
@@ -2921,10 +4666,17 @@ impl ParuGui {
 
                     let unwatch = Button::with_label("Unwatch");
                     let name_for_unwatch = pkg_name.clone();
+                    let list_for_unwatch = list_box.clone();
+                    let row_for_unwatch = row.clone();
+                    let count_for_unwatch = count_label.clone();
                     unwatch.connect_clicked(move |_| {
                         if crate::data_store::is_favorite(&name_for_unwatch) {
                             let _ = crate::data_store::toggle_favorite(&name_for_unwatch);
                         }
+                        list_for_unwatch.remove(&row_for_unwatch);
+                        count_for_unwatch
+                            .borrow()
+                            .set_text(&format!("{} items", crate::data_store::favorites().len()));
                     });
                     actions.append(&unwatch);
 
@@ -2936,11 +4688,9 @@ impl ParuGui {
 
         render_watchlist();
         {
+            // Favorites can change from any package list; catch up when the page is shown.
             let refresh = render_watchlist.clone();
-            glib::timeout_add_seconds_local(2, move || {
-                refresh();
-                glib::ControlFlow::Continue
-            });
+            vbox.connect_map(move |_| refresh());
         }
 
         (vbox, render_watchlist)
@@ -3072,7 +4822,10 @@ impl ParuGui {
 
         info_box.append(&version_box);
         if crate::settings::get().show_package_sizes_in_lists
-            && let Some(size_text) = Self::query_package_size_text(&package.name)
+            && let Some(size_text) = package
+                .installed_size
+                .map(|bytes| format!("Size: {}", Self::format_bytes(bytes)))
+                .or_else(|| Self::query_package_size_text(&package.name))
         {
             let size_label = Label::new(Some(&size_text));
             size_label.add_css_class("caption");
@@ -3096,6 +4849,7 @@ impl ParuGui {
 
         if crate::settings::get().show_package_details_on_single_click {
             let pkg_name_click = package.name.clone();
+            let tq_details = task_queue.clone();
             let info_box_weak = info_box.downgrade();
             let click = gtk4::GestureClick::new();
             click.set_button(1);
@@ -3104,7 +4858,7 @@ impl ParuGui {
                     && let Some(window) =
                         info.root().and_then(|w| w.downcast::<gtk4::Window>().ok())
                 {
-                    Self::show_package_details_dialog(&window, &pkg_name_click);
+                    Self::show_package_details_dialog(&window, &pkg_name_click, tq_details.clone());
                 }
             });
             info_box.add_controller(click);
@@ -3126,12 +4880,13 @@ impl ParuGui {
             info_btn.set_tooltip_text(Some("View details"));
 
             let pkg_name_clone = package.name.clone();
+            let tq_details = task_queue.clone();
             let row_weak = row_box.downgrade();
             info_btn.connect_clicked(move |_| {
                 if let Some(row) = row_weak.upgrade()
                     && let Some(window) = row.root().and_then(|w| w.downcast::<gtk4::Window>().ok())
                 {
-                    Self::show_package_details_dialog(&window, &pkg_name_clone);
+                    Self::show_package_details_dialog(&window, &pkg_name_clone, tq_details.clone());
                 }
             });
             action_box.append(&info_btn);
@@ -3157,10 +4912,11 @@ impl ParuGui {
                         {
                             let tq = task_queue.clone();
                             let pkg = pkg_name.clone();
-                            Self::show_confirmation_dialog(
+                            Self::show_preview_confirmation(
                                 &window,
                                 "Confirm Package Removal",
                                 &format!("Remove package '{}'?", pkg_name),
+                                Some(PreviewRequest::Remove(vec![pkg_name.clone()])),
                                 move || {
                                     log_info(&format!("Adding remove task for package: {}", pkg));
                                     tq.add_task(TaskType::Remove, pkg.clone());
@@ -3220,12 +4976,13 @@ impl ParuGui {
             info_btn.set_tooltip_text(Some("View details"));
 
             let pkg_name_clone = package.name.clone();
+            let tq_details = task_queue.clone();
             let row_weak = row_box.downgrade();
             info_btn.connect_clicked(move |_| {
                 if let Some(row) = row_weak.upgrade()
                     && let Some(window) = row.root().and_then(|w| w.downcast::<gtk4::Window>().ok())
                 {
-                    Self::show_package_details_dialog(&window, &pkg_name_clone);
+                    Self::show_package_details_dialog(&window, &pkg_name_clone, tq_details.clone());
                 }
             });
             action_box.append(&info_btn);
@@ -3313,7 +5070,10 @@ impl ParuGui {
         versions.append(&new_ver);
         info_box.append(&versions);
         if crate::settings::get().show_package_sizes_in_lists
-            && let Some(size_text) = Self::query_package_size_text(&package.name)
+            && let Some(size_text) = package
+                .installed_size
+                .map(|bytes| format!("Size: {}", Self::format_bytes(bytes)))
+                .or_else(|| Self::query_package_size_text(&package.name))
         {
             let size_label = Label::new(Some(&size_text));
             size_label.add_css_class("caption");
@@ -3335,12 +5095,13 @@ impl ParuGui {
         info_btn.add_css_class("circular");
         info_btn.set_tooltip_text(Some("View details"));
         let pkg_for_info = package.name.clone();
+        let tq_details = task_queue.clone();
         let row_weak = row_box.downgrade();
         info_btn.connect_clicked(move |_| {
             if let Some(row) = row_weak.upgrade()
                 && let Some(window) = row.root().and_then(|w| w.downcast::<gtk4::Window>().ok())
             {
-                Self::show_package_details_dialog(&window, &pkg_for_info);
+                Self::show_package_details_dialog(&window, &pkg_for_info, tq_details.clone());
             }
         });
         action_box.append(&info_btn);
@@ -3589,7 +5350,10 @@ impl ParuGui {
         }
 
         if crate::settings::get().show_package_sizes_in_lists
-            && let Some(size_text) = Self::query_package_size_text(&package.name)
+            && let Some(size_text) = package
+                .installed_size
+                .map(|bytes| format!("Size: {}", Self::format_bytes(bytes)))
+                .or_else(|| Self::query_package_size_text(&package.name))
         {
             let size_label = Label::new(Some(&size_text));
             size_label.add_css_class("caption");
@@ -3600,6 +5364,7 @@ impl ParuGui {
 
         if crate::settings::get().show_package_details_on_single_click {
             let pkg_name_click = package.name.clone();
+            let tq_details = task_queue.clone();
             let info_box_weak = info_box.downgrade();
             let click = gtk4::GestureClick::new();
             click.set_button(1);
@@ -3608,7 +5373,7 @@ impl ParuGui {
                     && let Some(window) =
                         info.root().and_then(|w| w.downcast::<gtk4::Window>().ok())
                 {
-                    Self::show_package_details_dialog(&window, &pkg_name_click);
+                    Self::show_package_details_dialog(&window, &pkg_name_click, tq_details.clone());
                 }
             });
             info_box.add_controller(click);
@@ -3631,12 +5396,13 @@ impl ParuGui {
         info_btn.set_tooltip_text(Some("View details"));
 
         let pkg_name_info = package.name.clone();
+        let tq_details = task_queue.clone();
         let row_weak = row_box.downgrade();
         info_btn.connect_clicked(move |_| {
             if let Some(row) = row_weak.upgrade()
                 && let Some(window) = row.root().and_then(|w| w.downcast::<gtk4::Window>().ok())
             {
-                Self::show_package_details_dialog(&window, &pkg_name_info);
+                Self::show_package_details_dialog(&window, &pkg_name_info, tq_details.clone());
             }
         });
         action_box.append(&info_btn);
@@ -3661,10 +5427,11 @@ impl ParuGui {
                 {
                     let tq = task_queue.clone();
                     let pkg = pkg_name.clone();
-                    Self::show_confirmation_dialog(
+                    Self::show_preview_confirmation(
                         &window,
                         "Confirm Package Removal",
                         &format!("Remove package '{}'?", pkg_name),
+                        Some(PreviewRequest::Remove(vec![pkg_name.clone()])),
                         move || {
                             log_info(&format!("Adding remove task for package: {}", pkg));
                             tq.add_task(TaskType::Remove, pkg.clone());
@@ -3715,7 +5482,7 @@ impl ParuGui {
             Err(e) => {
                 log_error(&format!("Error loading installed packages: {}", e));
                 if let (Some(label), Some(timer)) = (refresh_label_err, refresh_timer_err) {
-                    Self::set_refresh_stale_warning(&label, &timer, &e);
+                    Self::set_refresh_stale_warning(&label, &timer, &e.to_string());
                 }
             }
         });
@@ -3764,7 +5531,7 @@ impl ParuGui {
             Err(e) => {
                 log_error(&format!("Error loading updates: {}", e));
                 if let (Some(label), Some(timer)) = (refresh_label_err, refresh_timer_err) {
-                    Self::set_refresh_stale_warning(&label, &timer, &e);
+                    Self::set_refresh_stale_warning(&label, &timer, &e.to_string());
                 }
             }
         });
@@ -3921,8 +5688,15 @@ impl ParuGui {
         filtered
     }
 
-    fn smart_search_packages(query: &str, limit: usize) -> Result<Vec<Package>, String> {
-        let direct = ParuBackend::search_packages(query, Some(limit))?;
+    fn smart_search_packages<S>(
+        query: &str,
+        limit: usize,
+        search: S,
+    ) -> Result<Vec<Package>, BackendError>
+    where
+        S: Fn(&str, Option<usize>) -> Result<Vec<Package>, BackendError>,
+    {
+        let direct = search(query, Some(limit))?;
         if !direct.is_empty() {
             return Ok(Self::rank_packages_by_query(direct, query, limit));
         }
@@ -3934,10 +5708,24 @@ impl ParuGui {
         // Fallback for typo tolerance: broad search by prefix, then rank by similarity.
         let prefix: String = query.chars().take(3).collect();
         let fallback_limit = (limit.saturating_mul(3)).min(500);
-        let broad = ParuBackend::search_packages(&prefix, Some(fallback_limit))?;
+        let broad = search(&prefix, Some(fallback_limit))?;
         Ok(Self::rank_packages_by_query(broad, query, limit))
     }
 
+    /// Merge late AUR results into the repository results already on screen.
+    /// Repository packages win on name clashes, like paru's own resolution.
+    fn merge_search_results(
+        repo: &[Package],
+        aur: Vec<Package>,
+        query: &str,
+        limit: usize,
+    ) -> Vec<Package> {
+        let seen: HashSet<&str> = repo.iter().map(|p| p.name.as_str()).collect();
+        let mut merged = repo.to_vec();
+        merged.extend(aur.into_iter().filter(|p| !seen.contains(p.name.as_str())));
+        Self::rank_packages_by_query(merged, query, limit)
+    }
+
     fn rank_packages_by_query(
         mut packages: Vec<Package>,
         query: &str,
@@ -4056,6 +5844,427 @@ impl ParuGui {
         }
     }
 
+    /// Fill `list` with one row per saved item, given as title and subtitle,
+    /// each with buttons to edit it in `open_editor` or `remove` it from the
+    /// settings. `refill` is the caller, run again once the list changed.
+    fn fill_settings_list(
+        window: &Window,
+        list: &ListBox,
+        noun: &str,
+        rows: Vec<(String, String)>,
+        refill: fn(&Window, &ListBox),
+        open_editor: fn(&Window, usize, std::boxed::Box<dyn Fn()>),
+        remove: fn(&mut crate::settings::AppSettings, usize),
+    ) {
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
+        }
+        for (index, (title, subtitle)) in rows.into_iter().enumerate() {
+            let row = ActionRow::new();
+            row.add_css_class("settings-row");
+            row.set_title(&title);
+            row.set_subtitle(&subtitle);
+
+            let edit_btn = Button::from_icon_name("document-edit-symbolic");
+            edit_btn.add_css_class("flat");
+            edit_btn.set_valign(gtk4::Align::Center);
+            edit_btn.set_tooltip_text(Some(&format!("Edit {}", noun)));
+            let window_weak = window.downgrade();
+            let list_weak = list.downgrade();
+            edit_btn.connect_clicked(move |_| {
+                if let (Some(window), Some(list)) = (window_weak.upgrade(), list_weak.upgrade()) {
+                    let parent = window.clone();
+                    open_editor(
+                        &window,
+                        index,
+                        std::boxed::Box::new(move || refill(&parent, &list)),
+                    );
+                }
+            });
+            row.add_suffix(&edit_btn);
+
+            let delete_btn = Button::from_icon_name("user-trash-symbolic");
+            delete_btn.add_css_class("flat");
+            delete_btn.set_valign(gtk4::Align::Center);
+            delete_btn.set_tooltip_text(Some(&format!("Delete {}", noun)));
+            let window_weak = window.downgrade();
+            let list_weak = list.downgrade();
+            delete_btn.connect_clicked(move |_| {
+                crate::settings::update(|s| remove(s, index));
+                if let (Some(window), Some(list)) = (window_weak.upgrade(), list_weak.upgrade()) {
+                    refill(&window, &list);
+                }
+            });
+            row.add_suffix(&delete_btn);
+            list.append(&row);
+        }
+    }
+
+    /// Show `content` in a modal editor with Cancel and Save. `save` stores
+    /// the edited item, or returns what is missing to show below the content.
+    fn show_settings_editor<S>(parent: &Window, title: &str, width: i32, content: &Box, save: S)
+    where
+        S: Fn() -> Result<(), String> + 'static,
+    {
+        let dialog = Window::builder()
+            .title(title)
+            .default_width(width)
+            .modal(true)
+            .transient_for(parent)
+            .build();
+
+        let vbox = Box::new(Orientation::Vertical, 12);
+        vbox.set_margin_start(16);
+        vbox.set_margin_end(16);
+        vbox.set_margin_top(16);
+        vbox.set_margin_bottom(16);
+        vbox.append(content);
+
+        let error_label = Label::new(None);
+        error_label.add_css_class("error");
+        error_label.add_css_class("caption");
+        error_label.set_halign(gtk4::Align::Start);
+        error_label.set_visible(false);
+        vbox.append(&error_label);
+
+        let buttons = Box::new(Orientation::Horizontal, 8);
+        buttons.set_halign(gtk4::Align::End);
+        let cancel = Button::with_label("Cancel");
+        let save_btn = Button::with_label("Save");
+        save_btn.add_css_class("suggested-action");
+        buttons.append(&cancel);
+        buttons.append(&save_btn);
+        vbox.append(&buttons);
+        dialog.set_child(Some(&vbox));
+
+        let dialog_weak = dialog.downgrade();
+        cancel.connect_clicked(move |_| {
+            if let Some(d) = dialog_weak.upgrade() {
+                d.close();
+            }
+        });
+
+        let dialog_weak = dialog.downgrade();
+        save_btn.connect_clicked(move |_| match save() {
+            Ok(()) => {
+                if let Some(d) = dialog_weak.upgrade() {
+                    d.close();
+                }
+            }
+            Err(message) => {
+                error_label.set_text(&message);
+                error_label.set_visible(true);
+            }
+        });
+
+        dialog.present();
+    }
+
+    fn fill_routine_list(window: &Window, list: &ListBox) {
+        let rows = crate::settings::get()
+            .routines
+            .iter()
+            .map(|routine| {
+                let steps: Vec<&str> = routine.steps.iter().map(|s| s.task_type.label()).collect();
+                (routine.name.clone(), steps.join(" \u{2192} "))
+            })
+            .collect();
+        Self::fill_settings_list(
+            window,
+            list,
+            "routine",
+            rows,
+            Self::fill_routine_list,
+            |window, index, on_saved| Self::show_routine_editor(window, Some(index), on_saved),
+            |s, index| {
+                if index < s.routines.len() {
+                    s.routines.remove(index);
+                }
+            },
+        );
+    }
+
+    /// Create a routine (`index` is `None`) or edit the one at `index` in settings.
+    fn show_routine_editor<F>(parent: &Window, index: Option<usize>, on_saved: F)
+    where
+        F: Fn() + 'static,
+    {
+        const STEP_TYPES: [TaskType; 6] = [
+            TaskType::Update,
+            TaskType::UpdatePackage,
+            TaskType::Install,
+            TaskType::Remove,
+            TaskType::RemoveOrphans,
+            TaskType::CleanCache,
+        ];
+        fn takes_package(task_type: &TaskType) -> bool {
+            matches!(
+                task_type,
+                TaskType::UpdatePackage | TaskType::Install | TaskType::Remove
+            )
+        }
+        type StepWidgets = (Box, DropDown, Entry, CheckButton);
+
+        let existing = index.and_then(|i| crate::settings::get().routines.get(i).cloned());
+        let vbox = Box::new(Orientation::Vertical, 12);
+
+        let name_entry = Entry::new();
+        name_entry.set_placeholder_text(Some("Routine name"));
+        vbox.append(&name_entry);
+
+        let steps_box = Box::new(Orientation::Vertical, 6);
+        vbox.append(&steps_box);
+        let steps: Rc<RefCell<Vec<StepWidgets>>> = Rc::new(RefCell::new(Vec::new()));
+
+        let add_step = {
+            let steps_box = steps_box.clone();
+            let steps = steps.clone();
+            Rc::new(move |step: Option<&crate::settings::RoutineStep>| {
+                let row = Box::new(Orientation::Horizontal, 8);
+                let names: Vec<&str> = STEP_TYPES.iter().map(TaskType::label).collect();
+                let type_dropdown =
+                    DropDown::new(Some(StringList::new(&names)), None::<gtk4::Expression>);
+                let package_entry = Entry::new();
+                package_entry.set_placeholder_text(Some("Package"));
+                package_entry.set_hexpand(true);
+                let continue_check = CheckButton::with_label("Continue on failure");
+                continue_check.set_tooltip_text(Some("Run the next step even if this one fails"));
+                if let Some(step) = step {
+                    let selected = STEP_TYPES.iter().position(|t| *t == step.task_type);
+                    type_dropdown.set_selected(selected.unwrap_or(0) as u32);
+                    if takes_package(&step.task_type) {
+                        package_entry.set_text(&step.package_name);
+                    }
+                    continue_check.set_active(step.continue_on_failure);
+                }
+                package_entry.set_sensitive(takes_package(
+                    &STEP_TYPES[type_dropdown.selected() as usize % STEP_TYPES.len()],
+                ));
+                let entry_for_type = package_entry.clone();
+                type_dropdown.connect_selected_notify(move |dropdown| {
+                    entry_for_type.set_sensitive(takes_package(
+                        &STEP_TYPES[dropdown.selected() as usize % STEP_TYPES.len()],
+                    ));
+                });
+
+                let remove_btn = Button::from_icon_name("list-remove-symbolic");
+                remove_btn.add_css_class("flat");
+                remove_btn.set_tooltip_text(Some("Remove step"));
+                row.append(&type_dropdown);
+                row.append(&package_entry);
+                row.append(&continue_check);
+                row.append(&remove_btn);
+                steps_box.append(&row);
+
+                let steps_for_remove = steps.clone();
+                let steps_box_for_remove = steps_box.clone();
+                let row_for_remove = row.clone();
+                remove_btn.connect_clicked(move |_| {
+                    steps_box_for_remove.remove(&row_for_remove);
+                    steps_for_remove
+                        .borrow_mut()
+                        .retain(|(r, ..)| r != &row_for_remove);
+                });
+                steps
+                    .borrow_mut()
+                    .push((row, type_dropdown, package_entry, continue_check));
+            })
+        };
+
+        match &existing {
+            Some(routine) => {
+                name_entry.set_text(&routine.name);
+                for step in &routine.steps {
+                    add_step(Some(step));
+                }
+            }
+            None => add_step(None),
+        }
+
+        let add_step_btn = Button::with_label("Add Step");
+        add_step_btn.add_css_class("flat");
+        add_step_btn.set_halign(gtk4::Align::Start);
+        let add_step_for_btn = add_step.clone();
+        add_step_btn.connect_clicked(move |_| add_step_for_btn(None));
+        vbox.append(&add_step_btn);
+
+        let title = if existing.is_some() {
+            "Edit Routine"
+        } else {
+            "New Routine"
+        };
+        Self::show_settings_editor(parent, title, 560, &vbox, move || {
+            let name = name_entry.text().trim().to_string();
+            let mut routine_steps = Vec::new();
+            for (_, type_dropdown, package_entry, continue_check) in steps.borrow().iter() {
+                let task_type =
+                    STEP_TYPES[type_dropdown.selected() as usize % STEP_TYPES.len()].clone();
+                let package_name = if takes_package(&task_type) {
+                    package_entry.text().trim().to_string()
+                } else {
+                    "system".to_string()
+                };
+                if package_name.is_empty() {
+                    return Err(format!("{} needs a package name", task_type.label()));
+                }
+                routine_steps.push(crate::settings::RoutineStep {
+                    task_type,
+                    package_name,
+                    continue_on_failure: continue_check.is_active(),
+                });
+            }
+            if name.is_empty() || routine_steps.is_empty() {
+                return Err("Give the routine a name and at least one step".to_string());
+            }
+
+            let routine = crate::settings::Routine {
+                name,
+                steps: routine_steps,
+            };
+            crate::settings::update(|s| match index {
+                Some(i) if i < s.routines.len() => s.routines[i] = routine,
+                _ => s.routines.push(routine),
+            });
+            on_saved();
+            Ok(())
+        });
+    }
+
+    fn fill_hook_list(window: &Window, list: &ListBox) {
+        let rows = crate::settings::get()
+            .task_hooks
+            .iter()
+            .map(|hook| {
+                let types: Vec<&str> = hook.task_types.iter().map(TaskType::label).collect();
+                (
+                    hook.name.clone(),
+                    format!(
+                        "{} {}: {}",
+                        hook.stage.label(),
+                        types.join(", "),
+                        hook.command
+                    ),
+                )
+            })
+            .collect();
+        Self::fill_settings_list(
+            window,
+            list,
+            "hook",
+            rows,
+            Self::fill_hook_list,
+            |window, index, on_saved| Self::show_hook_editor(window, Some(index), on_saved),
+            |s, index| {
+                if index < s.task_hooks.len() {
+                    s.task_hooks.remove(index);
+                }
+            },
+        );
+    }
+
+    /// Create a hook (`index` is `None`) or edit the one at `index` in settings.
+    fn show_hook_editor<F>(parent: &Window, index: Option<usize>, on_saved: F)
+    where
+        F: Fn() + 'static,
+    {
+        use crate::settings::{HookStage, TaskHook};
+        const STAGES: [HookStage; 2] = [HookStage::Before, HookStage::After];
+
+        let existing = index.and_then(|i| crate::settings::get().task_hooks.get(i).cloned());
+        let vbox = Box::new(Orientation::Vertical, 12);
+
+        let name_entry = Entry::new();
+        name_entry.set_placeholder_text(Some("Hook name"));
+        vbox.append(&name_entry);
+
+        let command_entry = Entry::new();
+        command_entry.set_placeholder_text(Some("Command, e.g. snapper create -d parut"));
+        command_entry.add_css_class("monospace");
+        vbox.append(&command_entry);
+
+        let stage_dropdown = DropDown::from_strings(&["Before the task", "After the task"]);
+        stage_dropdown.set_halign(gtk4::Align::Start);
+        vbox.append(&stage_dropdown);
+
+        let types_label = Label::new(Some("Run for"));
+        types_label.set_halign(gtk4::Align::Start);
+        types_label.add_css_class("heading");
+        vbox.append(&types_label);
+        let types_grid = gtk4::Grid::new();
+        types_grid.set_row_spacing(4);
+        types_grid.set_column_spacing(12);
+        let type_checks: Vec<(TaskType, CheckButton)> = TaskType::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, task_type)| {
+                let check = CheckButton::with_label(task_type.label());
+                types_grid.attach(&check, (i % 3) as i32, (i / 3) as i32, 1, 1);
+                (task_type.clone(), check)
+            })
+            .collect();
+        vbox.append(&types_grid);
+
+        let abort_check = CheckButton::with_label("Fail the task if the hook fails");
+        abort_check.set_tooltip_text(Some(
+            "A failing before-hook stops the task from running; a failing after-hook marks it as failed",
+        ));
+        vbox.append(&abort_check);
+
+        let hint = Label::new(Some(
+            "Runs with sh -c. PARUT_TASK_TYPE, PARUT_PACKAGES and PARUT_HOOK_STAGE describe the task.",
+        ));
+        hint.add_css_class("dim-label");
+        hint.add_css_class("caption");
+        hint.set_wrap(true);
+        hint.set_xalign(0.0);
+        vbox.append(&hint);
+
+        if let Some(hook) = &existing {
+            name_entry.set_text(&hook.name);
+            command_entry.set_text(&hook.command);
+            stage_dropdown
+                .set_selected(STAGES.iter().position(|s| *s == hook.stage).unwrap_or(0) as u32);
+            for (task_type, check) in &type_checks {
+                check.set_active(hook.task_types.contains(task_type));
+            }
+            abort_check.set_active(hook.abort_on_failure);
+        }
+
+        let title = if existing.is_some() {
+            "Edit Hook"
+        } else {
+            "New Hook"
+        };
+        Self::show_settings_editor(parent, title, 520, &vbox, move || {
+            let name = name_entry.text().trim().to_string();
+            let command = command_entry.text().trim().to_string();
+            let task_types: Vec<TaskType> = type_checks
+                .iter()
+                .filter(|(_, check)| check.is_active())
+                .map(|(task_type, _)| task_type.clone())
+                .collect();
+            if name.is_empty() || command.is_empty() || task_types.is_empty() {
+                return Err(
+                    "Give the hook a name, a command and at least one task type".to_string()
+                );
+            }
+
+            let hook = TaskHook {
+                name,
+                command,
+                stage: STAGES[stage_dropdown.selected() as usize % STAGES.len()],
+                task_types,
+                abort_on_failure: abort_check.is_active(),
+            };
+            crate::settings::update(|s| match index {
+                Some(i) if i < s.task_hooks.len() => s.task_hooks[i] = hook,
+                _ => s.task_hooks.push(hook),
+            });
+            on_saved();
+            Ok(())
+        });
+    }
+
     fn show_settings_dialog(parent_window: &(impl IsA<gtk4::Window> + gtk4::prelude::WidgetExt)) {
         let window = Window::builder()
             .title("Preferences")
@@ -4346,6 +6555,21 @@ impl ParuGui {
         });
         row_ignored.add_suffix(&ignored_entry);
         general.add(&row_ignored);
+
+        let row_aur_url = ActionRow::new();
+        row_aur_url.set_title("AUR Server");
+        row_aur_url.set_subtitle("Base URL for AUR metadata and comments");
+        let aur_url_entry = Entry::new();
+        aur_url_entry.set_hexpand(true);
+        aur_url_entry.set_valign(gtk4::Align::Center);
+        aur_url_entry.set_placeholder_text(Some(crate::aur::DEFAULT_AUR_URL));
+        aur_url_entry.set_text(&current.aur_rpc_base_url);
+        aur_url_entry.connect_changed(|entry| {
+            let url = entry.text().trim().to_string();
+            crate::settings::update(|s| s.aur_rpc_base_url = url);
+        });
+        row_aur_url.add_suffix(&aur_url_entry);
+        general.add(&row_aur_url);
         prefs.add(&general);
 
         let safety = PreferencesGroup::new();
@@ -4477,8 +6701,43 @@ impl ParuGui {
         row_compact.add_suffix(&sw_compact);
         behavior.add(&row_compact);
 
+        let execution_row = ComboRow::new();
+        execution_row.set_title("Run Tasks In");
+        execution_row.set_subtitle("Built-in output streams paru into the task queue");
+        execution_row.set_model(Some(&StringList::new(&[
+            "Built-in Output",
+            "External Terminal",
+        ])));
+        execution_row.set_selected(match current.task_execution_mode.as_str() {
+            "terminal" => 1,
+            _ => 0,
+        });
+        execution_row.connect_selected_notify(|row| {
+            let value = match row.selected() {
+                1 => "terminal",
+                _ => "embedded",
+            };
+            crate::settings::update(|s| s.task_execution_mode = value.to_string());
+        });
+        style_combo_row(&execution_row);
+        behavior.add(&execution_row);
+
+        let row_prompts = ActionRow::new();
+        row_prompts.set_title("Answer paru Prompts");
+        row_prompts.set_subtitle("When off, paru runs with --noconfirm and picks defaults");
+        let sw_prompts = gtk4::Switch::new();
+        sw_prompts.set_active(current.interactive_prompts);
+        sw_prompts.connect_state_set(|_, state| {
+            crate::settings::update(|s| s.interactive_prompts = state);
+            glib::Propagation::Proceed
+        });
+        style_switch_row(&row_prompts, &sw_prompts);
+        row_prompts.add_suffix(&sw_prompts);
+        behavior.add(&row_prompts);
+
         let terminal_row = ComboRow::new();
         terminal_row.set_title("Preferred Terminal");
+        terminal_row.set_subtitle("Used when tasks run in an external terminal");
         terminal_row.set_model(Some(&StringList::new(&[
             "Auto",
             "GNOME Terminal",
@@ -4509,8 +6768,35 @@ impl ParuGui {
         style_combo_row(&terminal_row);
         behavior.add(&terminal_row);
 
+        let privilege_row = ComboRow::new();
+        privilege_row.set_title("Authenticate With");
+        privilege_row.set_subtitle("How paru gets root when no terminal emulator is installed");
+        privilege_row.set_model(Some(&StringList::new(&[
+            "Auto",
+            "sudo (password dialog)",
+            "polkit (pkexec)",
+        ])));
+        privilege_row.set_selected(match current.privilege_helper.as_str() {
+            "sudo" => 1,
+            "pkexec" => 2,
+            _ => 0,
+        });
+        privilege_row.connect_selected_notify(|row| {
+            let value = match row.selected() {
+                1 => "sudo",
+                2 => "pkexec",
+                _ => "auto",
+            };
+            crate::settings::update(|s| s.privilege_helper = value.to_string());
+        });
+        style_combo_row(&privilege_row);
+        behavior.add(&privilege_row);
+
         let parallel_row = ComboRow::new();
         parallel_row.set_title("Max Parallel Tasks");
+        parallel_row.set_subtitle(
+            "Tasks that lock the package database still run one at a time. AUR packages only build in parallel with more than one",
+        );
         parallel_row.set_model(Some(&StringList::new(&["1", "2", "3", "4"])));
         parallel_row.set_selected(match current.max_parallel_tasks {
             2 => 1,
@@ -4530,6 +6816,34 @@ impl ParuGui {
         style_combo_row(&parallel_row);
         behavior.add(&parallel_row);
 
+        let row_batch = ActionRow::new();
+        row_batch.set_title("Combine Package Tasks");
+        row_batch.set_subtitle("Run adjacent queued installs or removals as one transaction");
+        let sw_batch = gtk4::Switch::new();
+        sw_batch.set_active(current.batch_package_tasks);
+        sw_batch.connect_state_set(|_, state| {
+            crate::settings::update(|s| s.batch_package_tasks = state);
+            glib::Propagation::Proceed
+        });
+        style_switch_row(&row_batch, &sw_batch);
+        row_batch.add_suffix(&sw_batch);
+        behavior.add(&row_batch);
+
+        let row_aur_builds = ActionRow::new();
+        row_aur_builds.set_title("Build AUR Packages in Parallel");
+        row_aur_builds.set_subtitle(
+            "When installing several AUR packages, build them side by side and install them together. Needs more than one parallel task",
+        );
+        let sw_aur_builds = gtk4::Switch::new();
+        sw_aur_builds.set_active(current.parallel_aur_builds);
+        sw_aur_builds.connect_state_set(|_, state| {
+            crate::settings::update(|s| s.parallel_aur_builds = state);
+            glib::Propagation::Proceed
+        });
+        style_switch_row(&row_aur_builds, &sw_aur_builds);
+        row_aur_builds.add_suffix(&sw_aur_builds);
+        behavior.add(&row_aur_builds);
+
         let output_limit_row = ComboRow::new();
         output_limit_row.set_title("Task Output Line Limit");
         output_limit_row.set_model(Some(&StringList::new(&["100", "300", "500", "1000"])));
@@ -4628,6 +6942,98 @@ impl ParuGui {
         notifications.add(&row_task_failed);
         prefs.add(&notifications);
 
+        let timeouts = PreferencesGroup::new();
+        timeouts.set_title("Task Timeouts");
+        timeouts.set_description(Some(
+            "Cancel tasks that run longer than this and mark them as failed",
+        ));
+        style_group(&timeouts);
+        const TIMEOUT_MINUTES: [u64; 6] = [0, 15, 30, 60, 120, 240];
+        for task_type in TaskType::ALL {
+            let row = ComboRow::new();
+            row.set_title(task_type.label());
+            row.set_model(Some(&StringList::new(&[
+                "Off", "15 min", "30 min", "1 hour", "2 hours", "4 hours",
+            ])));
+            let minutes = current.timeout_minutes(&task_type).unwrap_or(0);
+            row.set_selected(
+                TIMEOUT_MINUTES
+                    .iter()
+                    .position(|m| *m == minutes)
+                    .unwrap_or(0) as u32,
+            );
+            row.connect_selected_notify(move |row| {
+                let minutes = TIMEOUT_MINUTES
+                    .get(row.selected() as usize)
+                    .copied()
+                    .unwrap_or(0);
+                crate::settings::update(|s| {
+                    s.task_timeouts.retain(|t| t.task_type != task_type);
+                    if minutes > 0 {
+                        s.task_timeouts.push(crate::settings::TaskTimeout {
+                            task_type: task_type.clone(),
+                            minutes,
+                        });
+                    }
+                });
+            });
+            style_combo_row(&row);
+            timeouts.add(&row);
+        }
+        prefs.add(&timeouts);
+
+        let routines_group = PreferencesGroup::new();
+        routines_group.set_title("Routines");
+        routines_group.set_description(Some(
+            "Named task sequences, started from Routines on the dashboard",
+        ));
+        style_group(&routines_group);
+        let routines_list = ListBox::new();
+        routines_list.add_css_class("boxed-list");
+        routines_list.set_selection_mode(gtk4::SelectionMode::None);
+        routines_group.add(&routines_list);
+        let add_routine_btn = Button::with_label("Add Routine");
+        add_routine_btn.set_halign(gtk4::Align::Start);
+        add_routine_btn.set_margin_top(8);
+        routines_group.add(&add_routine_btn);
+        Self::fill_routine_list(&window, &routines_list);
+        let window_weak = window.downgrade();
+        add_routine_btn.connect_clicked(move |_| {
+            if let Some(window) = window_weak.upgrade() {
+                let list = routines_list.clone();
+                let parent = window.clone();
+                Self::show_routine_editor(&window, None, move || {
+                    Self::fill_routine_list(&parent, &list)
+                });
+            }
+        });
+        prefs.add(&routines_group);
+
+        let hooks_group = PreferencesGroup::new();
+        hooks_group.set_title("Task Hooks");
+        hooks_group.set_description(Some(
+            "Your own commands, run before or after tasks. Their output appears in the task.",
+        ));
+        style_group(&hooks_group);
+        let hooks_list = ListBox::new();
+        hooks_list.add_css_class("boxed-list");
+        hooks_list.set_selection_mode(gtk4::SelectionMode::None);
+        hooks_group.add(&hooks_list);
+        let add_hook_btn = Button::with_label("Add Hook");
+        add_hook_btn.set_halign(gtk4::Align::Start);
+        add_hook_btn.set_margin_top(8);
+        hooks_group.add(&add_hook_btn);
+        Self::fill_hook_list(&window, &hooks_list);
+        let window_weak = window.downgrade();
+        add_hook_btn.connect_clicked(move |_| {
+            if let Some(window) = window_weak.upgrade() {
+                let list = hooks_list.clone();
+                let parent = window.clone();
+                Self::show_hook_editor(&window, None, move || Self::fill_hook_list(&parent, &list));
+            }
+        });
+        prefs.add(&hooks_group);
+
         let appearance = PreferencesGroup::new();
         appearance.set_title("Appearance");
         style_group(&appearance);